@@ -0,0 +1,166 @@
+//! Fuzzes program processing with arbitrary instruction data and
+//! synthetic accounts, any panic is a failure
+//!
+//! The accounts are serialized in the layout the runtime passes to the
+//! program entrypoint so account reallocation behaves as on chain
+
+#![no_main]
+
+use std::{mem::size_of, sync::Once};
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use solana_did_method::{event_log::SDMEventLog, id, process::process};
+use solana_program::{
+    clock::Clock,
+    entrypoint::{deserialize, MAX_PERMITTED_DATA_INCREASE, NON_DUP_MARKER, SUCCESS},
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program, sysvar,
+};
+
+/// Syscalls of the runtime the program relies on, cross program
+/// invocations succeed without effect
+struct FuzzStubs;
+
+impl SyscallStubs for FuzzStubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_log_data(&self, _fields: &[&[u8]]) {}
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Clock) = Clock::default() };
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        SUCCESS
+    }
+}
+
+/// Account address, addresses the program expects are derived so the
+/// fuzzer reaches past the account checks
+#[derive(Arbitrary, Debug)]
+enum FuzzKey {
+    Any([u8; 32]),
+    Program,
+    SystemProgram,
+    Instructions,
+    Did([u8; 32]),
+    EventLog([u8; 32]),
+}
+
+impl FuzzKey {
+    fn pubkey(&self) -> Pubkey {
+        match self {
+            Self::Any(key) => Pubkey::new_from_array(*key),
+            Self::Program => id(),
+            Self::SystemProgram => system_program::id(),
+            Self::Instructions => sysvar::instructions::id(),
+            Self::Did(prefix) => Pubkey::find_program_address(&[prefix], &id()).0,
+            Self::EventLog(prefix) => SDMEventLog::address(prefix, &id()).0,
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+enum FuzzAccount {
+    /// Repeats a preceding account
+    Duplicate(u8),
+    Account {
+        key: FuzzKey,
+        owned: bool,
+        is_signer: bool,
+        is_writable: bool,
+        lamports: u64,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Arbitrary, Debug)]
+struct FuzzInput {
+    accounts: Vec<FuzzAccount>,
+    instruction_data: Vec<u8>,
+}
+
+/// Serializes the input as the runtime serializes the entrypoint input
+fn serialize(input: &FuzzInput) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    buffer.extend_from_slice(&(input.accounts.len() as u64).to_le_bytes());
+    for (index, account) in input.accounts.iter().enumerate() {
+        match account {
+            FuzzAccount::Duplicate(of) if index > 0 => {
+                buffer.push(*of % index as u8);
+                buffer.extend_from_slice(&[0u8; 7]);
+            }
+            FuzzAccount::Duplicate(_) => serialize_account(
+                &mut buffer,
+                &Pubkey::default(),
+                &system_program::id(),
+                (false, false),
+                0,
+                &[],
+            ),
+            FuzzAccount::Account {
+                key,
+                owned,
+                is_signer,
+                is_writable,
+                lamports,
+                data,
+            } => serialize_account(
+                &mut buffer,
+                &key.pubkey(),
+                &if *owned { id() } else { system_program::id() },
+                (*is_signer, *is_writable),
+                *lamports,
+                data,
+            ),
+        }
+    }
+    buffer.extend_from_slice(&(input.instruction_data.len() as u64).to_le_bytes());
+    buffer.extend_from_slice(&input.instruction_data);
+    buffer.extend_from_slice(id().as_ref());
+    buffer
+}
+
+fn serialize_account(
+    buffer: &mut Vec<u8>,
+    key: &Pubkey,
+    owner: &Pubkey,
+    (is_signer, is_writable): (bool, bool),
+    lamports: u64,
+    data: &[u8],
+) {
+    buffer.extend_from_slice(&[NON_DUP_MARKER, is_signer as u8, is_writable as u8, 0]);
+    // Original data length, written on deserialize
+    buffer.extend_from_slice(&[0u8; 4]);
+    buffer.extend_from_slice(key.as_ref());
+    buffer.extend_from_slice(owner.as_ref());
+    buffer.extend_from_slice(&lamports.to_le_bytes());
+    buffer.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    buffer.extend_from_slice(data);
+    buffer.resize(buffer.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+    buffer.resize((buffer.len() + 7) / 8 * 8, 0);
+    // Rent epoch
+    buffer.extend_from_slice(&0u64.to_le_bytes());
+}
+
+static STUBS: Once = Once::new();
+
+fuzz_target!(|input: FuzzInput| {
+    STUBS.call_once(|| {
+        set_syscall_stubs(Box::new(FuzzStubs));
+    });
+    let serialized = serialize(&input);
+    // The entrypoint input is aligned as the runtime aligns it
+    let mut aligned = vec![0u64; (serialized.len() + size_of::<u64>() - 1) / size_of::<u64>()];
+    let bytes = aligned.as_mut_ptr() as *mut u8;
+    unsafe {
+        std::ptr::copy_nonoverlapping(serialized.as_ptr(), bytes, serialized.len());
+        let (program_id, accounts, instruction_data) = deserialize(bytes);
+        let _ = process(program_id, &accounts, instruction_data);
+    }
+});
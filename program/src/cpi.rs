@@ -0,0 +1,61 @@
+//! @brief Cross-program invocation of the DID program
+//!
+//! Programs gate their instructions on control of a DID by invoking
+//! `SDMVerifyController`, which fails the invoking instruction unless the
+//! signer is the authority or a current Ed25519 key of the active DID.
+//! Integrators depend on this crate with the `no-entrypoint` feature
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+    program_error::ProgramError,
+};
+
+use crate::{
+    id,
+    instruction::{DIDVerifyController, SDMInstruction},
+};
+
+/// Verifies the signer controls the DID of the prefix, the program is
+/// the DID program account and the DID its PDA
+pub fn verify_controller<'a>(
+    program: &AccountInfo<'a>,
+    signer: &AccountInfo<'a>,
+    did: &AccountInfo<'a>,
+    prefix: &[u8; 32],
+) -> ProgramResult {
+    verify_controller_signed(program, signer, did, prefix, &[])
+}
+
+/// Verifies a PDA of the invoking program signing with the seeds
+/// controls the DID of the prefix. Fails with `IncorrectProgramId` unless
+/// the program is the DID program
+pub fn verify_controller_signed<'a>(
+    program: &AccountInfo<'a>,
+    signer: &AccountInfo<'a>,
+    did: &AccountInfo<'a>,
+    prefix: &[u8; 32],
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    // Any other program could approve the signer
+    if program.key != &id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    // The DID account is passed as given, the DID program verifies it is
+    // the DID of the prefix
+    let instruction = Instruction::new_with_borsh(
+        *program.key,
+        &SDMInstruction::SDMVerifyController(DIDVerifyController { prefix: *prefix }),
+        vec![
+            AccountMeta::new_readonly(*signer.key, true),
+            AccountMeta::new_readonly(*did.key, false),
+        ],
+    );
+    invoke_signed(
+        &instruction,
+        &[signer.clone(), did.clone(), program.clone()],
+        signer_seeds,
+    )
+}
@@ -21,6 +21,100 @@ pub enum SDMProgramError {
     OwnerNotSignerError,
     #[error("Authority not valid for DID")]
     InvalidAuthority,
+    #[error("Signature verification instruction not found")]
+    MissingSignatureInstruction,
+    #[error("Signature verification instruction is malformed")]
+    InvalidSignatureInstruction,
+    #[error("Event signer is not the authority for DID")]
+    SignerNotAuthority,
+    #[error("Signed event type does not match instruction")]
+    EventTypeMismatch,
+    #[error("Signed event prefix does not match instruction")]
+    EventPrefixMismatch,
+    #[error("Signed event keys do not match instruction")]
+    EventKeysMismatch,
+    #[error("Signed event sequence or digests do not match instruction")]
+    EventSequenceMismatch,
+    #[error("Rotated keys do not match the pre-rotation commitment")]
+    PreRotationMismatch,
+    #[error("Event is stale, replayed or forked from the DID's latest event")]
+    EventOutOfSequence,
+    #[error("Signing threshold must be between one and the number of keys")]
+    InvalidThreshold,
+    #[error("Signed event threshold does not match instruction")]
+    EventThresholdMismatch,
+    #[error("Controller signatures do not meet the DID signing threshold")]
+    ThresholdNotMet,
+    #[error("DID account is not owned by the program")]
+    IncorrectDidOwner,
+    #[error("DID must be decommissioned before closing")]
+    DidNotDecommissioned,
+    #[error("Close recipient can not be the DID account")]
+    InvalidCloseRecipient,
+    #[error("Account must be writable")]
+    AccountNotWritable,
+    #[error("DID account is not the PDA of the prefix and bump")]
+    InvalidDidAddress,
+    #[error("Account is not the expected sysvar")]
+    InvalidSysvar,
+    #[error("Account is not the system program")]
+    InvalidSystemProgram,
+    #[error("DID account storage is not the size of the DID state")]
+    InvalidAccountSize,
+    #[error("DID has been decommissioned")]
+    DidDecommissioned,
+    #[error("Service id, type or endpoint is empty or exceeds its size limit")]
+    InvalidService,
+    #[error("DID has the maximum number of services")]
+    ServiceLimitExceeded,
+    #[error("DID already has a service with the id")]
+    ServiceAlreadyExists,
+    #[error("DID has no service with the id")]
+    ServiceNotFound,
+    #[error("Verification relationships do not match the keys or are invalid")]
+    InvalidRelationships,
+    #[error("Signed event does not carry the delegator of the instruction")]
+    EventDelegatorMismatch,
+    #[error("Delegated DIDs change keys with delegated events")]
+    DidDelegated,
+    #[error("DID is not delegated")]
+    DidNotDelegated,
+    #[error("Delegator DID account is not the delegator of the DID")]
+    InvalidDelegator,
+    #[error("Delegated event is not approved by the delegator")]
+    DelegatorNotApproved,
+    #[error("DID has been revoked")]
+    DidRevoked,
+    #[error("Event log account is not the event log of the DID")]
+    InvalidEventLog,
+    #[error("Interaction anchors no seals or more than the maximum")]
+    InvalidSeals,
+    #[error("Signed event seals do not match instruction")]
+    EventSealsMismatch,
+    #[error("Program event could not be encoded or decoded")]
+    InvalidProgramEvent,
+    #[error("Instruction data could not be decoded")]
+    InvalidInstructionData,
+    #[error("Keys are not of the key type of the DID")]
+    KeyTypeMismatch,
+    #[error("Pasta signature is malformed or does not verify")]
+    InvalidPastaSignature,
+    #[error("Pasta session account is not the session of the DID and payer")]
+    InvalidPastaSession,
+    #[error("Pasta session has not completed verifying its signatures")]
+    PastaVerificationIncomplete,
+    #[error("DID has no pending authority")]
+    NoPendingAuthority,
+    #[error("Signer is neither the authority nor a current key of the DID")]
+    SignerNotController,
+    #[error("Signed event message is not a well formed KERI event")]
+    InvalidEventMessage,
+    #[error("Signed event does not commit to the next keys of the instruction")]
+    EventCommitmentMismatch,
+    #[error("Keys must not be listed more than once")]
+    DuplicateKeys,
+    #[error("Pasta DIDs can not be delegated")]
+    PastaNotDelegable,
 }
 
 /// Enables 'into()` on custom error to convert
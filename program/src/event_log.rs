@@ -0,0 +1,128 @@
+//! @brief Append-only key event log of a DID
+//!
+//! Each DID has a companion log account derived from its prefix holding
+//! a compact record of every key event, so verifiers can replay the key
+//! event log and the seals it anchors from account data alone. Records
+//! are appended to the account data without decoding the records already
+//! logged
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{hash::hashv, pubkey::Pubkey};
+
+use crate::{error::SDMProgramError, instruction::SDMKey};
+
+/// Seed following the DID prefix in the event log address
+pub const EVENT_LOG_SEED: &[u8] = b"event_log";
+
+/// Offset of the record count, following the prefix and bump
+const RECORD_COUNT_OFFSET: usize = 33;
+/// Length of the prefix, bump and record count preceding the records
+pub const EVENT_LOG_HEADER_LEN: usize = RECORD_COUNT_OFFSET + 4;
+
+/// Key event recorded in the log
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq)]
+pub enum SDMEventType {
+    Inception,
+    Rotation,
+    DelegatedInception,
+    DelegatedRotation,
+    Decommission,
+    Interaction,
+}
+
+/// Compact record of a key event
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct SDMEventRecord {
+    pub event_type: SDMEventType,
+    pub sn: u64,
+    pub digest: [u8; 32],
+    /// Digest of each key established by the event
+    pub key_digests: Vec<[u8; 32]>,
+    /// Digests anchored by the seals of an interaction
+    pub seal_digests: Vec<[u8; 32]>,
+    /// Slot the event was recorded in
+    pub slot: u64,
+}
+
+impl SDMEventRecord {
+    pub fn new(
+        event_type: SDMEventType,
+        sn: u64,
+        digest: [u8; 32],
+        keys: &[SDMKey],
+        seals: &[[u8; 32]],
+        slot: u64,
+    ) -> Self {
+        Self {
+            event_type,
+            sn,
+            digest,
+            key_digests: keys.iter().map(key_digest).collect(),
+            seal_digests: seals.to_vec(),
+            slot,
+        }
+    }
+}
+
+/// Digest of a key recorded in the event log
+pub fn key_digest<K: AsRef<[u8]>>(key: &K) -> [u8; 32] {
+    hashv(&[key.as_ref()]).to_bytes()
+}
+
+/// Event log account state, the records in the order of the events
+#[derive(BorshDeserialize, BorshSerialize, Debug, PartialEq)]
+pub struct SDMEventLog {
+    pub prefix: [u8; 32],
+    pub bump: u8,
+    pub records: Vec<SDMEventRecord>,
+}
+
+impl SDMEventLog {
+    /// Empty log of the DID prefix
+    pub fn new(prefix: [u8; 32], bump: u8) -> Self {
+        Self {
+            prefix,
+            bump,
+            records: Vec::new(),
+        }
+    }
+
+    /// Address and bump of the event log of the DID prefix
+    pub fn address(prefix: &[u8; 32], program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[prefix, EVENT_LOG_SEED], program_id)
+    }
+
+    /// Unpacks the log with all records
+    pub fn unpack(data: &[u8]) -> Result<Self, SDMProgramError> {
+        Self::try_from_slice(data).map_err(|_| SDMProgramError::InvalidEventLog)
+    }
+
+    /// Unpacks the prefix and bump of the log without the records
+    pub fn unpack_header(data: &[u8]) -> Result<([u8; 32], u8), SDMProgramError> {
+        if data.len() < EVENT_LOG_HEADER_LEN {
+            return Err(SDMProgramError::InvalidEventLog);
+        }
+        let mut prefix = [0u8; 32];
+        prefix.copy_from_slice(&data[..32]);
+        Ok((prefix, data[32]))
+    }
+
+    /// Writes the serialized record at offset, the end of the records
+    /// logged, and counts it. The data must be sized to hold the record
+    pub fn append(data: &mut [u8], offset: usize, record: &[u8]) -> Result<(), SDMProgramError> {
+        if offset < EVENT_LOG_HEADER_LEN || data.len() != offset + record.len() {
+            return Err(SDMProgramError::InvalidEventLog);
+        }
+        let count_bytes = &mut data[RECORD_COUNT_OFFSET..EVENT_LOG_HEADER_LEN];
+        let count = u32::from_le_bytes(
+            count_bytes
+                .try_into()
+                .map_err(|_| SDMProgramError::InvalidEventLog)?,
+        )
+        .checked_add(1)
+        .ok_or(SDMProgramError::InvalidEventLog)?;
+        count_bytes.copy_from_slice(&count.to_le_bytes());
+        data[offset..].copy_from_slice(record);
+        Ok(())
+    }
+}
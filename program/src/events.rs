@@ -0,0 +1,120 @@
+//! @brief Structured program events for indexers
+//!
+//! Every state changing instruction logs a Borsh serialized event with
+//! `sol_log_data`, tagged to tell it apart from other data logged in the
+//! transaction. Off-chain consumers recover the events of the program
+//! from the log messages of a transaction with `SDMProgramEvent::from_logs`
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{log::sol_log_data, pubkey::Pubkey};
+
+use crate::{
+    error::SDMProgramError,
+    event_log::key_digest,
+    state::{SDMDid, SDMDidState},
+};
+
+/// Tag logged ahead of each event
+pub const PROGRAM_EVENT_TAG: &[u8] = b"sdm_event";
+
+/// Prefix of the log messages of data logged by a program
+const PROGRAM_DATA_LOG: &str = "Program data: ";
+
+/// Instruction that changed the DID
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq)]
+pub enum SDMEventKind {
+    Inception,
+    Rotation,
+    DelegatedInception,
+    DelegatedRotation,
+    Decommission,
+    Interaction,
+    Revocation,
+    Close,
+    Migration,
+    ServiceAdded,
+    ServiceUpdated,
+    ServiceRemoved,
+    AuthorityProposed,
+    AuthorityAccepted,
+    AuthorityCancelled,
+}
+
+/// Event describing the DID following the change
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct SDMProgramEvent {
+    pub kind: SDMEventKind,
+    pub pda: Pubkey,
+    pub prefix: [u8; 32],
+    pub state: SDMDidState,
+    pub sn: u64,
+    /// Digest of each current key
+    pub key_digests: Vec<[u8; 32]>,
+}
+
+impl SDMProgramEvent {
+    /// Event of the DID held by the PDA
+    pub fn new(kind: SDMEventKind, pda: &Pubkey, did_doc: &SDMDid) -> Self {
+        Self {
+            kind,
+            pda: *pda,
+            prefix: *did_doc.prefix(),
+            state: did_doc.did_doc.state,
+            sn: did_doc.did_doc.sn,
+            key_digests: did_doc.did_doc.keys.iter().map(key_digest).collect(),
+        }
+    }
+
+    /// Log the tagged event
+    pub fn emit(&self) -> Result<(), SDMProgramError> {
+        let data = self
+            .try_to_vec()
+            .map_err(|_| SDMProgramError::InvalidProgramEvent)?;
+        sol_log_data(&[PROGRAM_EVENT_TAG, &data]);
+        Ok(())
+    }
+
+    /// Decode the event from the fields of the data logged
+    pub fn decode(fields: &[Vec<u8>]) -> Result<Self, SDMProgramError> {
+        match fields {
+            [tag, data] if tag.as_slice() == PROGRAM_EVENT_TAG => {
+                Self::try_from_slice(data).map_err(|_| SDMProgramError::InvalidProgramEvent)
+            }
+            _ => Err(SDMProgramError::InvalidProgramEvent),
+        }
+    }
+
+    /// Decode the events logged by the program in the log messages of a
+    /// transaction, data logged by other programs is skipped
+    pub fn from_logs(program_id: &Pubkey, logs: &[String]) -> Vec<Self> {
+        let program = program_id.to_string();
+        let mut invoked = Vec::<&str>::new();
+        let mut events = Vec::new();
+        for log in logs {
+            if let Some(data) = log.strip_prefix(PROGRAM_DATA_LOG) {
+                if invoked.last() != Some(&program.as_str()) {
+                    continue;
+                }
+                let event = data
+                    .split(' ')
+                    .map(base64::decode)
+                    .collect::<Result<Vec<Vec<u8>>, _>>()
+                    .ok()
+                    .and_then(|fields| Self::decode(&fields).ok());
+                events.extend(event);
+            } else if let Some(status) = log.strip_prefix("Program ") {
+                // Track the program invoked to attribute the data logged,
+                // the log, data and return lines of programs are skipped
+                match status.split(' ').collect::<Vec<&str>>().as_slice() {
+                    [kind, ..] if kind.ends_with(':') => {}
+                    [id, "invoke", depth] if depth.starts_with('[') => invoked.push(id),
+                    [id, "success"] | [id, "failed:", ..] if invoked.last() == Some(id) => {
+                        invoked.pop();
+                    }
+                    _ => {}
+                }
+            }
+        }
+        events
+    }
+}
@@ -1,15 +1,130 @@
-//! @brief Program instruction enum
+//! @brief Program instruction enum and the instruction builders of
+//! clients
 //!
 
+use std::io::{Error, ErrorKind, Read, Write};
+
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    borsh::try_from_slice_unchecked, program_error::ProgramError, pubkey::Pubkey,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::{Pubkey, PUBKEY_BYTES},
+    system_program, sysvar,
+};
+
+use crate::{
+    error::SDMProgramError,
+    event_log::SDMEventLog,
+    id,
+    pasta::SDMPastaSession,
+    state::{SDMRevocationReason, SDMService},
 };
 
+/// Key type of the DID keys. Controller signatures of Ed25519 keys are
+/// verified by Ed25519 instructions and those of secp256k1 keys by
+/// secp256k1 instructions, whose signature data must be in the
+/// instruction itself. Pasta signatures are verified by the program
+/// over several transactions of a Pasta session
 #[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq)]
 pub enum SMDKeyType {
     Ed25519,
     PASTA,
+    Secp256k1,
+}
+
+/// Length of a compressed secp256k1 public key
+pub const SECP256K1_PUBKEY_BYTES: usize = 33;
+
+/// Compressed secp256k1 public key
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Secp256k1Pubkey(pub [u8; SECP256K1_PUBKEY_BYTES]);
+
+/// Borsh only derives arrays of select lengths, the key is
+/// serialized as its 33 bytes
+impl BorshSerialize for Secp256k1Pubkey {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&self.0)
+    }
+}
+
+impl BorshDeserialize for Secp256k1Pubkey {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        let mut key = [0u8; SECP256K1_PUBKEY_BYTES];
+        buf.read_exact(&mut key)
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "Unexpected length of input"))?;
+        Ok(Self(key))
+    }
+}
+
+impl AsRef<[u8]> for Secp256k1Pubkey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// A DID key, Ed25519 and Pasta keys are stored as 32 byte keys and
+/// secp256k1 keys as compressed keys
+#[derive(
+    BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum SDMKey {
+    Pubkey(Pubkey),
+    Secp256k1(Secp256k1Pubkey),
+}
+
+impl SDMKey {
+    /// Indicates the key is stored as the key type requires
+    pub fn is_keytype(&self, keytype: SMDKeyType) -> bool {
+        matches!(
+            (self, keytype),
+            (Self::Pubkey(_), SMDKeyType::Ed25519 | SMDKeyType::PASTA)
+                | (Self::Secp256k1(_), SMDKeyType::Secp256k1)
+        )
+    }
+}
+
+impl AsRef<[u8]> for SDMKey {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            Self::Pubkey(key) => key.as_ref(),
+            Self::Secp256k1(key) => key.as_ref(),
+        }
+    }
+}
+
+impl From<Pubkey> for SDMKey {
+    fn from(key: Pubkey) -> Self {
+        Self::Pubkey(key)
+    }
+}
+
+impl From<Secp256k1Pubkey> for SDMKey {
+    fn from(key: Secp256k1Pubkey) -> Self {
+        Self::Secp256k1(key)
+    }
+}
+
+/// Keys are distinguished by their length
+impl TryFrom<&[u8]> for SDMKey {
+    type Error = SDMProgramError;
+
+    fn try_from(key: &[u8]) -> Result<Self, Self::Error> {
+        match key.len() {
+            PUBKEY_BYTES => Ok(Self::Pubkey(Pubkey::new(key))),
+            SECP256K1_PUBKEY_BYTES => {
+                let mut compressed = [0u8; SECP256K1_PUBKEY_BYTES];
+                compressed.copy_from_slice(key);
+                Ok(Self::Secp256k1(Secp256k1Pubkey(compressed)))
+            }
+            _ => Err(SDMProgramError::KeyTypeMismatch),
+        }
+    }
+}
+
+impl PartialEq<Pubkey> for SDMKey {
+    fn eq(&self, other: &Pubkey) -> bool {
+        matches!(self, Self::Pubkey(key) if key == other)
+    }
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
@@ -17,23 +132,126 @@ pub struct DIDInception {
     pub keytype: SMDKeyType,
     pub prefix: [u8; 32],
     pub bump: u8,
-    pub keys: Vec<Pubkey>,
+    pub keys: Vec<SDMKey>,
+    /// Verification relationship bitmask of each key
+    pub relationships: Vec<u8>,
+    pub threshold: u64,
+    pub next_digest: [u8; 32],
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct DIDDelegatedInception {
+    /// Prefix of the delegator DID
+    pub delegator: [u8; 32],
+    pub inception: DIDInception,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
 pub struct DIDRotation {
     pub keytype: SMDKeyType,
     pub prefix: [u8; 32],
-    pub keys: Vec<Pubkey>,
+    pub prior_digest: [u8; 32],
+    pub digest: [u8; 32],
+    pub sn: u64,
+    pub keys: Vec<SDMKey>,
+    /// Verification relationship bitmask of each key, if not provided
+    /// the relationships are preserved by key position
+    pub relationships: Option<Vec<u8>>,
+    pub threshold: u64,
+    pub next_digest: [u8; 32],
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct DIDInteraction {
+    pub keytype: SMDKeyType,
+    pub prefix: [u8; 32],
+    pub prior_digest: [u8; 32],
+    pub digest: [u8; 32],
+    pub sn: u64,
+    /// Digests anchored by the digest seals of the event
+    pub seals: Vec<[u8; 32]>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct DIDRevoke {
+    pub keytype: SMDKeyType,
+    pub prefix: [u8; 32],
+    pub reason: SDMRevocationReason,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
 pub struct DIDDecommission {
     pub keytype: SMDKeyType,
     pub prefix: [u8; 32],
+    pub prior_digest: [u8; 32],
+    pub digest: [u8; 32],
+    pub sn: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct DIDProposeAuthority {
+    pub keytype: SMDKeyType,
+    pub prefix: [u8; 32],
+    /// The authority to transfer the DID to
+    pub authority: Pubkey,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct DIDAuthorityTransfer {
+    pub keytype: SMDKeyType,
+    pub prefix: [u8; 32],
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct DIDVerifyController {
+    pub prefix: [u8; 32],
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct DIDClose {
+    pub force: bool,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct DIDService {
+    pub keytype: SMDKeyType,
+    pub prefix: [u8; 32],
+    pub service: SDMService,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct DIDServiceRemove {
+    pub keytype: SMDKeyType,
+    pub prefix: [u8; 32],
+    pub id: String,
+}
+
+/// Schnorr signature of the event by a current Pasta key of the DID
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct PastaSignature {
+    /// Position of the signing key in the current keys
+    pub key_index: u8,
+    /// The y coordinate of the signing key, which spares decompressing
+    /// the key on chain
+    pub key_y: [u8; 32],
+    /// Compressed nonce point
+    pub r: [u8; 32],
+    pub s: [u8; 32],
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct DIDPastaSignatures {
+    pub keytype: SMDKeyType,
+    pub prefix: [u8; 32],
+    /// The signed event
+    pub message: Vec<u8>,
+    pub signatures: Vec<PastaSignature>,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Debug, PartialEq)]
+/// Funding and storage of a new DID account, storage must be the
+/// exact size of the DID state. The payer tops the account up to rent
+/// exemption of the storage, rent is informational
 pub struct InitializeDidAccount {
     pub rent: u64,
     pub storage: u64,
@@ -41,6 +259,11 @@ pub struct InitializeDidAccount {
 
 #[derive(BorshDeserialize, BorshSerialize, Debug, PartialEq)]
 /// All custom program instructions
+///
+/// Instructions share the order of the accounts they have in common,
+/// the payer, the authorizing account, the DID PDA, the system program
+/// and the instructions sysvar, followed by the accounts particular to
+/// the instruction
 pub enum SDMInstruction {
     /// Initialize a DID account
     /// Accounts expected by this insruction
@@ -53,9 +276,16 @@ pub enum SDMInstruction {
     // SDMInitialize(InitializeDidAccount),
     /// Sets a new accounts Inception Event
     /// Accounts expected by this insruction
-    /// 0. `[writeable]` signable]` Authorizing account
-    /// 1. `[writeable]` The new DID PDA
+    /// 0. `[writeable, signable]` Paying account, funds the new DID PDA
+    ///    and event log
+    /// 1. `[signable]` Authorizing account, the authority of the new DID
+    /// 2. `[writeable]` The new DID PDA
+    /// 3. `[]` The system program
+    /// 4. `[]` The instructions sysvar
+    /// 5. `[writeable]` The DID event log PDA, created by the payer
     ///
+    /// Must be preceded by an Ed25519 instruction with the authority's
+    /// signature of the inception event
     /// The inception data includes
     /// 0. InceptionDidAccount details information about the PDA creation
     /// 1. DIDInception is the payload containing the DID active keys
@@ -63,16 +293,41 @@ pub enum SDMInstruction {
     SDMInception(InitializeDidAccount, DIDInception),
     /// Rotate DID public keys
     /// Accounts expected by this instruction
-    /// 0. `[writeable, signable]` Authorizing account
-    /// 1. `[writeable]` The DID PDA
+    /// 0. `[writeable, signable]` Paying account, pays for growth and is
+    ///    refunded on shrink of the DID PDA
+    /// 1. `[signable]` Authorizing account
+    /// 2. `[writeable]` The DID PDA
+    /// 3. `[]` The system program
+    /// 4. `[]` The instructions sysvar
+    /// 5. `[writeable]` The DID event log PDA, created by the payer when
+    ///    the DID has no log
+    ///
+    /// Must be preceded by an Ed25519 instruction with the authority's
+    /// signature of the rotation event and signatures of the same event
+    /// by at least threshold current keys. Pasta DIDs rotate with
+    /// SDMPastaRotation
     ///
     /// The rotation data includes
     /// 0. DIDRotation with verifying information and new keys
     SDMRotation(DIDRotation),
     /// Decommission DID public keys
     /// Accounts expected by this instruction
-    /// 0. `[writeable, signable]` Authorizing account
-    /// 1. `[writeable]` The DID PDA
+    /// 0. `[writeable, signable]` Paying account, pays for growth and is
+    ///    refunded on shrink of the DID PDA
+    /// 1. `[signable]` Authorizing account
+    /// 2. `[writeable]` The DID PDA
+    /// 3. `[]` The system program
+    /// 4. `[]` The instructions sysvar
+    /// 5. `[writeable]` The DID event log PDA, created by the payer when
+    ///    the DID has no log
+    /// 6. `[writeable]` The Pasta session PDA of the DID and payer, of
+    ///    Pasta DIDs only
+    ///
+    /// Must be preceded by an Ed25519 instruction with the authority's
+    /// signature of the decommission (empty key rotation) event. The
+    /// same event must be signed by at least threshold current keys,
+    /// Pasta signatures verified by the completed session which closes
+    /// to the payer
     ///
     /// The decommission data includes
     /// 0. DIDDecommission with verifying information and new keys
@@ -81,21 +336,746 @@ pub enum SDMInstruction {
     /// Accounts expected by this instruction
     /// 0. `[writeable, signable]` Authorizing account
     /// 1. `[writeable]` The DID PDA
-    SDMClose,
+    /// 2. `[writeable]` The recipient of the DID account lamports
+    /// 3. `[]` The instructions sysvar
+    /// 4. `[writeable]` The DID event log PDA
+    /// 5. `[writeable]` The Pasta session PDA of the DID and authority,
+    ///    of Pasta DIDs with current keys only
+    ///
+    /// The event log closes with the DID, its lamports also go to the
+    /// recipient. The DID must be decommissioned unless force closed. DIDs with
+    /// current keys require signatures of the latest event digest by at
+    /// least threshold current keys, in preceding signature instructions
+    /// or, for Pasta DIDs, verified by the completed session which closes
+    /// to the authority
+    ///
+    /// The close data includes
+    /// 0. DIDClose with the force close flag
+    SDMClose(DIDClose),
+    /// Upgrade the DID account state to the current version and resize
+    /// the account to the exact size of the state
+    /// Accounts expected by this instruction
+    /// 0. `[writeable, signable]` Paying account, pays for growth and is
+    ///    refunded on shrink of the DID PDA
+    /// 1. `[signable]` Authorizing account
+    /// 2. `[writeable]` The DID PDA
+    /// 3. `[]` The system program
+    SDMMigrate,
+    /// Publish a service endpoint of the DID
+    /// Accounts expected by this instruction
+    /// 0. `[writeable, signable]` Paying account, pays for growth and is
+    ///    refunded on shrink of the DID PDA
+    /// 1. `[signable]` Authorizing account
+    /// 2. `[writeable]` The DID PDA
+    /// 3. `[]` The system program
+    /// 4. `[]` The instructions sysvar
+    /// 5. `[writeable]` The Pasta session PDA of the DID and payer, of
+    ///    Pasta DIDs only
+    ///
+    /// Must be preceded by an Ed25519 instruction with the authority's
+    /// signature of the service message of this instruction data. The
+    /// same message must be signed by at least threshold current keys,
+    /// Pasta signatures verified by the completed session which closes
+    /// to the payer
+    ///
+    /// The service data includes
+    /// 0. DIDService with the service of an unused id
+    SDMAddService(DIDService),
+    /// Replace the type and endpoint of a service of the DID
+    /// Accounts and signatures expected are those of SDMAddService
+    ///
+    /// The service data includes
+    /// 0. DIDService with the service of an existing id
+    SDMUpdateService(DIDService),
+    /// Remove a service of the DID
+    /// Accounts and signatures expected are those of SDMAddService
+    ///
+    /// The service data includes
+    /// 0. DIDServiceRemove with the id of the service
+    SDMRemoveService(DIDServiceRemove),
+    /// Sets a new accounts Delegated Inception Event
+    /// Accounts expected by this insruction
+    /// 0. `[writeable, signable]` Paying account, funds the new DID PDA
+    ///    and event log
+    /// 1. `[signable]` Authorizing account, the authority of the new DID
+    /// 2. `[writeable]` The new DID PDA
+    /// 3. `[]` The system program
+    /// 4. `[]` The instructions sysvar
+    /// 5. `[]` The delegator DID PDA
+    /// 6. `[writeable]` The DID event log PDA, created by the payer
+    ///
+    /// Must be preceded by Ed25519 instructions with the signatures of
+    /// the delegated inception event by the authority and, approving the
+    /// delegation, by the delegator's authority, and signatures of the
+    /// same event by at least threshold of the delegator's current keys.
+    /// Pasta delegators can not approve as the signatures are not
+    /// verified by a session
+    ///
+    /// The delegated inception data includes
+    /// 0. InceptionDidAccount details information about the PDA creation
+    /// 1. DIDDelegatedInception is the delegator and the DID active keys
+    SDMDelegatedInception(InitializeDidAccount, DIDDelegatedInception),
+    /// Rotate delegated DID public keys
+    /// Accounts expected by this instruction
+    /// 0. `[writeable, signable]` Paying account, pays for growth and is
+    ///    refunded on shrink of the DID PDA
+    /// 1. `[signable]` Authorizing account
+    /// 2. `[writeable]` The DID PDA
+    /// 3. `[]` The system program
+    /// 4. `[]` The instructions sysvar
+    /// 5. `[]` The delegator DID PDA
+    /// 6. `[writeable]` The DID event log PDA, created by the payer when
+    ///    the DID has no log
+    ///
+    /// Signatures expected are those of SDMRotation over the delegated
+    /// rotation event, which is also approved as in SDMDelegatedInception
+    ///
+    /// The rotation data includes
+    /// 0. DIDRotation with verifying information and new keys
+    SDMDelegatedRotation(DIDRotation),
+    /// Revoke the DID after compromise of its keys, distinct from a
+    /// planned decommission
+    /// Accounts expected by this instruction
+    /// 0. `[writeable, signable]` Paying account, refunded on shrink of
+    ///    the DID PDA
+    /// 1. `[signable]` Authorizing account, the recovery authority of the
+    ///    DID
+    /// 2. `[writeable]` The DID PDA
+    /// 3. `[]` The system program
+    ///
+    /// Revocation requires the authority alone as the current keys may
+    /// be compromised
+    ///
+    /// The revoke data includes
+    /// 0. DIDRevoke with the reason code of the revocation
+    SDMRevoke(DIDRevoke),
+    /// Anchor seal digests to the DID without changing its keys
+    /// Accounts expected by this instruction
+    /// 0. `[writeable, signable]` Paying account, pays for growth of the
+    ///    event log
+    /// 1. `[signable]` Authorizing account
+    /// 2. `[writeable]` The DID PDA
+    /// 3. `[]` The system program
+    /// 4. `[]` The instructions sysvar
+    /// 5. `[writeable]` The DID event log PDA, created by the payer when
+    ///    the DID has no log
+    /// 6. `[writeable]` The Pasta session PDA of the DID and payer, of
+    ///    Pasta DIDs only
+    ///
+    /// Must be preceded by an Ed25519 instruction with the authority's
+    /// signature of the interaction event. The same event must be signed
+    /// by at least threshold current keys, Pasta signatures verified by
+    /// the completed session which closes to the payer
+    ///
+    /// The interaction data includes
+    /// 0. DIDInteraction with verifying information and the seal digests
+    SDMInteraction(DIDInteraction),
+    /// Start verifying Pasta controller signatures of an event or message,
+    /// replacing the payer's earlier session for the DID
+    /// Accounts expected by this instruction
+    /// 0. `[writeable, signable]` Paying account, funds the session
+    /// 1. `[]` The DID PDA
+    /// 2. `[writeable]` The Pasta session PDA of the DID and payer
+    /// 3. `[]` The system program
+    ///
+    /// Each signature is by a distinct current key of a Pasta DID. No
+    /// authority signature is required
+    ///
+    /// The signatures data includes
+    /// 0. DIDPastaSignatures with the signed event and the signatures
+    SDMPastaBegin(DIDPastaSignatures),
+    /// Advance the verification of the Pasta session signatures, fails
+    /// once a signature does not verify
+    /// Accounts expected by this instruction
+    /// 0. `[signable]` Paying account of the session
+    /// 1. `[writeable]` The Pasta session PDA of the DID and payer
+    ///
+    /// The verify data includes
+    /// 0. Steps to advance, sized to the compute budget of the transaction
+    SDMPastaVerify(u16),
+    /// Rotate Pasta DID public keys authorized by the controllers whose
+    /// signatures were verified by the Pasta session
+    /// Accounts expected by this instruction
+    /// 0. `[writeable, signable]` Paying account of the session, pays
+    ///    for growth and is refunded on shrink of the DID PDA and the
+    ///    closed session
+    /// 1. `[signable]` Authorizing account
+    /// 2. `[writeable]` The DID PDA
+    /// 3. `[]` The system program
+    /// 4. `[writeable]` The DID event log PDA, created by the payer when
+    ///    the DID has no log
+    /// 5. `[writeable]` The Pasta session PDA of the DID and payer
+    ///
+    /// The session must have verified signatures of the rotation event by
+    /// at least threshold current keys, the rotation data must match the
+    /// event. Delegated DIDs are not supported
+    ///
+    /// The rotation data includes
+    /// 0. DIDRotation with verifying information and new keys
+    SDMPastaRotation(DIDRotation),
+    /// Propose the transfer of the DID to a new authority, replacing any
+    /// pending proposal
+    /// Accounts expected by this instruction
+    /// 0. `[writeable, signable]` Paying account, pays for growth and is
+    ///    refunded on shrink of the DID PDA
+    /// 1. `[signable]` Authorizing account
+    /// 2. `[writeable]` The DID PDA
+    /// 3. `[]` The system program
+    ///
+    /// The authority alone proposes, the transfer takes effect once the
+    /// proposed authority accepts it
+    ///
+    /// The propose data includes
+    /// 0. DIDProposeAuthority with the proposed authority
+    SDMProposeAuthority(DIDProposeAuthority),
+    /// Accept the pending transfer, the signer becomes the authority
+    /// Accounts expected by this instruction
+    /// 0. `[writeable, signable]` Paying account, refunded on shrink of
+    ///    the DID PDA
+    /// 1. `[signable]` The pending authority
+    /// 2. `[writeable]` The DID PDA
+    /// 3. `[]` The system program
+    ///
+    /// The accept data includes
+    /// 0. DIDAuthorityTransfer of the DID
+    SDMAcceptAuthority(DIDAuthorityTransfer),
+    /// Withdraw the pending transfer of the DID
+    /// Accounts expected by this instruction
+    /// 0. `[writeable, signable]` Paying account, refunded on shrink of
+    ///    the DID PDA
+    /// 1. `[signable]` Authorizing account
+    /// 2. `[writeable]` The DID PDA
+    /// 3. `[]` The system program
+    ///
+    /// The cancel data includes
+    /// 0. DIDAuthorityTransfer of the DID
+    SDMCancelAuthority(DIDAuthorityTransfer),
+    /// Verify the signer controls the DID without changing it, for other
+    /// programs to invoke, see the `cpi` module
+    /// Accounts expected by this instruction
+    /// 0. `[signable]` The controlling account
+    /// 1. `[]` The DID PDA
+    ///
+    /// Succeeds only if the DID is neither decommissioned nor revoked and
+    /// the signer is its authority or one of its current keys, when the
+    /// DID's keys are Ed25519
+    ///
+    /// The verify data includes
+    /// 0. DIDVerifyController with the prefix of the DID
+    SDMVerifyController(DIDVerifyController),
 }
 
 impl SDMInstruction {
     /// Unpack inbound buffer to associated Instruction
-    /// The expected format for input is a Borsh serialized vector
+    /// The expected format for input is a Borsh serialized vector, with
+    /// no trailing bytes
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
-        // let payload = SDMInstruction::try_from_slice(input)?;
-        let payload = try_from_slice_unchecked::<SDMInstruction>(input).unwrap();
-
-        match payload {
-            SDMInstruction::SDMInception(_, _) => Ok(payload),
-            SDMInstruction::SDMRotation(_) => Ok(payload),
-            SDMInstruction::SDMDecommission(_) => Ok(payload),
-            SDMInstruction::SDMClose => Ok(payload),
-        }
+        SDMInstruction::try_from_slice(input)
+            .map_err(|_| SDMProgramError::InvalidInstructionData.into())
     }
 }
+
+/// Address and bump of the DID PDA of the prefix
+pub fn find_did_address(prefix: &[u8; 32]) -> (Pubkey, u8) {
+    find_did_address_with_program_id(prefix, &id())
+}
+
+/// Address and bump of the DID PDA of the prefix for the program
+pub fn find_did_address_with_program_id(prefix: &[u8; 32], program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[prefix], program_id)
+}
+
+/// Accounts of an instruction on a DID, the payer, the authority, the
+/// DID PDA, the system program and the instructions sysvar
+fn did_accounts(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    prefix: &[u8; 32],
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(
+            find_did_address_with_program_id(prefix, program_id).0,
+            false,
+        ),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+    ]
+}
+
+/// The DID accounts followed by the DID event log
+fn logged_accounts(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    prefix: &[u8; 32],
+) -> Vec<AccountMeta> {
+    let mut accounts = did_accounts(program_id, payer, authority, prefix);
+    accounts.push(AccountMeta::new(
+        SDMEventLog::address(prefix, program_id).0,
+        false,
+    ));
+    accounts
+}
+
+/// The logged accounts with the delegator DID of the delegator prefix
+/// preceding the event log
+fn delegated_accounts(
+    mut accounts: Vec<AccountMeta>,
+    program_id: &Pubkey,
+    delegator: &[u8; 32],
+) -> Vec<AccountMeta> {
+    accounts.insert(
+        accounts.len() - 1,
+        AccountMeta::new_readonly(
+            find_did_address_with_program_id(delegator, program_id).0,
+            false,
+        ),
+    );
+    accounts
+}
+
+/// The accounts followed, for a Pasta DID, by the Pasta session of the
+/// DID and payer verifying the controller signatures
+fn session_accounts(
+    mut accounts: Vec<AccountMeta>,
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    keytype: SMDKeyType,
+    prefix: &[u8; 32],
+) -> Vec<AccountMeta> {
+    if keytype == SMDKeyType::PASTA {
+        accounts.push(AccountMeta::new(
+            SDMPastaSession::address(prefix, payer, program_id).0,
+            false,
+        ));
+    }
+    accounts
+}
+
+/// Creates an `SDMInception` instruction
+pub fn inception(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    did_init: InitializeDidAccount,
+    did_inception: DIDInception,
+) -> Instruction {
+    let accounts = logged_accounts(program_id, payer, authority, &did_inception.prefix);
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMInception(did_init, did_inception),
+        accounts,
+    )
+}
+
+/// Creates an `SDMDelegatedInception` instruction
+pub fn delegated_inception(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    did_init: InitializeDidAccount,
+    did_inception: DIDDelegatedInception,
+) -> Instruction {
+    let accounts = delegated_accounts(
+        logged_accounts(
+            program_id,
+            payer,
+            authority,
+            &did_inception.inception.prefix,
+        ),
+        program_id,
+        &did_inception.delegator,
+    );
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMDelegatedInception(did_init, did_inception),
+        accounts,
+    )
+}
+
+/// Creates an `SDMRotation` instruction
+pub fn rotation(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    did_rotation: DIDRotation,
+) -> Instruction {
+    let accounts = logged_accounts(program_id, payer, authority, &did_rotation.prefix);
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMRotation(did_rotation),
+        accounts,
+    )
+}
+
+/// Creates an `SDMDelegatedRotation` instruction of the DID delegated by
+/// the delegator prefix
+pub fn delegated_rotation(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    delegator: &[u8; 32],
+    did_rotation: DIDRotation,
+) -> Instruction {
+    let accounts = delegated_accounts(
+        logged_accounts(program_id, payer, authority, &did_rotation.prefix),
+        program_id,
+        delegator,
+    );
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMDelegatedRotation(did_rotation),
+        accounts,
+    )
+}
+
+/// Creates an `SDMDecommission` instruction
+pub fn decommission(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    did_decommission: DIDDecommission,
+) -> Instruction {
+    let prefix = did_decommission.prefix;
+    let accounts = session_accounts(
+        logged_accounts(program_id, payer, authority, &prefix),
+        program_id,
+        payer,
+        did_decommission.keytype,
+        &prefix,
+    );
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMDecommission(did_decommission),
+        accounts,
+    )
+}
+
+/// Creates an `SDMClose` instruction of the DID of the prefix and its
+/// event log, the lamports go to the recipient
+pub fn close(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    prefix: &[u8; 32],
+    recipient: &Pubkey,
+    force: bool,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMClose(DIDClose { force }),
+        vec![
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(
+                find_did_address_with_program_id(prefix, program_id).0,
+                false,
+            ),
+            AccountMeta::new(*recipient, false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            AccountMeta::new(SDMEventLog::address(prefix, program_id).0, false),
+        ],
+    )
+}
+
+/// Creates an `SDMClose` instruction of the Pasta DID of the prefix
+/// closing the authority's Pasta session, the lamports go to the recipient
+pub fn pasta_close(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    prefix: &[u8; 32],
+    recipient: &Pubkey,
+    force: bool,
+) -> Instruction {
+    let mut instruction = close(program_id, authority, prefix, recipient, force);
+    instruction.accounts.push(AccountMeta::new(
+        SDMPastaSession::address(prefix, authority, program_id).0,
+        false,
+    ));
+    instruction
+}
+
+/// Creates an `SDMMigrate` instruction of the DID PDA
+pub fn migrate(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    did: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMMigrate,
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*did, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates an `SDMAddService` instruction
+pub fn add_service(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    did_service: DIDService,
+) -> Instruction {
+    let prefix = did_service.prefix;
+    let accounts = session_accounts(
+        did_accounts(program_id, payer, authority, &prefix),
+        program_id,
+        payer,
+        did_service.keytype,
+        &prefix,
+    );
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMAddService(did_service),
+        accounts,
+    )
+}
+
+/// Creates an `SDMUpdateService` instruction
+pub fn update_service(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    did_service: DIDService,
+) -> Instruction {
+    let prefix = did_service.prefix;
+    let accounts = session_accounts(
+        did_accounts(program_id, payer, authority, &prefix),
+        program_id,
+        payer,
+        did_service.keytype,
+        &prefix,
+    );
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMUpdateService(did_service),
+        accounts,
+    )
+}
+
+/// Creates an `SDMRemoveService` instruction
+pub fn remove_service(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    did_service: DIDServiceRemove,
+) -> Instruction {
+    let prefix = did_service.prefix;
+    let accounts = session_accounts(
+        did_accounts(program_id, payer, authority, &prefix),
+        program_id,
+        payer,
+        did_service.keytype,
+        &prefix,
+    );
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMRemoveService(did_service),
+        accounts,
+    )
+}
+
+/// Creates an `SDMRevoke` instruction
+pub fn revoke(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    did_revoke: DIDRevoke,
+) -> Instruction {
+    let did = find_did_address_with_program_id(&did_revoke.prefix, program_id).0;
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMRevoke(did_revoke),
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(did, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates an `SDMInteraction` instruction
+pub fn interaction(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    did_interaction: DIDInteraction,
+) -> Instruction {
+    let prefix = did_interaction.prefix;
+    let accounts = session_accounts(
+        logged_accounts(program_id, payer, authority, &prefix),
+        program_id,
+        payer,
+        did_interaction.keytype,
+        &prefix,
+    );
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMInteraction(did_interaction),
+        accounts,
+    )
+}
+
+/// Creates an `SDMPastaBegin` instruction of the payer's Pasta session
+pub fn pasta_begin(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    did_signatures: DIDPastaSignatures,
+) -> Instruction {
+    let prefix = did_signatures.prefix;
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMPastaBegin(did_signatures),
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(
+                find_did_address_with_program_id(&prefix, program_id).0,
+                false,
+            ),
+            AccountMeta::new(
+                SDMPastaSession::address(&prefix, payer, program_id).0,
+                false,
+            ),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates an `SDMPastaVerify` instruction of the payer's Pasta session
+/// of the DID prefix
+pub fn pasta_verify(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    prefix: &[u8; 32],
+    steps: u16,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMPastaVerify(steps),
+        vec![
+            AccountMeta::new_readonly(*payer, true),
+            AccountMeta::new(SDMPastaSession::address(prefix, payer, program_id).0, false),
+        ],
+    )
+}
+
+/// Creates an `SDMPastaRotation` instruction closing the payer's Pasta
+/// session
+pub fn pasta_rotation(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    did_rotation: DIDRotation,
+) -> Instruction {
+    let prefix = did_rotation.prefix;
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMPastaRotation(did_rotation),
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(
+                find_did_address_with_program_id(&prefix, program_id).0,
+                false,
+            ),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(SDMEventLog::address(&prefix, program_id).0, false),
+            AccountMeta::new(
+                SDMPastaSession::address(&prefix, payer, program_id).0,
+                false,
+            ),
+        ],
+    )
+}
+
+/// Accounts of an authority transfer instruction, the payer, the signer,
+/// the DID PDA and the system program
+fn authority_accounts(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    signer: &Pubkey,
+    prefix: &[u8; 32],
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(*signer, true),
+        AccountMeta::new(
+            find_did_address_with_program_id(prefix, program_id).0,
+            false,
+        ),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]
+}
+
+/// Creates an `SDMProposeAuthority` instruction
+pub fn propose_authority(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    did_propose: DIDProposeAuthority,
+) -> Instruction {
+    let accounts = authority_accounts(program_id, payer, authority, &did_propose.prefix);
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMProposeAuthority(did_propose),
+        accounts,
+    )
+}
+
+/// Creates an `SDMAcceptAuthority` instruction signed by the pending
+/// authority
+pub fn accept_authority(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    pending_authority: &Pubkey,
+    did_transfer: DIDAuthorityTransfer,
+) -> Instruction {
+    let accounts = authority_accounts(program_id, payer, pending_authority, &did_transfer.prefix);
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMAcceptAuthority(did_transfer),
+        accounts,
+    )
+}
+
+/// Creates an `SDMCancelAuthority` instruction
+pub fn cancel_authority(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    did_transfer: DIDAuthorityTransfer,
+) -> Instruction {
+    let accounts = authority_accounts(program_id, payer, authority, &did_transfer.prefix);
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMCancelAuthority(did_transfer),
+        accounts,
+    )
+}
+
+/// Creates an `SDMVerifyController` instruction of the DID of the prefix
+/// signed by the controller
+pub fn verify_controller(
+    program_id: &Pubkey,
+    controller: &Pubkey,
+    prefix: &[u8; 32],
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &SDMInstruction::SDMVerifyController(DIDVerifyController { prefix: *prefix }),
+        vec![
+            AccountMeta::new_readonly(*controller, true),
+            AccountMeta::new_readonly(
+                find_did_address_with_program_id(prefix, program_id).0,
+                false,
+            ),
+        ],
+    )
+}
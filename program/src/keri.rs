@@ -0,0 +1,257 @@
+//! @brief KERI event message matching and key commitments
+//!
+//! Events are signed in their JSON serialization. The signed message is
+//! parsed on chain and its top level fields compared with the fields
+//! expected from the instruction payload, rendered as qb64
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    error::SDMProgramError,
+    instruction::{DIDDecommission, DIDInception, DIDInteraction, DIDRotation, SDMKey, SMDKeyType},
+};
+
+/// Event type of an inception
+pub const INCEPTION_ILK: &str = "icp";
+/// Event type of a rotation, also used to decommission
+pub const ROTATION_ILK: &str = "rot";
+/// Event type of a delegated inception
+pub const DELEGATED_INCEPTION_ILK: &str = "dip";
+/// Event type of a delegated rotation
+pub const DELEGATED_ROTATION_ILK: &str = "drt";
+/// Event type of an interaction
+pub const INTERACTION_ILK: &str = "ixn";
+
+/// Derivation code of a Blake3-256 self-addressing prefix
+const SELF_ADDRESSING_CODE: &str = "E";
+
+/// Derivation code of a basic prefix for the key type
+pub fn key_code(keytype: SMDKeyType) -> &'static str {
+    match keytype {
+        SMDKeyType::Ed25519 => "D",
+        SMDKeyType::PASTA => "1AAE",
+        SMDKeyType::Secp256k1 => "1AAB",
+    }
+}
+
+/// Render raw bytes as a qualified base64 string
+fn qb64(code: &str, raw: &[u8]) -> String {
+    [code, &base64::encode_config(raw, base64::URL_SAFE_NO_PAD)].concat()
+}
+
+/// Render a self-addressing digest as a qualified base64 string
+fn digest_qb64(digest: &[u8; 32]) -> String {
+    qb64(SELF_ADDRESSING_CODE, digest)
+}
+
+/// Top level fields of a signed event message. Fields not verified on
+/// chain are ignored, a field appearing more than once fails to parse
+#[derive(Deserialize, Debug)]
+struct SignedEvent {
+    #[serde(rename = "t")]
+    ilk: String,
+    #[serde(rename = "d")]
+    digest: String,
+    #[serde(rename = "i")]
+    prefix: String,
+    #[serde(rename = "s")]
+    sn: String,
+    #[serde(rename = "p")]
+    prior_digest: Option<String>,
+    #[serde(rename = "kt")]
+    threshold: Option<String>,
+    #[serde(rename = "k")]
+    keys: Option<Vec<String>>,
+    #[serde(rename = "n")]
+    next_digest: Option<String>,
+    #[serde(rename = "di")]
+    delegator: Option<String>,
+    #[serde(rename = "a", default)]
+    seals: Vec<Value>,
+}
+
+/// Fields of a signed event that must match the instruction payload
+#[derive(Debug)]
+pub struct ExpectedEvent<'a> {
+    pub ilk: &'a str,
+    pub keytype: SMDKeyType,
+    pub prefix: &'a [u8; 32],
+    pub sn: u64,
+    pub digest: &'a [u8; 32],
+    pub prior_digest: Option<&'a [u8; 32]>,
+    pub keys: &'a [SDMKey],
+    pub threshold: u64,
+    /// Commitment to the next keys of an inception or rotation
+    pub next_digest: Option<&'a [u8; 32]>,
+    pub delegator: Option<&'a [u8; 32]>,
+    /// Digest seals of an interaction, which establishes no keys
+    pub seals: Option<&'a [[u8; 32]]>,
+}
+
+impl<'a> ExpectedEvent<'a> {
+    /// Expect the delegated inception of the inception by the delegator
+    pub fn delegated_inception(self, delegator: &'a [u8; 32]) -> Self {
+        Self {
+            ilk: DELEGATED_INCEPTION_ILK,
+            delegator: Some(delegator),
+            ..self
+        }
+    }
+    /// Expect the delegated rotation of the rotation
+    pub fn delegated_rotation(self) -> Self {
+        Self {
+            ilk: DELEGATED_ROTATION_ILK,
+            ..self
+        }
+    }
+
+    /// Verifies the signed event is of the expected type, is at the
+    /// expected position of the key event log and carries the prefix,
+    /// current keys, signing threshold and next keys commitment of the
+    /// instruction or, for an interaction, its digest seals
+    pub fn verify(&self, message: &[u8]) -> Result<(), SDMProgramError> {
+        let event = serde_json::from_slice::<SignedEvent>(message)
+            .map_err(|_| SDMProgramError::InvalidEventMessage)?;
+        if event.ilk != self.ilk {
+            return Err(SDMProgramError::EventTypeMismatch);
+        }
+        if event.prefix != digest_qb64(self.prefix) {
+            return Err(SDMProgramError::EventPrefixMismatch);
+        }
+        let position_matches = event.prior_digest == self.prior_digest.map(digest_qb64)
+            && event.sn == format!("{:x}", self.sn)
+            && event.digest == digest_qb64(self.digest);
+        if !position_matches {
+            return Err(SDMProgramError::EventSequenceMismatch);
+        }
+        if let Some(seals) = self.seals {
+            let seals = seals
+                .iter()
+                .map(|s| json!({ "d": digest_qb64(s) }))
+                .collect::<Vec<Value>>();
+            if event.seals != seals {
+                return Err(SDMProgramError::EventSealsMismatch);
+            }
+            return Ok(());
+        }
+        let keys = self
+            .keys
+            .iter()
+            .map(|k| qb64(key_code(self.keytype), k.as_ref()))
+            .collect::<Vec<String>>();
+        if event.keys != Some(keys) {
+            return Err(SDMProgramError::EventKeysMismatch);
+        }
+        if event.threshold != Some(format!("{:x}", self.threshold)) {
+            return Err(SDMProgramError::EventThresholdMismatch);
+        }
+        if let Some(next_digest) = self.next_digest {
+            if event.next_digest != Some(digest_qb64(next_digest)) {
+                return Err(SDMProgramError::EventCommitmentMismatch);
+            }
+        }
+        if event.delegator != self.delegator.map(digest_qb64) {
+            return Err(SDMProgramError::EventDelegatorMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// Inception is the first event and its digest is the prefix
+impl<'a> From<&'a DIDInception> for ExpectedEvent<'a> {
+    fn from(did: &'a DIDInception) -> Self {
+        Self {
+            ilk: INCEPTION_ILK,
+            keytype: did.keytype,
+            prefix: &did.prefix,
+            sn: 0,
+            digest: &did.prefix,
+            prior_digest: None,
+            keys: &did.keys,
+            threshold: did.threshold,
+            next_digest: Some(&did.next_digest),
+            delegator: None,
+            seals: None,
+        }
+    }
+}
+
+impl<'a> From<&'a DIDRotation> for ExpectedEvent<'a> {
+    fn from(did: &'a DIDRotation) -> Self {
+        Self {
+            ilk: ROTATION_ILK,
+            keytype: did.keytype,
+            prefix: &did.prefix,
+            sn: did.sn,
+            digest: &did.digest,
+            prior_digest: Some(&did.prior_digest),
+            keys: &did.keys,
+            threshold: did.threshold,
+            next_digest: Some(&did.next_digest),
+            delegator: None,
+            seals: None,
+        }
+    }
+}
+
+/// Decommission is a rotation to an empty set of keys with no threshold
+impl<'a> From<&'a DIDDecommission> for ExpectedEvent<'a> {
+    fn from(did: &'a DIDDecommission) -> Self {
+        Self {
+            ilk: ROTATION_ILK,
+            keytype: did.keytype,
+            prefix: &did.prefix,
+            sn: did.sn,
+            digest: &did.digest,
+            prior_digest: Some(&did.prior_digest),
+            keys: &[],
+            threshold: 0,
+            next_digest: None,
+            delegator: None,
+            seals: None,
+        }
+    }
+}
+
+/// Interaction anchors seals at the next position of the key event log
+impl<'a> From<&'a DIDInteraction> for ExpectedEvent<'a> {
+    fn from(did: &'a DIDInteraction) -> Self {
+        Self {
+            ilk: INTERACTION_ILK,
+            keytype: did.keytype,
+            prefix: &did.prefix,
+            sn: did.sn,
+            digest: &did.digest,
+            prior_digest: Some(&did.prior_digest),
+            keys: &[],
+            threshold: 0,
+            next_digest: None,
+            delegator: None,
+            seals: Some(&did.seals),
+        }
+    }
+}
+
+/// Commitment to the signing threshold and keys of the next rotation,
+/// the digest of the `n` field of inceptions and rotations. Derived as
+/// hbkr derives it, the Blake3 digest of the hex threshold folded by XOR
+/// with the Blake3 digest of each key rendered as qb64
+pub fn next_keys_digest<K: AsRef<[u8]>>(
+    keytype: SMDKeyType,
+    threshold: u64,
+    keys: &[K],
+) -> [u8; 32] {
+    let code = key_code(keytype);
+    keys.iter().fold(
+        *blake3::hash(format!("{:x}", threshold).as_bytes()).as_bytes(),
+        |mut commitment, key| {
+            let digest = blake3::hash(qb64(code, key.as_ref()).as_bytes());
+            commitment
+                .iter_mut()
+                .zip(digest.as_bytes())
+                .for_each(|(c, d)| *c ^= d);
+            commitment
+        },
+    )
+}
@@ -0,0 +1,83 @@
+//! @brief Borsh sizes of the DID account layout
+//!
+//! Sizes follow the Borsh encoding of the current `SDMDid` layout without
+//! serializing it. The program resizes DID accounts and clients fund the
+//! rent of new DID accounts with the same sizes
+
+use solana_program::pubkey::PUBKEY_BYTES;
+
+use crate::{
+    instruction::{SMDKeyType, SECP256K1_PUBKEY_BYTES},
+    state::{SDMDidState, SDMService},
+};
+
+/// Length prefix of a vector or string
+const LENGTH_PREFIX: usize = 4;
+
+/// Tag of an enum variant or option
+const TAG: usize = 1;
+
+/// Size of the fields of every DID whatever its keys and services: the
+/// initialized flag, version, key type, authority, prefix, bump, sequence
+/// number, digest, threshold, next keys digest, service changes, created
+/// and updated slots and updated timestamp
+pub const DID_FIXED_SIZE: usize =
+    1 + 2 + TAG + PUBKEY_BYTES + 32 + 1 + 8 + 32 + 8 + 32 + 8 + 8 + 8 + 8;
+
+/// Size of a DID state, revocation records its reason
+pub fn state_size(state: &SDMDidState) -> usize {
+    match state {
+        SDMDidState::Revoked(_) => TAG + 1,
+        _ => TAG,
+    }
+}
+
+/// Size of a key of the key type
+pub fn key_size(keytype: SMDKeyType) -> usize {
+    match keytype {
+        SMDKeyType::Secp256k1 => TAG + SECP256K1_PUBKEY_BYTES,
+        SMDKeyType::Ed25519 | SMDKeyType::PASTA => TAG + PUBKEY_BYTES,
+    }
+}
+
+/// Size of a service
+pub fn service_size(service: &SDMService) -> usize {
+    3 * LENGTH_PREFIX + service.id.len() + service.service_type.len() + service.endpoint.len()
+}
+
+/// Size of a DID account in the state with key_count keys of the key
+/// type, each with its relationships, the pending authority if a transfer
+/// is pending, the delegator if delegated and the services
+pub fn did_size(
+    state: &SDMDidState,
+    keytype: SMDKeyType,
+    key_count: usize,
+    pending: bool,
+    delegated: bool,
+    services: &[SDMService],
+) -> usize {
+    let pending_authority = TAG + if pending { PUBKEY_BYTES } else { 0 };
+    let delegator = TAG + if delegated { 32 } else { 0 };
+    let keys = LENGTH_PREFIX + key_count * key_size(keytype);
+    let relationships = LENGTH_PREFIX + key_count;
+    let services = LENGTH_PREFIX + services.iter().map(service_size).sum::<usize>();
+    DID_FIXED_SIZE
+        + state_size(state)
+        + pending_authority
+        + delegator
+        + keys
+        + relationships
+        + services
+}
+
+/// Size of a new DID account of key_count keys of the key type
+pub fn inception_size(keytype: SMDKeyType, key_count: usize, delegated: bool) -> usize {
+    did_size(
+        &SDMDidState::Inception,
+        keytype,
+        key_count,
+        false,
+        delegated,
+        &[],
+    )
+}
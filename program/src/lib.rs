@@ -1,8 +1,17 @@
 //! solana-did-method library and ID
 
+pub mod cpi;
+#[cfg(not(feature = "no-entrypoint"))]
 mod entry_point;
 pub mod error;
+pub mod event_log;
+pub mod events;
 pub mod instruction;
+pub mod keri;
+pub mod layout;
+pub mod pasta;
 pub mod process;
+pub mod sigverify;
 pub mod state;
+pub mod versions;
 solana_program::declare_id!("SDMEj57ww5W2sXXfAcS8UFGhwUHyYocEyn6VZWrJ2Yp");
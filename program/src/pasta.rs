@@ -0,0 +1,314 @@
+//! @brief Pallas Schnorr signatures verified within the compute budget
+//!
+//! Pasta keys are Pallas points in their 32 byte compressed encoding. The
+//! signature of a message by the key `P = x·G` is the compressed nonce
+//! point `R = k·G` and the scalar `s = k + e·x`, where the challenge `e`
+//! commits to R, P and the message. The signature verifies when
+//! `s·G - e·P = R`.
+//!
+//! A scalar multiplication exceeds the compute budget of a transaction so
+//! verification is resumable. The joint double and add of `s·G - e·P`
+//! accumulates a bounded number of scalar bits per instruction and is
+//! kept in a session account between transactions. Points are never
+//! decompressed on chain, the signer provides the y coordinate of its key
+//! which must compress to the key
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use pasta_curves::{
+    arithmetic::{CurveAffine, CurveExt, FieldExt},
+    group::{
+        ff::{Field, PrimeField},
+        prime::PrimeCurveAffine,
+        Group, GroupEncoding,
+    },
+    pallas::{Affine, Base, Point, Scalar},
+};
+use solana_program::{hash::hashv, pubkey::Pubkey};
+
+use crate::{
+    error::SDMProgramError,
+    instruction::{PastaSignature, SDMKey},
+};
+
+/// Seed following the DID prefix in the Pasta session address, itself
+/// followed by the payer of the session
+pub const PASTA_SESSION_SEED: &[u8] = b"pasta_session";
+
+/// Bits of a Pallas scalar, each a step of the verification
+pub const PASTA_SCALAR_BITS: u16 = 255;
+
+/// Compute units of a verification step, a doubling and the additions
+/// of the bits set. The step and base units are set to the units
+/// `cargo test-bpf -- --nocapture pasta_compute` derives from full and
+/// partial verify instructions plus a margin of ten percent, the
+/// benchmark fails when either is below its derived units or above
+/// them with the margin. The values are to be replaced by the first
+/// run of the benchmark against the BPF program
+pub const PASTA_STEP_UNITS: u32 = 20_000;
+
+/// Compute units of a verify instruction besides its steps, loading and
+/// storing the session and the curve checks of each signature
+pub const PASTA_VERIFY_BASE_UNITS: u32 = 60_000;
+
+/// Compute unit limit a transaction may request
+pub const MAX_TRANSACTION_UNITS: u32 = 1_400_000;
+
+/// Domain of the challenge hash
+const CHALLENGE_DOMAIN: &[u8] = b"sdm_pasta_challenge";
+
+/// Domain of the deterministic nonce hash
+const NONCE_DOMAIN: &[u8] = b"sdm_pasta_nonce";
+
+/// Steps of a verify instruction that fit the compute units requested
+/// by its transaction
+pub fn steps_within(compute_units: u32) -> u16 {
+    let steps = compute_units.saturating_sub(PASTA_VERIFY_BASE_UNITS) / PASTA_STEP_UNITS;
+    steps.min(u16::MAX as u32) as u16
+}
+
+/// Scalar reduced from the 512 bit digest of the domain separated data
+fn wide_scalar(data: &[&[u8]]) -> Scalar {
+    let mut wide = [0u8; 64];
+    for (half, chunk) in wide.chunks_exact_mut(32).enumerate() {
+        let half = [half as u8];
+        let mut parts = vec![&half[..]];
+        parts.extend_from_slice(data);
+        chunk.copy_from_slice(&hashv(&parts).to_bytes());
+    }
+    Scalar::from_bytes_wide(&wide)
+}
+
+/// Challenge committing to the nonce point, the key and the message
+pub fn challenge(r: &[u8; 32], key: &[u8; 32], message: &[u8]) -> Scalar {
+    wide_scalar(&[CHALLENGE_DOMAIN, r, key, message])
+}
+
+/// Signs the message with the secret scalar of the key at key_index of
+/// the DID's current keys. The nonce is derived from the secret and the
+/// message
+pub fn sign(
+    secret: &[u8; 32],
+    key_index: u8,
+    message: &[u8],
+) -> Result<PastaSignature, SDMProgramError> {
+    let secret = Option::<Scalar>::from(Scalar::from_repr(*secret))
+        .filter(|s| !bool::from(s.is_zero()))
+        .ok_or(SDMProgramError::DidInvalidKey)?;
+    let key = Point::generator() * secret;
+    let key_y = Option::<Base>::from(Affine::from(key).coordinates().map(|c| *c.y()))
+        .ok_or(SDMProgramError::DidInvalidKey)?;
+    let nonce = wide_scalar(&[NONCE_DOMAIN, &secret.to_repr(), message]);
+    let r = (Point::generator() * nonce).to_bytes();
+    let e = challenge(&r, &key.to_bytes(), message);
+    Ok(PastaSignature {
+        key_index,
+        key_y: key_y.to_repr(),
+        r,
+        s: (nonce + e * secret).to_repr(),
+    })
+}
+
+/// The key point of the compressed key and the y coordinate provided,
+/// which must be on the curve and compress to the key
+fn key_point(key: &[u8; 32], key_y: &[u8; 32]) -> Result<Affine, SDMProgramError> {
+    let mut x = *key;
+    x[31] &= 0x7f;
+    let point = Option::<Base>::from(Base::from_repr(x))
+        .zip(Option::<Base>::from(Base::from_repr(*key_y)))
+        .and_then(|(x, y)| Option::<Affine>::from(Affine::from_xy(x, y)))
+        .ok_or(SDMProgramError::InvalidPastaSignature)?;
+    if bool::from(point.is_identity()) || point.to_bytes() != *key {
+        return Err(SDMProgramError::InvalidPastaSignature);
+    }
+    Ok(point)
+}
+
+/// Indicates the bit at position of the little endian scalar
+fn bit(scalar: &[u8; 32], position: u16) -> bool {
+    (scalar[position as usize / 8] >> (position % 8)) & 1 == 1
+}
+
+/// Jacobian coordinates of the point
+fn to_jacobian(point: &Point) -> [[u8; 32]; 3] {
+    let (x, y, z) = point.jacobian_coordinates();
+    [x.to_repr(), y.to_repr(), z.to_repr()]
+}
+
+/// Point of the Jacobian coordinates, which must be on the curve
+fn from_jacobian(coordinates: &[[u8; 32]; 3]) -> Result<Point, SDMProgramError> {
+    let [x, y, z] = coordinates.map(|c| Option::<Base>::from(Base::from_repr(c)));
+    x.zip(y)
+        .zip(z)
+        .and_then(|((x, y), z)| Option::<Point>::from(Point::new_jacobian(x, y, z)))
+        .ok_or(SDMProgramError::InvalidPastaSession)
+}
+
+/// Resumable verification of a signature
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct PastaVerification {
+    pub key: [u8; 32],
+    pub key_y: [u8; 32],
+    pub r: [u8; 32],
+    pub s: [u8; 32],
+    pub e: [u8; 32],
+    /// Jacobian coordinates of `s·G - e·P` accumulated so far
+    pub acc: [[u8; 32]; 3],
+    /// Bits of the scalars left to accumulate, most significant first
+    pub remaining: u16,
+}
+
+impl PastaVerification {
+    /// Verification of the signature of the message by the key
+    pub fn new(
+        key: &[u8; 32],
+        signature: &PastaSignature,
+        message: &[u8],
+    ) -> Result<Self, SDMProgramError> {
+        key_point(key, &signature.key_y)?;
+        if bool::from(Scalar::from_repr(signature.s).is_none()) {
+            return Err(SDMProgramError::InvalidPastaSignature);
+        }
+        Ok(Self {
+            key: *key,
+            key_y: signature.key_y,
+            r: signature.r,
+            s: signature.s,
+            e: challenge(&signature.r, key, message).to_repr(),
+            acc: to_jacobian(&Point::identity()),
+            remaining: PASTA_SCALAR_BITS,
+        })
+    }
+
+    /// Indicates every bit has been accumulated and the signature verified
+    pub fn is_complete(&self) -> bool {
+        self.remaining == 0
+    }
+
+    /// Accumulates at most steps bits and returns the steps taken. Fails
+    /// when the last bit is accumulated and the result is not the nonce
+    /// point of the signature
+    pub fn advance(&mut self, steps: u16) -> Result<u16, SDMProgramError> {
+        let taken = steps.min(self.remaining);
+        if taken == 0 {
+            return Ok(0);
+        }
+        let generator = Affine::generator();
+        let neg_key = -key_point(&self.key, &self.key_y)?;
+        let mut acc = from_jacobian(&self.acc)?;
+        for _ in 0..taken {
+            self.remaining -= 1;
+            acc = acc.double();
+            if bit(&self.s, self.remaining) {
+                acc += generator;
+            }
+            if bit(&self.e, self.remaining) {
+                acc += neg_key;
+            }
+        }
+        if self.is_complete() && acc.to_bytes() != self.r {
+            return Err(SDMProgramError::InvalidPastaSignature);
+        }
+        self.acc = to_jacobian(&acc);
+        Ok(taken)
+    }
+}
+
+/// Pasta session account state, the verifications of the signatures of
+/// an event by the controllers of a DID, funded by the payer
+#[derive(BorshDeserialize, BorshSerialize, Debug, PartialEq)]
+pub struct SDMPastaSession {
+    pub prefix: [u8; 32],
+    pub bump: u8,
+    pub payer: Pubkey,
+    /// The signed event
+    pub message: Vec<u8>,
+    pub verifications: Vec<PastaVerification>,
+}
+
+impl SDMPastaSession {
+    /// Address and bump of the Pasta session of the DID prefix and payer
+    pub fn address(prefix: &[u8; 32], payer: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[prefix, PASTA_SESSION_SEED, payer.as_ref()], program_id)
+    }
+
+    /// Session of the payer verifying the signatures of the message by
+    /// distinct keys, each at the position of its signature in keys
+    pub fn new(
+        prefix: [u8; 32],
+        bump: u8,
+        payer: Pubkey,
+        keys: &[SDMKey],
+        message: Vec<u8>,
+        signatures: &[PastaSignature],
+    ) -> Result<Self, SDMProgramError> {
+        let mut indices = signatures.iter().map(|s| s.key_index).collect::<Vec<u8>>();
+        indices.sort_unstable();
+        indices.dedup();
+        if signatures.is_empty() || indices.len() != signatures.len() {
+            return Err(SDMProgramError::InvalidPastaSignature);
+        }
+        let verifications = signatures
+            .iter()
+            .map(|signature| match keys.get(signature.key_index as usize) {
+                Some(SDMKey::Pubkey(key)) => {
+                    PastaVerification::new(&key.to_bytes(), signature, &message)
+                }
+                _ => Err(SDMProgramError::InvalidPastaSignature),
+            })
+            .collect::<Result<Vec<PastaVerification>, SDMProgramError>>()?;
+        Ok(Self {
+            prefix,
+            bump,
+            payer,
+            message,
+            verifications,
+        })
+    }
+
+    /// Unpacks the session
+    pub fn unpack(data: &[u8]) -> Result<Self, SDMProgramError> {
+        Self::try_from_slice(data).map_err(|_| SDMProgramError::InvalidPastaSession)
+    }
+
+    /// Serializes the session to the account state, which must be the
+    /// size of the session
+    pub fn pack(&self, data: &mut [u8]) -> Result<(), SDMProgramError> {
+        let state = self
+            .try_to_vec()
+            .map_err(|_| SDMProgramError::InvalidPastaSession)?;
+        if data.len() != state.len() {
+            return Err(SDMProgramError::InvalidPastaSession);
+        }
+        data.copy_from_slice(&state);
+        Ok(())
+    }
+
+    /// Advances the verifications in order by at most steps in total
+    pub fn advance(&mut self, mut steps: u16) -> Result<(), SDMProgramError> {
+        for verification in self.verifications.iter_mut() {
+            if steps == 0 {
+                break;
+            }
+            steps -= verification.advance(steps)?;
+        }
+        Ok(())
+    }
+
+    /// Indicates every signature has been verified
+    pub fn is_complete(&self) -> bool {
+        self.verifications.iter().all(|v| v.is_complete())
+    }
+
+    /// Keys of the verified signatures, fails until every signature has
+    /// been verified
+    pub fn signers(&self) -> Result<Vec<SDMKey>, SDMProgramError> {
+        if !self.is_complete() {
+            return Err(SDMProgramError::PastaVerificationIncomplete);
+        }
+        Ok(self
+            .verifications
+            .iter()
+            .map(|v| SDMKey::Pubkey(Pubkey::new_from_array(v.key)))
+            .collect())
+    }
+}
@@ -1,137 +1,946 @@
 //! Program core processing module
 
 use crate::{
+    error::SDMProgramError,
+    event_log::{SDMEventLog, SDMEventRecord, SDMEventType, EVENT_LOG_SEED},
+    events::{SDMEventKind, SDMProgramEvent},
     instruction::{
-        DIDDecommission, DIDInception, DIDRotation, InitializeDidAccount, SDMInstruction,
+        find_did_address_with_program_id, DIDClose, DIDDecommission, DIDInception, DIDInteraction,
+        DIDPastaSignatures, DIDRevoke, DIDRotation, DIDVerifyController, InitializeDidAccount,
+        SDMInstruction, SDMKey, SMDKeyType,
     },
+    keri::ExpectedEvent,
+    pasta::{SDMPastaSession, PASTA_SESSION_SEED},
+    sigverify::{preceding_signed_messages, signers_of},
     state::SDMDid,
 };
 
+use borsh::BorshSerialize;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
-    msg,
-    program::invoke_signed,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
     pubkey::Pubkey,
-    system_instruction,
+    rent::Rent,
+    system_instruction, system_program,
+    sysvar::{self, Sysvar},
 };
 
-/// Checks each account to confirm it is owned by our program
-/// This function assumes that the program account is always the last
-/// in the array
-/// Change this to suite your account logic
-#[allow(dead_code)]
-fn check_account_ownership(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
-    // First account is wallet so, any subsequent in this example must be owned by the program.
-    for account in &accounts[1..] {
-        msg!("Accoumt key {:?}", account.key);
-        if account.owner != program_id {
-            return Err(ProgramError::IncorrectProgramId);
+/// Verifies the account signed the transaction
+fn verify_signer(account: &AccountInfo) -> ProgramResult {
+    if account.is_signer {
+        Ok(())
+    } else {
+        Err(ProgramError::MissingRequiredSignature)
+    }
+}
+
+/// Verifies the account is writable in the transaction
+fn verify_writable(account: &AccountInfo) -> ProgramResult {
+    if account.is_writable {
+        Ok(())
+    } else {
+        Err(SDMProgramError::AccountNotWritable.into())
+    }
+}
+
+/// Verifies the account is the system program
+fn verify_system_program(account: &AccountInfo) -> ProgramResult {
+    if system_program::check_id(account.key) {
+        Ok(())
+    } else {
+        Err(SDMProgramError::InvalidSystemProgram.into())
+    }
+}
+
+/// Verifies the account is the instructions sysvar
+fn verify_instructions_sysvar(account: &AccountInfo) -> ProgramResult {
+    if sysvar::instructions::check_id(account.key) {
+        Ok(())
+    } else {
+        Err(SDMProgramError::InvalidSysvar.into())
+    }
+}
+
+/// Verifies the account to create for a DID is writable and is the
+/// canonical PDA of the prefix
+fn verify_new_did_account(
+    pda: &AccountInfo,
+    program_id: &Pubkey,
+    prefix: &[u8; 32],
+    bump: u8,
+) -> ProgramResult {
+    verify_writable(pda)?;
+    if find_did_address_with_program_id(prefix, program_id) == (*pda.key, bump) {
+        Ok(())
+    } else {
+        Err(SDMProgramError::InvalidDidAddress.into())
+    }
+}
+
+/// Verifies an existing DID account is writable, owned by the program and
+/// derived from its stored prefix and bump and returns the DID state
+fn verify_did_account(pda: &AccountInfo, program_id: &Pubkey) -> Result<SDMDid, ProgramError> {
+    verify_writable(pda)?;
+    read_did_account(pda, program_id)
+}
+
+/// Verifies the delegator DID account is the DID of the delegator prefix
+/// and approves the delegated event signed by signers
+fn verify_delegator_account(
+    pda: &AccountInfo,
+    program_id: &Pubkey,
+    delegator: &[u8; 32],
+    signers: &[SDMKey],
+) -> ProgramResult {
+    let delegator_doc = read_did_account(pda, program_id)?;
+    if delegator_doc.prefix() != delegator {
+        return Err(SDMProgramError::InvalidDelegator.into());
+    }
+    delegator_doc.verify_approval(signers)?;
+    Ok(())
+}
+
+/// Verifies an existing DID account is owned by the program and derived
+/// from its stored prefix and bump and returns the DID state
+fn read_did_account(pda: &AccountInfo, program_id: &Pubkey) -> Result<SDMDid, ProgramError> {
+    if pda.owner != program_id {
+        return Err(SDMProgramError::IncorrectDidOwner.into());
+    }
+    let did_doc = SDMDid::unpack(&pda.try_borrow_data()?)?;
+    match Pubkey::create_program_address(&[did_doc.prefix(), &[did_doc.bump()]], program_id) {
+        Ok(address) if address == *pda.key => Ok(did_doc),
+        _ => Err(SDMProgramError::InvalidDidAddress.into()),
+    }
+}
+
+/// Resizes a program account to size, the DID account to the size of its
+/// state. The payer pays the rent of growth and is refunded the rent in
+/// excess on shrink
+fn resize_account<'a>(
+    pda: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    size: usize,
+) -> ProgramResult {
+    let required = Rent::get()?.minimum_balance(size);
+    let current = pda.lamports();
+    if required > current {
+        invoke(
+            &system_instruction::transfer(payer.key, pda.key, required - current),
+            &[payer.clone(), pda.clone(), system_program.clone()],
+        )?;
+    } else if current > required {
+        **payer.lamports.borrow_mut() = payer
+            .lamports()
+            .checked_add(current - required)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        **pda.lamports.borrow_mut() = required;
+    }
+    pda.realloc(size, false)
+}
+
+/// Creates a program PDA of size signed by its seeds. The payer tops the
+/// PDA up to rent exemption, so lamports sent to the address in advance
+/// do not prevent its creation
+fn create_pda_account<'a>(
+    pda: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    program_id: &Pubkey,
+    size: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let required = Rent::get()?.minimum_balance(size);
+    let current = pda.lamports();
+    if required > current {
+        invoke(
+            &system_instruction::transfer(payer.key, pda.key, required - current),
+            &[payer.clone(), pda.clone(), system_program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(pda.key, size as u64),
+        &[pda.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(pda.key, program_id),
+        &[pda.clone(), system_program.clone()],
+        &[seeds],
+    )
+}
+
+/// Closes a program account, its lamports go to the recipient and its
+/// data is cleared
+fn close_account(account: &AccountInfo, recipient: &AccountInfo) -> ProgramResult {
+    **recipient.lamports.borrow_mut() = recipient
+        .lamports()
+        .checked_add(account.lamports())
+        .ok_or(ProgramError::InvalidAccountData)?;
+    **account.lamports.borrow_mut() = 0;
+    account.data.borrow_mut().fill(0);
+    Ok(())
+}
+
+/// Verifies the Pasta session account is writable, owned by the program
+/// and the session of the payer derived from its stored prefix and bump
+/// and returns the session
+fn read_pasta_session(
+    session: &AccountInfo,
+    program_id: &Pubkey,
+    payer: &Pubkey,
+) -> Result<SDMPastaSession, ProgramError> {
+    verify_writable(session)?;
+    if session.owner != program_id {
+        return Err(SDMProgramError::InvalidPastaSession.into());
+    }
+    let state = SDMPastaSession::unpack(&session.try_borrow_data()?)?;
+    match Pubkey::create_program_address(
+        &[
+            &state.prefix,
+            PASTA_SESSION_SEED,
+            payer.as_ref(),
+            &[state.bump],
+        ],
+        program_id,
+    ) {
+        Ok(address) if address == *session.key && state.payer == *payer => Ok(state),
+        _ => Err(SDMProgramError::InvalidPastaSession.into()),
+    }
+}
+
+/// Appends the record of the latest event of the DID to its event log,
+/// creating the log with the first event recorded. The payer pays the
+/// rent of the log. Interactions establish no keys and record the
+/// seals they anchor
+fn append_event_log<'a>(
+    log: &AccountInfo<'a>,
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    did_doc: &SDMDid,
+    event_type: SDMEventType,
+    seals: &[[u8; 32]],
+) -> ProgramResult {
+    verify_writable(log)?;
+    let prefix = did_doc.prefix();
+    let keys: &[SDMKey] = match event_type {
+        SDMEventType::Interaction => &[],
+        _ => &did_doc.did_doc.keys,
+    };
+    let record = SDMEventRecord::new(
+        event_type,
+        did_doc.did_doc.sn,
+        did_doc.did_doc.digest,
+        keys,
+        seals,
+        Clock::get()?.slot,
+    )
+    .try_to_vec()?;
+    if log.data_is_empty() {
+        let (address, bump) = SDMEventLog::address(prefix, program_id);
+        if address != *log.key {
+            return Err(SDMProgramError::InvalidEventLog.into());
         }
+        let header = SDMEventLog::new(*prefix, bump).try_to_vec()?;
+        let size = header.len() + record.len();
+        create_pda_account(
+            log,
+            payer,
+            system_program,
+            program_id,
+            size,
+            &[prefix, EVENT_LOG_SEED, &[bump]],
+        )?;
+        let mut data = log.try_borrow_mut_data()?;
+        data.get_mut(..header.len())
+            .ok_or(SDMProgramError::InvalidEventLog)?
+            .copy_from_slice(&header);
+        SDMEventLog::append(&mut data, header.len(), &record)?;
+    } else {
+        if log.owner != program_id {
+            return Err(SDMProgramError::InvalidEventLog.into());
+        }
+        let (log_prefix, bump) = SDMEventLog::unpack_header(&log.try_borrow_data()?)?;
+        match Pubkey::create_program_address(&[prefix, EVENT_LOG_SEED, &[bump]], program_id) {
+            Ok(address) if address == *log.key && log_prefix == *prefix => (),
+            _ => return Err(SDMProgramError::InvalidEventLog.into()),
+        }
+        let offset = log.data_len();
+        resize_account(log, payer, system_program, offset + record.len())?;
+        SDMEventLog::append(&mut log.try_borrow_mut_data()?, offset, &record)?;
     }
     Ok(())
 }
 
+/// Verifies the signature instructions preceding this one carry the
+/// authority's signature over the KERI event matching the payload
+/// Returns all keys that signed the same event and the event
+fn verify_signed_event(
+    instructions: &AccountInfo,
+    authority: &Pubkey,
+    expected: ExpectedEvent,
+) -> Result<(Vec<SDMKey>, Vec<u8>), ProgramError> {
+    let signed = preceding_signed_messages(instructions)?;
+    let event = signed
+        .iter()
+        .find(|s| s.signer == *authority)
+        .ok_or(SDMProgramError::SignerNotAuthority)?;
+    expected.verify(&event.message)?;
+    Ok((signers_of(&signed, &event.message), event.message.clone()))
+}
+
+/// Signers of the message among the current keys of a Pasta DID, those
+/// verified by the payer's completed session of the message. The session
+/// is consumed, the instruction closes it to the payer once done
+fn pasta_session_signers(
+    session: &AccountInfo,
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    did_doc: &SDMDid,
+    message: &[u8],
+) -> Result<Vec<SDMKey>, ProgramError> {
+    let state = read_pasta_session(session, program_id, payer)?;
+    if state.prefix != *did_doc.prefix() || state.message != message {
+        return Err(SDMProgramError::InvalidPastaSession.into());
+    }
+    Ok(state.signers()?)
+}
+
+/// Closes the consumed Pasta session, if any, to the payer
+fn close_pasta_session(session: Option<&AccountInfo>, payer: &AccountInfo) -> ProgramResult {
+    match session {
+        Some(session) => close_account(session, payer),
+        None => Ok(()),
+    }
+}
+
 /// Inception event creates and initiates a DID PDA and
-/// stores the active public keys
+/// stores the active public keys. A delegated inception also
+/// records the delegator approving it
 fn sdm_inception(
     accounts: &[AccountInfo],
     program_id: &Pubkey,
     init: InitializeDidAccount,
     did: DIDInception,
+    delegator: Option<[u8; 32]>,
 ) -> ProgramResult {
     let account_iter = &mut accounts.iter();
-    // Signer and payer of PDA for DID
+    // Payer of PDA for DID
+    let payer = next_account_info(account_iter)?;
+    verify_signer(payer)?;
+    verify_writable(payer)?;
+    // Authority of the DID, signs for it
     let authority_account = next_account_info(account_iter)?;
-    if !authority_account.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
+    verify_signer(authority_account)?;
     // Get the did proposed account
     let pda = next_account_info(account_iter)?;
+    verify_new_did_account(pda, program_id, &did.prefix, did.bump)?;
     // Get the system program
     let sys_prog_id = next_account_info(account_iter)?;
-
-    // Create the PDA for this DID
-    let create_pda_ix = &system_instruction::create_account(
-        authority_account.key,
-        pda.key,
-        init.rent,
-        init.storage,
-        program_id,
-    );
+    verify_system_program(sys_prog_id)?;
+    // Get the instructions sysvar
+    let instructions = next_account_info(account_iter)?;
+    verify_instructions_sysvar(instructions)?;
+    // Pasta DIDs change keys in Pasta sessions, which take no approval
+    // of a delegator
+    if delegator.is_some() && did.keytype == SMDKeyType::PASTA {
+        return Err(SDMProgramError::PastaNotDelegable.into());
+    }
+    let expected = match &delegator {
+        Some(delegator) => ExpectedEvent::from(&did).delegated_inception(delegator),
+        None => ExpectedEvent::from(&did),
+    };
+    let (signers, _) = verify_signed_event(instructions, authority_account.key, expected)?;
+    if let Some(delegator) = &delegator {
+        // Get the delegator DID
+        let delegator_account = next_account_info(account_iter)?;
+        verify_delegator_account(delegator_account, program_id, delegator, &signers)?;
+    }
+    let (prefix, bump) = (did.prefix, did.bump);
+    let mut did_doc = SDMDid::from_inception(did, authority_account.key)?;
+    did_doc.set_delegator(delegator);
+    did_doc.set_created(&Clock::get()?);
+    if init.storage != did_doc.size() as u64 {
+        return Err(SDMProgramError::InvalidAccountSize.into());
+    }
 
     // Create PDA account with storage for DID
-    invoke_signed(
-        &create_pda_ix,
-        &[authority_account.clone(), pda.clone(), sys_prog_id.clone()],
-        &[&[&did.prefix, &[did.bump]]],
+    create_pda_account(
+        pda,
+        payer,
+        sys_prog_id,
+        program_id,
+        did_doc.size(),
+        &[&prefix, &[bump]],
     )?;
-    let mut my_data = pda.try_borrow_mut_data()?;
-    let mut did_doc = SDMDid::unpack_unitialized(&my_data, did, authority_account.key)?;
-    did_doc.pack(*my_data)?;
+    did_doc.pack(*pda.try_borrow_mut_data()?)?;
+    // Get the event log of the DID
+    let log = next_account_info(account_iter)?;
+    let (event_type, kind) = match delegator {
+        Some(_) => (
+            SDMEventType::DelegatedInception,
+            SDMEventKind::DelegatedInception,
+        ),
+        None => (SDMEventType::Inception, SDMEventKind::Inception),
+    };
+    append_event_log(
+        log,
+        program_id,
+        payer,
+        sys_prog_id,
+        &did_doc,
+        event_type,
+        &[],
+    )?;
+    SDMProgramEvent::new(kind, pda.key, &did_doc).emit()?;
     Ok(())
 }
 
 /// Rotation verifies the prefix and then stores a new set of public keys
-fn sdm_rotation(accounts: &[AccountInfo], _program_id: &Pubkey, did: DIDRotation) -> ProgramResult {
+/// Delegated DIDs rotate with delegated rotations approved by the delegator
+fn sdm_rotation(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    did: DIDRotation,
+    delegated: bool,
+) -> ProgramResult {
     let account_iter = &mut accounts.iter();
-    // Signer and payer of PDA for DID
+    // Payer of PDA for DID
+    let payer = next_account_info(account_iter)?;
+    verify_signer(payer)?;
+    verify_writable(payer)?;
+    // Authority of the DID, signs for it
     let authority_account = next_account_info(account_iter)?;
-    if !authority_account.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
+    verify_signer(authority_account)?;
     // Get the did proposed account
     let pda = next_account_info(account_iter)?;
-    let mut my_data = pda.try_borrow_mut_data()?;
-    let mut did_doc = SDMDid::unpack(&my_data)?;
-    did_doc.verify_authority(authority_account.key.clone())?;
+    let mut did_doc = verify_did_account(pda, program_id)?;
+    // Get the system program
+    let sys_prog_id = next_account_info(account_iter)?;
+    verify_system_program(sys_prog_id)?;
+    // Get the instructions sysvar
+    let instructions = next_account_info(account_iter)?;
+    verify_instructions_sysvar(instructions)?;
+    did_doc.verify_authority(*authority_account.key)?;
     did_doc.verify_inbound(did.keytype, did.prefix)?;
+    did_doc.verify_delegation(delegated)?;
+    let expected = match delegated {
+        true => ExpectedEvent::from(&did).delegated_rotation(),
+        false => ExpectedEvent::from(&did),
+    };
+    let (signers, _) = verify_signed_event(instructions, authority_account.key, expected)?;
+    did_doc.verify_controllers(&signers)?;
+    if let Some(delegator) = did_doc.delegator() {
+        // Get the delegator DID
+        let delegator_account = next_account_info(account_iter)?;
+        verify_delegator_account(delegator_account, program_id, delegator, &signers)?;
+    }
     did_doc.rotate_with(did)?;
-    did_doc.pack(*my_data)?;
+    // Get the event log of the DID, appended before the DID account
+    // refunds the payer
+    let log = next_account_info(account_iter)?;
+    let (event_type, kind) = match delegated {
+        true => (
+            SDMEventType::DelegatedRotation,
+            SDMEventKind::DelegatedRotation,
+        ),
+        false => (SDMEventType::Rotation, SDMEventKind::Rotation),
+    };
+    append_event_log(
+        log,
+        program_id,
+        payer,
+        sys_prog_id,
+        &did_doc,
+        event_type,
+        &[],
+    )?;
+    resize_account(pda, payer, sys_prog_id, did_doc.size())?;
+    did_doc.set_updated(&Clock::get()?);
+    did_doc.pack(*pda.try_borrow_mut_data()?)?;
+    SDMProgramEvent::new(kind, pda.key, &did_doc).emit()?;
     Ok(())
 }
 
 /// Decommission verifies the prefix and then stores a new set of public keys
 fn sdm_decommission(
     accounts: &[AccountInfo],
-    _program_id: &Pubkey,
+    program_id: &Pubkey,
     did: DIDDecommission,
 ) -> ProgramResult {
     let account_iter = &mut accounts.iter();
-    // Signer and payer of PDA for DID
+    // Payer of PDA for DID
+    let payer = next_account_info(account_iter)?;
+    verify_signer(payer)?;
+    verify_writable(payer)?;
+    // Authority of the DID, signs for it
     let authority_account = next_account_info(account_iter)?;
-    if !authority_account.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
+    verify_signer(authority_account)?;
     // Get the did proposed account
     let pda = next_account_info(account_iter)?;
-    let mut my_data = pda.try_borrow_mut_data()?;
-    let mut did_doc = SDMDid::unpack(&my_data)?;
-    did_doc.verify_authority(authority_account.key.clone())?;
+    let mut did_doc = verify_did_account(pda, program_id)?;
+    // Get the system program
+    let sys_prog_id = next_account_info(account_iter)?;
+    verify_system_program(sys_prog_id)?;
+    // Get the instructions sysvar
+    let instructions = next_account_info(account_iter)?;
+    verify_instructions_sysvar(instructions)?;
+    did_doc.verify_authority(*authority_account.key)?;
     did_doc.verify_inbound(did.keytype, did.prefix)?;
+    // Get the event log of the DID, appended before the DID account
+    // refunds the payer
+    let log = next_account_info(account_iter)?;
+    let (signers, event) = verify_signed_event(
+        instructions,
+        authority_account.key,
+        ExpectedEvent::from(&did),
+    )?;
+    let (signers, session) = match did_doc.keytype() {
+        SMDKeyType::PASTA => {
+            // Get the Pasta session of the event
+            let session = next_account_info(account_iter)?;
+            let signers = pasta_session_signers(session, program_id, payer.key, &did_doc, &event)?;
+            (signers, Some(session))
+        }
+        _ => (signers, None),
+    };
+    did_doc.verify_controllers(&signers)?;
     did_doc.decommission_with(did)?;
-    did_doc.pack(*my_data)?;
+    append_event_log(
+        log,
+        program_id,
+        payer,
+        sys_prog_id,
+        &did_doc,
+        SDMEventType::Decommission,
+        &[],
+    )?;
+    resize_account(pda, payer, sys_prog_id, did_doc.size())?;
+    close_pasta_session(session, payer)?;
+    did_doc.set_updated(&Clock::get()?);
+    did_doc.pack(*pda.try_borrow_mut_data()?)?;
+    SDMProgramEvent::new(SDMEventKind::Decommission, pda.key, &did_doc).emit()?;
     Ok(())
 }
 
-/// Close a DID account
-fn sdm_close(accounts: &[AccountInfo]) -> ProgramResult {
+/// Interaction verifies the prefix and anchors the seals of the event
+/// to the DID, recording them in the event log
+fn sdm_interaction(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    did: DIDInteraction,
+) -> ProgramResult {
     let account_iter = &mut accounts.iter();
-    // Signer and payer of PDA for DID
+    // Payer of PDA for DID
+    let payer = next_account_info(account_iter)?;
+    verify_signer(payer)?;
+    verify_writable(payer)?;
+    // Authority of the DID, signs for it
     let authority_account = next_account_info(account_iter)?;
-    if !authority_account.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
+    verify_signer(authority_account)?;
+    // Get the did proposed account
+    let pda = next_account_info(account_iter)?;
+    let mut did_doc = verify_did_account(pda, program_id)?;
+    // Get the system program
+    let sys_prog_id = next_account_info(account_iter)?;
+    verify_system_program(sys_prog_id)?;
+    // Get the instructions sysvar
+    let instructions = next_account_info(account_iter)?;
+    verify_instructions_sysvar(instructions)?;
+    did_doc.verify_authority(*authority_account.key)?;
+    did_doc.verify_inbound(did.keytype, did.prefix)?;
+    // Get the event log of the DID
+    let log = next_account_info(account_iter)?;
+    let (signers, event) = verify_signed_event(
+        instructions,
+        authority_account.key,
+        ExpectedEvent::from(&did),
+    )?;
+    let (signers, session) = match did_doc.keytype() {
+        SMDKeyType::PASTA => {
+            // Get the Pasta session of the event
+            let session = next_account_info(account_iter)?;
+            let signers = pasta_session_signers(session, program_id, payer.key, &did_doc, &event)?;
+            (signers, Some(session))
+        }
+        _ => (signers, None),
+    };
+    did_doc.verify_controllers(&signers)?;
+    did_doc.interact_with(&did)?;
+    append_event_log(
+        log,
+        program_id,
+        payer,
+        sys_prog_id,
+        &did_doc,
+        SDMEventType::Interaction,
+        &did.seals,
+    )?;
+    close_pasta_session(session, payer)?;
+    did_doc.set_updated(&Clock::get()?);
+    did_doc.pack(*pda.try_borrow_mut_data()?)?;
+    SDMProgramEvent::new(SDMEventKind::Interaction, pda.key, &did_doc).emit()?;
+    Ok(())
+}
+
+/// Revoke verifies the prefix and records the revocation reason, the
+/// authority alone may revoke as the current keys may be compromised
+fn sdm_revoke(accounts: &[AccountInfo], program_id: &Pubkey, revoke: DIDRevoke) -> ProgramResult {
+    let account_iter = &mut accounts.iter();
+    // Payer of PDA for DID
+    let payer = next_account_info(account_iter)?;
+    verify_signer(payer)?;
+    verify_writable(payer)?;
+    // Authority of the DID, signs for it
+    let authority_account = next_account_info(account_iter)?;
+    verify_signer(authority_account)?;
+    // Get the did proposed account
+    let pda = next_account_info(account_iter)?;
+    let mut did_doc = verify_did_account(pda, program_id)?;
+    // Get the system program
+    let sys_prog_id = next_account_info(account_iter)?;
+    verify_system_program(sys_prog_id)?;
+    did_doc.verify_authority(*authority_account.key)?;
+    did_doc.verify_inbound(revoke.keytype, revoke.prefix)?;
+    did_doc.revoke_with(revoke.reason)?;
+    resize_account(pda, payer, sys_prog_id, did_doc.size())?;
+    did_doc.set_updated(&Clock::get()?);
+    did_doc.pack(*pda.try_borrow_mut_data()?)?;
+    SDMProgramEvent::new(SDMEventKind::Revocation, pda.key, &did_doc).emit()?;
+    Ok(())
+}
+
+/// Close a DID account, the current keys sign the latest event digest
+/// and the account lamports go to the recipient
+fn sdm_close(accounts: &[AccountInfo], program_id: &Pubkey, close: DIDClose) -> ProgramResult {
+    let account_iter = &mut accounts.iter();
+    // Signer authorizing the close
+    let authority_account = next_account_info(account_iter)?;
+    verify_signer(authority_account)?;
+    // Get the did proposed account
+    let pda = next_account_info(account_iter)?;
+    let did_doc = verify_did_account(pda, program_id)?;
+    // Get the recipient of the lamports
+    let recipient = next_account_info(account_iter)?;
+    verify_writable(recipient)?;
+    if recipient.key == pda.key {
+        return Err(SDMProgramError::InvalidCloseRecipient.into());
+    }
+    // Get the instructions sysvar
+    let instructions = next_account_info(account_iter)?;
+    verify_instructions_sysvar(instructions)?;
+    // Get the event log of the DID, closed with the DID
+    let log = next_account_info(account_iter)?;
+    verify_writable(log)?;
+    if *log.key != SDMEventLog::address(did_doc.prefix(), program_id).0 {
+        return Err(SDMProgramError::InvalidEventLog.into());
+    }
+    if recipient.key == log.key {
+        return Err(SDMProgramError::InvalidCloseRecipient.into());
+    }
+    did_doc.verify_authority(*authority_account.key)?;
+    did_doc.verify_closeable(close.force)?;
+    let mut session = None;
+    if did_doc.requires_controllers() {
+        let digest = did_doc.did_doc.digest;
+        let signers = match did_doc.keytype() {
+            SMDKeyType::PASTA => {
+                // Get the Pasta session of the authority over the digest
+                let account = next_account_info(account_iter)?;
+                session = Some(account);
+                let authority = authority_account.key;
+                pasta_session_signers(account, program_id, authority, &did_doc, &digest)?
+            }
+            _ => signers_of(&preceding_signed_messages(instructions)?, &digest),
+        };
+        did_doc.verify_controllers(&signers)?;
     }
+    close_pasta_session(session, authority_account)?;
+    close_account(pda, recipient)?;
+    // DIDs incepted before event logs have none to close
+    if log.owner == program_id {
+        close_account(log, recipient)?;
+    }
+    // The event describes the DID as it was closed
+    SDMProgramEvent::new(SDMEventKind::Close, pda.key, &did_doc).emit()?;
+    Ok(())
+}
+
+/// Migrate upgrades a DID account persisted in a previous version
+/// without waiting for its next change
+fn sdm_migrate(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_iter = &mut accounts.iter();
+    // Payer of PDA for DID
+    let payer = next_account_info(account_iter)?;
+    verify_signer(payer)?;
+    verify_writable(payer)?;
+    // Authority of the DID, signs for it
+    let authority_account = next_account_info(account_iter)?;
+    verify_signer(authority_account)?;
     // Get the did proposed account
     let pda = next_account_info(account_iter)?;
-    let dest_starting_lamports = authority_account.lamports();
-    **authority_account.lamports.borrow_mut() =
-        dest_starting_lamports.checked_add(pda.lamports()).unwrap();
-    **pda.lamports.borrow_mut() = 0;
+    let mut did_doc = verify_did_account(pda, program_id)?;
+    // Get the system program
+    let sys_prog_id = next_account_info(account_iter)?;
+    verify_system_program(sys_prog_id)?;
+    did_doc.verify_authority(*authority_account.key)?;
+    resize_account(pda, payer, sys_prog_id, did_doc.size())?;
+    did_doc.pack(*pda.try_borrow_mut_data()?)?;
+    SDMProgramEvent::new(SDMEventKind::Migration, pda.key, &did_doc).emit()?;
+    Ok(())
+}
 
-    let mut source_data = pda.data.borrow_mut();
-    source_data.fill(0);
+/// Service changes verify the prefix and that the authority and
+/// controllers signed the service message of the instruction data, then
+/// apply the change to the services
+fn sdm_service(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    instruction_data: &[u8],
+    keytype: SMDKeyType,
+    prefix: [u8; 32],
+    kind: SDMEventKind,
+    change: impl FnOnce(&mut SDMDid) -> Result<(), SDMProgramError>,
+) -> ProgramResult {
+    let account_iter = &mut accounts.iter();
+    // Payer of PDA for DID
+    let payer = next_account_info(account_iter)?;
+    verify_signer(payer)?;
+    verify_writable(payer)?;
+    // Authority of the DID, signs for it
+    let authority_account = next_account_info(account_iter)?;
+    verify_signer(authority_account)?;
+    // Get the did proposed account
+    let pda = next_account_info(account_iter)?;
+    let mut did_doc = verify_did_account(pda, program_id)?;
+    // Get the system program
+    let sys_prog_id = next_account_info(account_iter)?;
+    verify_system_program(sys_prog_id)?;
+    // Get the instructions sysvar
+    let instructions = next_account_info(account_iter)?;
+    verify_instructions_sysvar(instructions)?;
+    did_doc.verify_authority(*authority_account.key)?;
+    did_doc.verify_inbound(keytype, prefix)?;
+    let message = did_doc.service_message(instruction_data)?;
+    let signed = preceding_signed_messages(instructions)?;
+    let signers = signers_of(&signed, &message);
+    if !signers.contains(&SDMKey::Pubkey(*authority_account.key)) {
+        return Err(SDMProgramError::SignerNotAuthority.into());
+    }
+    let (signers, session) = match did_doc.keytype() {
+        SMDKeyType::PASTA => {
+            // Get the Pasta session of the service message
+            let session = next_account_info(account_iter)?;
+            let signers =
+                pasta_session_signers(session, program_id, payer.key, &did_doc, &message)?;
+            (signers, Some(session))
+        }
+        _ => (signers, None),
+    };
+    did_doc.verify_controllers(&signers)?;
+    change(&mut did_doc)?;
+    resize_account(pda, payer, sys_prog_id, did_doc.size())?;
+    close_pasta_session(session, payer)?;
+    did_doc.set_updated(&Clock::get()?);
+    did_doc.pack(*pda.try_borrow_mut_data()?)?;
+    SDMProgramEvent::new(kind, pda.key, &did_doc).emit()?;
+    Ok(())
+}
+
+/// Authority transfers verify the prefix, then apply the change of the
+/// signer, which verifies the signer may make it
+fn sdm_authority(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    keytype: SMDKeyType,
+    prefix: [u8; 32],
+    kind: SDMEventKind,
+    change: impl FnOnce(&mut SDMDid, Pubkey) -> Result<(), SDMProgramError>,
+) -> ProgramResult {
+    let account_iter = &mut accounts.iter();
+    // Payer of PDA growth
+    let payer = next_account_info(account_iter)?;
+    verify_signer(payer)?;
+    verify_writable(payer)?;
+    // Signer of the transfer
+    let signer = next_account_info(account_iter)?;
+    verify_signer(signer)?;
+    // Get the did proposed account
+    let pda = next_account_info(account_iter)?;
+    let mut did_doc = verify_did_account(pda, program_id)?;
+    // Get the system program
+    let sys_prog_id = next_account_info(account_iter)?;
+    verify_system_program(sys_prog_id)?;
+    did_doc.verify_inbound(keytype, prefix)?;
+    change(&mut did_doc, *signer.key)?;
+    resize_account(pda, payer, sys_prog_id, did_doc.size())?;
+    did_doc.set_updated(&Clock::get()?);
+    did_doc.pack(*pda.try_borrow_mut_data()?)?;
+    SDMProgramEvent::new(kind, pda.key, &did_doc).emit()?;
+    Ok(())
+}
+
+/// Pasta session begins verifying the signatures of an event or message
+/// by the controllers of a Pasta DID. The payer funds the session,
+/// replacing its earlier session for the DID
+fn sdm_pasta_begin(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    input: DIDPastaSignatures,
+) -> ProgramResult {
+    let account_iter = &mut accounts.iter();
+    // Signer and payer of the session
+    let payer = next_account_info(account_iter)?;
+    verify_signer(payer)?;
+    verify_writable(payer)?;
+    // Get the DID account
+    let pda = next_account_info(account_iter)?;
+    let did_doc = read_did_account(pda, program_id)?;
+    // Get the session account
+    let session = next_account_info(account_iter)?;
+    verify_writable(session)?;
+    // Get the system program
+    let sys_prog_id = next_account_info(account_iter)?;
+    verify_system_program(sys_prog_id)?;
+    did_doc.verify_inbound(input.keytype, input.prefix)?;
+    if input.keytype != SMDKeyType::PASTA {
+        return Err(SDMProgramError::KeyTypeMismatch.into());
+    }
+    // Revoked DIDs keep their keys, which sign to close them
+    if did_doc.revocation().is_none() {
+        did_doc.verify_active()?;
+    }
+    did_doc.verify_delegation(false)?;
+    let (address, bump) = SDMPastaSession::address(&input.prefix, payer.key, program_id);
+    if address != *session.key {
+        return Err(SDMProgramError::InvalidPastaSession.into());
+    }
+    let state = SDMPastaSession::new(
+        input.prefix,
+        bump,
+        *payer.key,
+        &did_doc.did_doc.keys,
+        input.message,
+        &input.signatures,
+    )?;
+    let size = state.try_to_vec()?.len();
+    if session.data_is_empty() {
+        create_pda_account(
+            session,
+            payer,
+            sys_prog_id,
+            program_id,
+            size,
+            &[
+                &input.prefix,
+                PASTA_SESSION_SEED,
+                payer.key.as_ref(),
+                &[bump],
+            ],
+        )?;
+    } else {
+        if session.owner != program_id {
+            return Err(SDMProgramError::InvalidPastaSession.into());
+        }
+        resize_account(session, payer, sys_prog_id, size)?;
+    }
+    state.pack(*session.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+/// Pasta verify advances the signature verifications of the payer's
+/// session by at most the steps requested
+fn sdm_pasta_verify(accounts: &[AccountInfo], program_id: &Pubkey, steps: u16) -> ProgramResult {
+    let account_iter = &mut accounts.iter();
+    // Signer and payer of the session
+    let payer = next_account_info(account_iter)?;
+    verify_signer(payer)?;
+    // Get the session account
+    let session = next_account_info(account_iter)?;
+    let mut state = read_pasta_session(session, program_id, payer.key)?;
+    state.advance(steps)?;
+    state.pack(*session.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+/// Pasta rotation stores a new set of public keys authorized by the
+/// controllers whose signatures of the rotation event the payer's session
+/// verified, then closes the session
+fn sdm_pasta_rotation(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    did: DIDRotation,
+) -> ProgramResult {
+    let account_iter = &mut accounts.iter();
+    // Signer and payer of the session
+    let payer = next_account_info(account_iter)?;
+    verify_signer(payer)?;
+    verify_writable(payer)?;
+    // Authority of the DID, signs for it
+    let authority_account = next_account_info(account_iter)?;
+    verify_signer(authority_account)?;
+    // Get the did proposed account
+    let pda = next_account_info(account_iter)?;
+    let mut did_doc = verify_did_account(pda, program_id)?;
+    // Get the system program
+    let sys_prog_id = next_account_info(account_iter)?;
+    verify_system_program(sys_prog_id)?;
+    did_doc.verify_authority(*authority_account.key)?;
+    did_doc.verify_inbound(did.keytype, did.prefix)?;
+    if did.keytype != SMDKeyType::PASTA {
+        return Err(SDMProgramError::KeyTypeMismatch.into());
+    }
+    did_doc.verify_delegation(false)?;
+    // Get the event log of the DID
+    let log = next_account_info(account_iter)?;
+    // Get the session account
+    let session = next_account_info(account_iter)?;
+    let state = read_pasta_session(session, program_id, payer.key)?;
+    if state.prefix != did.prefix {
+        return Err(SDMProgramError::InvalidPastaSession.into());
+    }
+    ExpectedEvent::from(&did).verify(&state.message)?;
+    did_doc.verify_threshold(&state.signers()?)?;
+    did_doc.rotate_with(did)?;
+    append_event_log(
+        log,
+        program_id,
+        payer,
+        sys_prog_id,
+        &did_doc,
+        SDMEventType::Rotation,
+        &[],
+    )?;
+    resize_account(pda, payer, sys_prog_id, did_doc.size())?;
+    did_doc.set_updated(&Clock::get()?);
+    did_doc.pack(*pda.try_borrow_mut_data()?)?;
+    close_account(session, payer)?;
+    SDMProgramEvent::new(SDMEventKind::Rotation, pda.key, &did_doc).emit()?;
+    Ok(())
+}
+
+/// Verify controller reads the DID, succeeding only if the signer
+/// controls the active DID of the prefix
+fn sdm_verify_controller(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    input: DIDVerifyController,
+) -> ProgramResult {
+    let account_iter = &mut accounts.iter();
+    // Signer claiming control of the DID
+    let controller = next_account_info(account_iter)?;
+    verify_signer(controller)?;
+    // Get the DID account, which is only read
+    let pda = next_account_info(account_iter)?;
+    let did_doc = read_did_account(pda, program_id)?;
+    if *did_doc.prefix() != input.prefix {
+        return Err(SDMProgramError::InvalidDidReference.into());
+    }
+    did_doc.verify_controller(controller.key)?;
     Ok(())
 }
 
@@ -142,18 +951,89 @@ pub fn process(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    // Check the account for program ownership relation
-    // if let Err(error) = check_account_ownership(program_id, accounts) {
-    //     return Err(error);
-    // }
-
     // Unpack the inbound data, mapping instruction to appropriate function
     match SDMInstruction::unpack(instruction_data)? {
         SDMInstruction::SDMInception(init, did_content) => {
-            sdm_inception(accounts, program_id, init, did_content)
+            sdm_inception(accounts, program_id, init, did_content, None)
         }
-        SDMInstruction::SDMRotation(input) => sdm_rotation(accounts, program_id, input),
+        SDMInstruction::SDMRotation(input) => sdm_rotation(accounts, program_id, input, false),
         SDMInstruction::SDMDecommission(input) => sdm_decommission(accounts, program_id, input),
-        SDMInstruction::SDMClose => sdm_close(accounts),
+        SDMInstruction::SDMRevoke(input) => sdm_revoke(accounts, program_id, input),
+        SDMInstruction::SDMInteraction(input) => sdm_interaction(accounts, program_id, input),
+        SDMInstruction::SDMClose(input) => sdm_close(accounts, program_id, input),
+        SDMInstruction::SDMMigrate => sdm_migrate(accounts, program_id),
+        SDMInstruction::SDMAddService(input) => sdm_service(
+            accounts,
+            program_id,
+            instruction_data,
+            input.keytype,
+            input.prefix,
+            SDMEventKind::ServiceAdded,
+            |did_doc| did_doc.add_service(input.service),
+        ),
+        SDMInstruction::SDMUpdateService(input) => sdm_service(
+            accounts,
+            program_id,
+            instruction_data,
+            input.keytype,
+            input.prefix,
+            SDMEventKind::ServiceUpdated,
+            |did_doc| did_doc.update_service(input.service),
+        ),
+        SDMInstruction::SDMRemoveService(input) => sdm_service(
+            accounts,
+            program_id,
+            instruction_data,
+            input.keytype,
+            input.prefix,
+            SDMEventKind::ServiceRemoved,
+            |did_doc| did_doc.remove_service(&input.id),
+        ),
+        SDMInstruction::SDMDelegatedInception(init, input) => sdm_inception(
+            accounts,
+            program_id,
+            init,
+            input.inception,
+            Some(input.delegator),
+        ),
+        SDMInstruction::SDMDelegatedRotation(input) => {
+            sdm_rotation(accounts, program_id, input, true)
+        }
+        SDMInstruction::SDMPastaBegin(input) => sdm_pasta_begin(accounts, program_id, input),
+        SDMInstruction::SDMPastaVerify(steps) => sdm_pasta_verify(accounts, program_id, steps),
+        SDMInstruction::SDMPastaRotation(input) => sdm_pasta_rotation(accounts, program_id, input),
+        SDMInstruction::SDMProposeAuthority(input) => sdm_authority(
+            accounts,
+            program_id,
+            input.keytype,
+            input.prefix,
+            SDMEventKind::AuthorityProposed,
+            |did_doc, signer| {
+                did_doc.verify_authority(signer)?;
+                did_doc.propose_authority(input.authority)
+            },
+        ),
+        SDMInstruction::SDMAcceptAuthority(input) => sdm_authority(
+            accounts,
+            program_id,
+            input.keytype,
+            input.prefix,
+            SDMEventKind::AuthorityAccepted,
+            |did_doc, signer| did_doc.accept_authority(signer),
+        ),
+        SDMInstruction::SDMCancelAuthority(input) => sdm_authority(
+            accounts,
+            program_id,
+            input.keytype,
+            input.prefix,
+            SDMEventKind::AuthorityCancelled,
+            |did_doc, signer| {
+                did_doc.verify_authority(signer)?;
+                did_doc.cancel_authority()
+            },
+        ),
+        SDMInstruction::SDMVerifyController(input) => {
+            sdm_verify_controller(accounts, program_id, input)
+        }
     }
 }
@@ -0,0 +1,192 @@
+//! @brief Signature verification instruction introspection
+
+use solana_program::{
+    account_info::AccountInfo,
+    ed25519_program,
+    instruction::Instruction,
+    keccak,
+    program_error::ProgramError,
+    pubkey::{Pubkey, PUBKEY_BYTES},
+    secp256k1_program,
+    secp256k1_recover::{secp256k1_recover, SECP256K1_SIGNATURE_LENGTH},
+    sysvar::instructions::{load_current_index_checked, load_instruction_at_checked},
+};
+
+use crate::{
+    error::SDMProgramError,
+    instruction::{SDMKey, Secp256k1Pubkey, SECP256K1_PUBKEY_BYTES},
+};
+
+/// Ed25519 precompile instruction data layout
+const SIGNATURE_OFFSETS_START: usize = 2;
+const SIGNATURE_OFFSETS_SERIALIZED_SIZE: usize = 14;
+/// Instruction index indicating the data is in the precompile instruction itself
+const SELF_INSTRUCTION_INDEX: u16 = u16::MAX;
+
+/// Secp256k1 precompile instruction data layout
+const SECP256K1_OFFSETS_START: usize = 1;
+const SECP256K1_OFFSETS_SERIALIZED_SIZE: usize = 11;
+/// Length of the Ethereum address the precompile verifies signatures with
+const ETH_ADDRESS_BYTES: usize = 20;
+
+/// A public key and the message it signed as declared
+/// in a signature verification instruction
+#[derive(Debug, PartialEq)]
+pub struct SignedMessage {
+    pub signer: SDMKey,
+    pub message: Vec<u8>,
+}
+
+/// Read a little endian u16 at offset
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, SDMProgramError> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or(SDMProgramError::InvalidSignatureInstruction)
+}
+
+/// Read a slice of size from offset
+fn read_slice(data: &[u8], offset: u16, size: usize) -> Result<&[u8], SDMProgramError> {
+    let offset = offset as usize;
+    data.get(offset..offset + size)
+        .ok_or(SDMProgramError::InvalidSignatureInstruction)
+}
+
+/// Extracts the signer and message of each signature in an Ed25519
+/// precompile instruction. The runtime has verified the signatures before
+/// the program runs so only the declared content needs to be trusted.
+/// Offsets referencing other instructions are rejected
+pub fn ed25519_signed_messages(ix: &Instruction) -> Result<Vec<SignedMessage>, SDMProgramError> {
+    if ix.program_id != ed25519_program::id() {
+        return Err(SDMProgramError::MissingSignatureInstruction);
+    }
+    let data = &ix.data;
+    let count = *data
+        .first()
+        .ok_or(SDMProgramError::InvalidSignatureInstruction)? as usize;
+    let mut signed = Vec::with_capacity(count);
+    for index in 0..count {
+        let start = SIGNATURE_OFFSETS_START + index * SIGNATURE_OFFSETS_SERIALIZED_SIZE;
+        let signature_index = read_u16(data, start + 2)?;
+        let pubkey_offset = read_u16(data, start + 4)?;
+        let pubkey_index = read_u16(data, start + 6)?;
+        let message_offset = read_u16(data, start + 8)?;
+        let message_size = read_u16(data, start + 10)?;
+        let message_index = read_u16(data, start + 12)?;
+        if signature_index != SELF_INSTRUCTION_INDEX
+            || pubkey_index != SELF_INSTRUCTION_INDEX
+            || message_index != SELF_INSTRUCTION_INDEX
+        {
+            return Err(SDMProgramError::InvalidSignatureInstruction);
+        }
+        signed.push(SignedMessage {
+            signer: SDMKey::Pubkey(Pubkey::new(read_slice(data, pubkey_offset, PUBKEY_BYTES)?)),
+            message: read_slice(data, message_offset, message_size as usize)?.to_vec(),
+        });
+    }
+    Ok(signed)
+}
+
+/// Compress the uncompressed secp256k1 key, the x coordinate prefixed
+/// by the parity of the y coordinate
+fn compress(uncompressed: &[u8; 64]) -> Secp256k1Pubkey {
+    let mut compressed = [0u8; SECP256K1_PUBKEY_BYTES];
+    compressed[0] = 0x02 | (uncompressed[63] & 1);
+    compressed[1..].copy_from_slice(&uncompressed[..32]);
+    Secp256k1Pubkey(compressed)
+}
+
+/// Extracts the signer and message of each signature in a secp256k1
+/// precompile instruction at index. The precompile verifies signatures
+/// against Ethereum addresses, so the compressed key is recovered from
+/// the signature of the message hash and must match the address the
+/// runtime verified. Offsets referencing other instructions are rejected
+pub fn secp256k1_signed_messages(
+    ix: &Instruction,
+    index: u8,
+) -> Result<Vec<SignedMessage>, SDMProgramError> {
+    if ix.program_id != secp256k1_program::id() {
+        return Err(SDMProgramError::MissingSignatureInstruction);
+    }
+    let data = &ix.data;
+    let count = *data
+        .first()
+        .ok_or(SDMProgramError::InvalidSignatureInstruction)? as usize;
+    let read_u8 = |offset: usize| {
+        data.get(offset)
+            .copied()
+            .ok_or(SDMProgramError::InvalidSignatureInstruction)
+    };
+    let mut signed = Vec::with_capacity(count);
+    for position in 0..count {
+        let start = SECP256K1_OFFSETS_START + position * SECP256K1_OFFSETS_SERIALIZED_SIZE;
+        let signature_offset = read_u16(data, start)?;
+        let signature_index = read_u8(start + 2)?;
+        let address_offset = read_u16(data, start + 3)?;
+        let address_index = read_u8(start + 5)?;
+        let message_offset = read_u16(data, start + 6)?;
+        let message_size = read_u16(data, start + 8)?;
+        let message_index = read_u8(start + 10)?;
+        if signature_index != index || address_index != index || message_index != index {
+            return Err(SDMProgramError::InvalidSignatureInstruction);
+        }
+        let signature = read_slice(data, signature_offset, SECP256K1_SIGNATURE_LENGTH + 1)?;
+        let address = read_slice(data, address_offset, ETH_ADDRESS_BYTES)?;
+        let message = read_slice(data, message_offset, message_size as usize)?;
+        let recovered = secp256k1_recover(
+            &keccak::hash(message).to_bytes(),
+            signature[SECP256K1_SIGNATURE_LENGTH],
+            &signature[..SECP256K1_SIGNATURE_LENGTH],
+        )
+        .map_err(|_| SDMProgramError::InvalidSignatureInstruction)?
+        .to_bytes();
+        if keccak::hash(&recovered).to_bytes()[32 - ETH_ADDRESS_BYTES..] != *address {
+            return Err(SDMProgramError::InvalidSignatureInstruction);
+        }
+        signed.push(SignedMessage {
+            signer: SDMKey::Secp256k1(compress(&recovered)),
+            message: message.to_vec(),
+        });
+    }
+    Ok(signed)
+}
+
+/// Loads the Ed25519 and secp256k1 instructions preceding the one
+/// executing from the instructions sysvar account and extracts their
+/// signed messages
+pub fn preceding_signed_messages(
+    instructions: &AccountInfo,
+) -> Result<Vec<SignedMessage>, ProgramError> {
+    // The current index trails the sysvar data
+    if instructions.data_len() < 2 {
+        return Err(SDMProgramError::InvalidSignatureInstruction.into());
+    }
+    let current = load_current_index_checked(instructions)? as usize;
+    let mut signed = Vec::new();
+    let mut found = false;
+    for index in 0..current {
+        let ix = load_instruction_at_checked(index, instructions)?;
+        if ix.program_id == ed25519_program::id() {
+            signed.extend(ed25519_signed_messages(&ix)?);
+            found = true;
+        } else if ix.program_id == secp256k1_program::id() {
+            let index =
+                u8::try_from(index).map_err(|_| SDMProgramError::InvalidSignatureInstruction)?;
+            signed.extend(secp256k1_signed_messages(&ix, index)?);
+            found = true;
+        }
+    }
+    if found {
+        Ok(signed)
+    } else {
+        Err(SDMProgramError::MissingSignatureInstruction.into())
+    }
+}
+
+/// Public keys that signed the message
+pub fn signers_of(signed: &[SignedMessage], message: &[u8]) -> Vec<SDMKey> {
+    signed
+        .iter()
+        .filter(|s| s.message == message)
+        .map(|s| s.signer)
+        .collect()
+}
@@ -1,33 +1,157 @@
 //! @brief Program account state management
 
-use std::io::BufWriter;
-
 use borsh::{BorshDeserialize, BorshSerialize};
-use solana_program::{borsh::try_from_slice_unchecked, pubkey::Pubkey};
+use solana_program::{clock::Clock, hash::hashv, pubkey::Pubkey};
 
 pub use crate::error::SDMProgramError;
-use crate::instruction::{DIDDecommission, DIDInception, DIDRotation, SMDKeyType};
+use crate::{
+    instruction::{DIDDecommission, DIDInception, DIDInteraction, DIDRotation, SDMKey, SMDKeyType},
+    keri::next_keys_digest,
+    layout::did_size,
+    versions::SDMDidDocVersion,
+};
 
 /// Indicates the current version supported
 /// If different from persist state, a copy on
 /// read occurs
-const CURRENT_DATA_VERSION: u16 = 1;
+pub const CURRENT_DATA_VERSION: u16 = 2;
 
-#[derive(BorshDeserialize, BorshSerialize, Debug, PartialEq)]
+/// Digest not recorded by the layout a DID was migrated from
+pub const UNKNOWN_DIGEST: [u8; 32] = [0u8; 32];
+
+/// Verification relationship bits of a key, a key may hold several
+pub const AUTHENTICATION: u8 = 1 << 0;
+pub const ASSERTION_METHOD: u8 = 1 << 1;
+pub const KEY_AGREEMENT: u8 = 1 << 2;
+pub const CAPABILITY_INVOCATION: u8 = 1 << 3;
+pub const CAPABILITY_DELEGATION: u8 = 1 << 4;
+/// Every verification relationship
+pub const ALL_RELATIONSHIPS: u8 = AUTHENTICATION
+    | ASSERTION_METHOD
+    | KEY_AGREEMENT
+    | CAPABILITY_INVOCATION
+    | CAPABILITY_DELEGATION;
+
+/// Maximum number of services a DID publishes
+pub const MAX_SERVICES: usize = 8;
+/// Maximum length of a service id
+pub const MAX_SERVICE_ID_LEN: usize = 64;
+/// Maximum length of a service type
+pub const MAX_SERVICE_TYPE_LEN: usize = 64;
+/// Maximum length of a service endpoint URI
+pub const MAX_SERVICE_ENDPOINT_LEN: usize = 256;
+/// Maximum number of seals an interaction anchors
+pub const MAX_INTERACTION_SEALS: usize = 16;
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq)]
 pub enum SDMDidState {
     Inception,
     Rotated,
     Decommissioned,
+    Revoked(SDMRevocationReason),
+}
+
+/// Reason code recorded when the authority revokes a DID
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq)]
+pub enum SDMRevocationReason {
+    Unspecified,
+    KeyCompromise,
+    AuthorityCompromise,
+    Superseded,
+    CessationOfOperation,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Debug, PartialEq)]
 pub struct SDMDidDocCurrent {
-    state: SDMDidState,
-    keytype: SMDKeyType,
-    authority: Pubkey,
-    prefix: [u8; 32],
-    bump: u8,
-    pub keys: Vec<Pubkey>,
+    pub(crate) state: SDMDidState,
+    pub(crate) keytype: SMDKeyType,
+    pub(crate) authority: Pubkey,
+    /// Authority proposed by the authority, which becomes the authority
+    /// once it accepts
+    pub(crate) pending_authority: Option<Pubkey>,
+    pub(crate) prefix: [u8; 32],
+    pub(crate) bump: u8,
+    pub(crate) delegator: Option<[u8; 32]>,
+    pub sn: u64,
+    pub digest: [u8; 32],
+    pub keys: Vec<SDMKey>,
+    pub relationships: Vec<u8>,
+    pub threshold: u64,
+    pub next_digest: [u8; 32],
+    pub services: Vec<SDMService>,
+    /// Number of service changes, bound to the message of the next change
+    pub(crate) service_changes: u64,
+    /// Slot of the inception of the DID
+    pub(crate) created_slot: u64,
+    /// Slot and unix timestamp of the latest change of the DID
+    pub(crate) updated_slot: u64,
+    pub(crate) updated_unix_timestamp: i64,
+}
+
+/// Service endpoint published by the DID, such as a DIDComm mediator
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct SDMService {
+    pub id: String,
+    pub service_type: String,
+    pub endpoint: String,
+}
+
+impl SDMService {
+    /// Verify each field is present and within its size limit
+    pub fn verify(&self) -> Result<(), SDMProgramError> {
+        let within = |field: &String, max: usize| !field.is_empty() && field.len() <= max;
+        if within(&self.id, MAX_SERVICE_ID_LEN)
+            && within(&self.service_type, MAX_SERVICE_TYPE_LEN)
+            && within(&self.endpoint, MAX_SERVICE_ENDPOINT_LEN)
+        {
+            Ok(())
+        } else {
+            Err(SDMProgramError::InvalidService)
+        }
+    }
+}
+
+/// Verify the threshold can be met by the keys and requires
+/// at least one signature
+fn verify_threshold_bounds(keys: &[SDMKey], threshold: u64) -> Result<(), SDMProgramError> {
+    if threshold > 0 && threshold <= keys.len() as u64 {
+        Ok(())
+    } else {
+        Err(SDMProgramError::InvalidThreshold)
+    }
+}
+
+/// Verify there is a relationship bitmask for each key and that each
+/// key holds at least one known relationship
+fn verify_relationships(keys: &[SDMKey], relationships: &[u8]) -> Result<(), SDMProgramError> {
+    let valid = |r: &u8| *r != 0 && *r & !ALL_RELATIONSHIPS == 0;
+    if relationships.len() == keys.len() && relationships.iter().all(valid) {
+        Ok(())
+    } else {
+        Err(SDMProgramError::InvalidRelationships)
+    }
+}
+
+/// Verify no key is listed twice, the next keys commitment folds the key
+/// digests by XOR and a pair of equal keys would cancel out of it
+fn verify_unique_keys(keys: &[SDMKey]) -> Result<(), SDMProgramError> {
+    let mut sorted = keys.iter().collect::<Vec<&SDMKey>>();
+    sorted.sort();
+    sorted.dedup();
+    if sorted.len() == keys.len() {
+        Ok(())
+    } else {
+        Err(SDMProgramError::DuplicateKeys)
+    }
+}
+
+/// Verify each key is stored as the key type requires
+fn verify_key_types(keytype: SMDKeyType, keys: &[SDMKey]) -> Result<(), SDMProgramError> {
+    if keys.iter().all(|k| k.is_keytype(keytype)) {
+        Ok(())
+    } else {
+        Err(SDMProgramError::KeyTypeMismatch)
+    }
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
@@ -51,6 +175,71 @@ impl SDMDid {
             Err(SDMProgramError::InvalidDidReference)
         }
     }
+    /// Get the prefix the DID PDA is derived from
+    pub fn prefix(&self) -> &[u8; 32] {
+        &self.did_doc.prefix
+    }
+    /// Get the key type of the DID keys
+    pub fn keytype(&self) -> SMDKeyType {
+        self.did_doc.keytype
+    }
+    /// Get the bump seed of the DID PDA
+    pub fn bump(&self) -> u8 {
+        self.did_doc.bump
+    }
+    /// Get the prefix of the delegator of a delegated DID
+    pub fn delegator(&self) -> Option<&[u8; 32]> {
+        self.did_doc.delegator.as_ref()
+    }
+    /// Get the authority proposed to take over the DID
+    pub fn pending_authority(&self) -> Option<&Pubkey> {
+        self.did_doc.pending_authority.as_ref()
+    }
+    /// Get the slot of the inception of the DID, zero if incepted
+    /// before slots were recorded
+    pub fn created_slot(&self) -> u64 {
+        self.did_doc.created_slot
+    }
+    /// Get the slot of the latest change of the DID, zero if not changed
+    /// since slots were recorded
+    pub fn updated_slot(&self) -> u64 {
+        self.did_doc.updated_slot
+    }
+    /// Get the unix timestamp of the latest change of the DID
+    pub fn updated_unix_timestamp(&self) -> i64 {
+        self.did_doc.updated_unix_timestamp
+    }
+    /// Records the clock of the inception of the DID
+    pub fn set_created(&mut self, clock: &Clock) {
+        self.did_doc.created_slot = clock.slot;
+        self.set_updated(clock);
+    }
+    /// Records the clock of the latest change of the DID
+    pub fn set_updated(&mut self, clock: &Clock) {
+        self.did_doc.updated_slot = clock.slot;
+        self.did_doc.updated_unix_timestamp = clock.unix_timestamp;
+    }
+    /// Sets the delegator of a delegated DID
+    pub fn set_delegator(&mut self, delegator: Option<[u8; 32]>) {
+        self.did_doc.delegator = delegator
+    }
+    /// Verify delegated DIDs change keys with delegated events only
+    pub fn verify_delegation(&self, delegated: bool) -> Result<(), SDMProgramError> {
+        match (self.did_doc.delegator.is_some(), delegated) {
+            (true, false) => Err(SDMProgramError::DidDelegated),
+            (false, true) => Err(SDMProgramError::DidNotDelegated),
+            _ => Ok(()),
+        }
+    }
+    /// Verify the delegator DID approves a delegated event signed by
+    /// signers, its authority and at least threshold of its keys
+    pub fn verify_approval(&self, signers: &[SDMKey]) -> Result<(), SDMProgramError> {
+        self.verify_active()?;
+        if !signers.contains(&SDMKey::Pubkey(self.did_doc.authority)) {
+            return Err(SDMProgramError::DelegatorNotApproved);
+        }
+        self.verify_controllers(signers)
+    }
     /// Verify that the authority key is equal on the DID
     pub fn verify_authority(&self, in_auth_key: Pubkey) -> Result<(), SDMProgramError> {
         if self.did_doc.authority == in_auth_key {
@@ -59,73 +248,341 @@ impl SDMDid {
             Err(SDMProgramError::InvalidAuthority)
         }
     }
-    /// Rotate the active keys from the instruction data
+    /// Verify the DID is active and the signer is its authority or one
+    /// of its current keys. Only Ed25519 keys sign transactions, the keys
+    /// of other key types never match a signer
+    pub fn verify_controller(&self, signer: &Pubkey) -> Result<(), SDMProgramError> {
+        self.verify_active()?;
+        let key_signer = self.did_doc.keytype == SMDKeyType::Ed25519
+            && self.did_doc.keys.contains(&SDMKey::Pubkey(*signer));
+        if self.did_doc.authority == *signer || key_signer {
+            Ok(())
+        } else {
+            Err(SDMProgramError::SignerNotController)
+        }
+    }
+    /// Verify the DID has not been decommissioned or revoked
+    pub fn verify_active(&self) -> Result<(), SDMProgramError> {
+        match self.did_doc.state {
+            SDMDidState::Decommissioned => Err(SDMProgramError::DidDecommissioned),
+            SDMDidState::Revoked(_) => Err(SDMProgramError::DidRevoked),
+            _ => Ok(()),
+        }
+    }
+    /// Get the reason the DID was revoked for, if revoked
+    pub fn revocation(&self) -> Option<SDMRevocationReason> {
+        match self.did_doc.state {
+            SDMDidState::Revoked(reason) => Some(reason),
+            _ => None,
+        }
+    }
+    /// Verify the event directly follows the latest event recorded
+    /// Rejects stale, replayed or skipped sequence numbers and forks
+    /// A DID migrated without its latest digest does not know its sequence
+    /// number either, it accepts any prior digest and later sequence number
+    pub fn verify_sequence(&self, prior_digest: &[u8; 32], sn: u64) -> Result<(), SDMProgramError> {
+        let follows = if self.did_doc.digest == UNKNOWN_DIGEST {
+            sn > self.did_doc.sn
+        } else {
+            *prior_digest == self.did_doc.digest && Some(sn) == self.did_doc.sn.checked_add(1)
+        };
+        if follows {
+            Ok(())
+        } else {
+            Err(SDMProgramError::EventOutOfSequence)
+        }
+    }
+    /// Indicates controller signatures are required to change the DID
+    /// Ed25519 and secp256k1 signatures are verified by their precompiles,
+    /// Pasta signatures by Pasta sessions
+    pub fn requires_controllers(&self) -> bool {
+        self.did_doc.threshold > 0
+    }
+    /// Verify at least threshold distinct current keys are signers when
+    /// controller signatures are required
+    pub fn verify_controllers(&self, signers: &[SDMKey]) -> Result<(), SDMProgramError> {
+        if !self.requires_controllers() {
+            return Ok(());
+        }
+        self.verify_threshold(signers)
+    }
+    /// Verify at least threshold distinct current keys are signers
+    pub fn verify_threshold(&self, signers: &[SDMKey]) -> Result<(), SDMProgramError> {
+        let mut signed = self
+            .did_doc
+            .keys
+            .iter()
+            .filter(|k| signers.contains(k))
+            .collect::<Vec<&SDMKey>>();
+        signed.sort();
+        signed.dedup();
+        if signed.len() as u64 >= self.did_doc.threshold {
+            Ok(())
+        } else {
+            Err(SDMProgramError::ThresholdNotMet)
+        }
+    }
+    /// Verify the DID may be closed, only decommissioned or revoked DIDs
+    /// unless forced
+    pub fn verify_closeable(&self, force: bool) -> Result<(), SDMProgramError> {
+        match self.did_doc.state {
+            SDMDidState::Decommissioned | SDMDidState::Revoked(_) => Ok(()),
+            _ if force => Ok(()),
+            _ => Err(SDMProgramError::DidNotDecommissioned),
+        }
+    }
+    /// Rotate the active keys and threshold from the instruction data
+    /// The keys must be those committed to by the previous event, unless
+    /// the DID was migrated without a commitment. Relationships not
+    /// reassigned are preserved by key position
     pub fn rotate_with(&mut self, with: DIDRotation) -> Result<(), SDMProgramError> {
+        self.verify_active()?;
+        self.verify_sequence(&with.prior_digest, with.sn)?;
+        verify_unique_keys(&with.keys)?;
+        if self.did_doc.next_digest != UNKNOWN_DIGEST
+            && next_keys_digest(self.did_doc.keytype, with.threshold, &with.keys)
+                != self.did_doc.next_digest
+        {
+            return Err(SDMProgramError::PreRotationMismatch);
+        }
+        verify_key_types(self.did_doc.keytype, &with.keys)?;
+        verify_threshold_bounds(&with.keys, with.threshold)?;
+        let relationships = with
+            .relationships
+            .unwrap_or_else(|| self.did_doc.relationships.clone());
+        verify_relationships(&with.keys, &relationships)?;
         self.did_doc.keys = with.keys;
+        self.did_doc.relationships = relationships;
+        self.did_doc.threshold = with.threshold;
+        self.did_doc.next_digest = with.next_digest;
+        self.did_doc.sn = with.sn;
+        self.did_doc.digest = with.digest;
         self.did_doc.state = SDMDidState::Rotated;
         Ok(())
     }
-    /// Rotate the active keys from the instruction data
-    pub fn decommission_with(&mut self, _with: DIDDecommission) -> Result<(), SDMProgramError> {
-        self.did_doc.keys = Vec::<Pubkey>::new();
+    /// Rotate the active keys from the instruction data, a decommissioned
+    /// DID publishes no services
+    pub fn decommission_with(&mut self, with: DIDDecommission) -> Result<(), SDMProgramError> {
+        self.verify_active()?;
+        self.verify_sequence(&with.prior_digest, with.sn)?;
+        self.did_doc.sn = with.sn;
+        self.did_doc.digest = with.digest;
+        self.did_doc.keys = Vec::<SDMKey>::new();
+        self.did_doc.relationships = Vec::new();
+        self.did_doc.threshold = 0;
+        self.did_doc.next_digest = UNKNOWN_DIGEST;
+        self.did_doc.services = Vec::new();
         self.did_doc.state = SDMDidState::Decommissioned;
         Ok(())
     }
+    /// Anchor the seals of the interaction, the event follows the latest
+    /// event and keeps the keys
+    pub fn interact_with(&mut self, with: &DIDInteraction) -> Result<(), SDMProgramError> {
+        self.verify_active()?;
+        if with.seals.is_empty() || with.seals.len() > MAX_INTERACTION_SEALS {
+            return Err(SDMProgramError::InvalidSeals);
+        }
+        self.verify_sequence(&with.prior_digest, with.sn)?;
+        self.did_doc.sn = with.sn;
+        self.did_doc.digest = with.digest;
+        Ok(())
+    }
+    /// Revoke the DID, its keys remain recorded as the revoked keys but
+    /// can no longer be rotated to and it publishes no services
+    pub fn revoke_with(&mut self, reason: SDMRevocationReason) -> Result<(), SDMProgramError> {
+        self.verify_active()?;
+        self.did_doc.next_digest = UNKNOWN_DIGEST;
+        self.did_doc.services = Vec::new();
+        self.did_doc.state = SDMDidState::Revoked(reason);
+        Ok(())
+    }
+    /// Propose the transfer of the DID to the authority, replacing any
+    /// earlier proposal
+    pub fn propose_authority(&mut self, authority: Pubkey) -> Result<(), SDMProgramError> {
+        self.verify_active()?;
+        self.did_doc.pending_authority = Some(authority);
+        Ok(())
+    }
+    /// The pending authority accepts the transfer and becomes the
+    /// authority of the DID
+    pub fn accept_authority(&mut self, authority: Pubkey) -> Result<(), SDMProgramError> {
+        self.verify_active()?;
+        match self.did_doc.pending_authority {
+            Some(pending) if pending == authority => {
+                self.did_doc.authority = pending;
+                self.did_doc.pending_authority = None;
+                Ok(())
+            }
+            Some(_) => Err(SDMProgramError::InvalidAuthority),
+            None => Err(SDMProgramError::NoPendingAuthority),
+        }
+    }
+    /// Withdraw the pending transfer of the DID
+    pub fn cancel_authority(&mut self) -> Result<(), SDMProgramError> {
+        self.did_doc
+            .pending_authority
+            .take()
+            .map(|_| ())
+            .ok_or(SDMProgramError::NoPendingAuthority)
+    }
+    /// Get the keys holding the verification relationship
+    pub fn keys_with(&self, relationship: u8) -> Vec<&SDMKey> {
+        self.did_doc
+            .keys
+            .iter()
+            .zip(self.did_doc.relationships.iter())
+            .filter(|(_, r)| *r & relationship == relationship)
+            .map(|(k, _)| k)
+            .collect()
+    }
+    /// Get the published services
+    pub fn services(&self) -> &[SDMService] {
+        &self.did_doc.services
+    }
+    /// Get the number of changes made to the services
+    pub fn service_changes(&self) -> u64 {
+        self.did_doc.service_changes
+    }
+    /// The message the authority and controllers sign to change the
+    /// services, the digest of the instruction data bound to the latest
+    /// event, the number of service changes and the current services.
+    /// Each change counts, so a signed change can not be replayed once
+    /// the services return to the state it was signed against
+    pub fn service_message(&self, instruction_data: &[u8]) -> Result<[u8; 32], SDMProgramError> {
+        let services = self
+            .did_doc
+            .services
+            .try_to_vec()
+            .map_err(|_| SDMProgramError::InvalidService)?;
+        Ok(hashv(&[
+            &self.did_doc.digest,
+            &self.did_doc.service_changes.to_le_bytes(),
+            &services,
+            instruction_data,
+        ])
+        .to_bytes())
+    }
+    /// Count a change of the services
+    fn count_service_change(&mut self) -> Result<(), SDMProgramError> {
+        self.did_doc.service_changes = self
+            .did_doc
+            .service_changes
+            .checked_add(1)
+            .ok_or(SDMProgramError::ServiceLimitExceeded)?;
+        Ok(())
+    }
+    fn service_position(&self, id: &str) -> Option<usize> {
+        self.did_doc.services.iter().position(|s| s.id == id)
+    }
+    /// Publish a new service with an unused id
+    pub fn add_service(&mut self, service: SDMService) -> Result<(), SDMProgramError> {
+        self.verify_active()?;
+        service.verify()?;
+        if self.service_position(&service.id).is_some() {
+            return Err(SDMProgramError::ServiceAlreadyExists);
+        }
+        if self.did_doc.services.len() >= MAX_SERVICES {
+            return Err(SDMProgramError::ServiceLimitExceeded);
+        }
+        self.did_doc.services.push(service);
+        self.count_service_change()
+    }
+    /// Replace the type and endpoint of the service with the same id
+    pub fn update_service(&mut self, service: SDMService) -> Result<(), SDMProgramError> {
+        self.verify_active()?;
+        service.verify()?;
+        let position = self
+            .service_position(&service.id)
+            .ok_or(SDMProgramError::ServiceNotFound)?;
+        self.did_doc.services[position] = service;
+        self.count_service_change()
+    }
+    /// Remove the service with the id
+    pub fn remove_service(&mut self, id: &str) -> Result<(), SDMProgramError> {
+        self.verify_active()?;
+        let position = self
+            .service_position(id)
+            .ok_or(SDMProgramError::ServiceNotFound)?;
+        self.did_doc.services.remove(position);
+        self.count_service_change()
+    }
     /// Sets the initialization flag
     pub fn set_initialized(&mut self) {
         self.initialized = true
     }
 
-    /// Assumes the account has not been initialized yet
-    /// If so, returns default state or otherwise throws error
-    pub fn unpack_unitialized(
-        data: &[u8],
-        with: DIDInception,
-        authority: &Pubkey,
-    ) -> Result<Self, SDMProgramError> {
-        let is_initialized = data[0] != 0;
-        if is_initialized {
-            Err(SDMProgramError::DidAlreadyInitialized)
-        } else {
-            Ok(Self {
-                initialized: !is_initialized,
-                version: CURRENT_DATA_VERSION,
-                did_doc: SDMDidDocCurrent {
-                    state: SDMDidState::Inception,
-                    keytype: with.keytype,
-                    authority: authority.clone(),
-                    prefix: with.prefix,
-                    bump: with.bump,
-                    keys: with.keys,
-                },
-            })
-        }
+    /// Creates the initial state of a DID from the inception data
+    pub fn from_inception(with: DIDInception, authority: &Pubkey) -> Result<Self, SDMProgramError> {
+        verify_unique_keys(&with.keys)?;
+        verify_key_types(with.keytype, &with.keys)?;
+        verify_threshold_bounds(&with.keys, with.threshold)?;
+        verify_relationships(&with.keys, &with.relationships)?;
+        Ok(Self {
+            initialized: true,
+            version: CURRENT_DATA_VERSION,
+            did_doc: SDMDidDocCurrent {
+                state: SDMDidState::Inception,
+                keytype: with.keytype,
+                authority: *authority,
+                pending_authority: None,
+                prefix: with.prefix,
+                bump: with.bump,
+                delegator: None,
+                sn: 0,
+                digest: with.prefix,
+                keys: with.keys,
+                relationships: with.relationships,
+                threshold: with.threshold,
+                next_digest: with.next_digest,
+                services: Vec::new(),
+                service_changes: 0,
+                created_slot: 0,
+                updated_slot: 0,
+                updated_unix_timestamp: 0,
+            },
+        })
+    }
+
+    /// Size of the serialized state, the exact storage of the DID account
+    pub fn size(&self) -> usize {
+        did_size(
+            &self.did_doc.state,
+            self.did_doc.keytype,
+            self.did_doc.keys.len(),
+            self.did_doc.pending_authority.is_some(),
+            self.did_doc.delegator.is_some(),
+            &self.did_doc.services,
+        )
     }
 
     /// Assumes the account statte has previously been initialized
-    /// If so, unpacks current statte or otherwise throws error
+    /// If so, unpacks the state of any version upgraded to the current
+    /// version or otherwise throws error
     pub fn unpack(data: &[u8]) -> Result<Self, SDMProgramError> {
-        let is_initialized = data[0] != 0;
-        if !is_initialized {
-            Err(SDMProgramError::DidNotInitialized)
-        } else {
-            let mut version: [u8; 2] = [0, 0];
-            version[0] = data[1];
-            version[1] = data[2];
-            let version = u16::from_le_bytes(version);
-            if version == CURRENT_DATA_VERSION {
-                let current = try_from_slice_unchecked::<SDMDid>(data).unwrap();
-                Ok(current)
-            } else {
-                Err(SDMProgramError::DidDataVersionInvalid)
+        match data {
+            [] | [0, ..] => Err(SDMProgramError::DidNotInitialized),
+            [_, v0, v1, doc @ ..] => {
+                let version = u16::from_le_bytes([*v0, *v1]);
+                let did_doc = SDMDidDocVersion::unpack(version, doc)?.upgrade();
+                Ok(Self {
+                    initialized: true,
+                    version: CURRENT_DATA_VERSION,
+                    did_doc,
+                })
             }
+            _ => Err(SDMProgramError::DidDataVersionInvalid),
         }
     }
 
-    /// Serializes the current data to the account state
+    /// Serializes the current data to the account state, the state
+    /// of a previous version is persisted in the current version
     pub fn pack(&mut self, data: &mut [u8]) -> Result<(), SDMProgramError> {
-        let mut bw = BufWriter::new(data);
-        self.serialize(&mut bw).unwrap();
+        let state = self
+            .try_to_vec()
+            .map_err(|_| SDMProgramError::InvalidAccountSize)?;
+        data.get_mut(..state.len())
+            .ok_or(SDMProgramError::InvalidAccountSize)?
+            .copy_from_slice(&state);
         Ok(())
     }
 }
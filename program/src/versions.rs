@@ -0,0 +1,89 @@
+//! @brief Historical DID account layouts
+//!
+//! Each layout is frozen as it was persisted under its version number
+//! and upgrades to the current layout. Accounts are read in any version
+//! and written in the current version
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::{
+    error::SDMProgramError,
+    instruction::{SDMKey, SMDKeyType},
+    state::{SDMDidDocCurrent, SDMDidState, ALL_RELATIONSHIPS, UNKNOWN_DIGEST},
+};
+
+/// Version 1, the active keys only
+#[derive(BorshDeserialize, BorshSerialize, Debug, PartialEq)]
+pub struct SDMDidDocV1 {
+    pub state: SDMDidState,
+    pub keytype: SMDKeyType,
+    pub authority: Pubkey,
+    pub prefix: [u8; 32],
+    pub bump: u8,
+    pub keys: Vec<Pubkey>,
+}
+
+/// A DID document in any of the persisted layouts
+#[derive(Debug, PartialEq)]
+pub enum SDMDidDocVersion {
+    V1(SDMDidDocV1),
+    V2(Box<SDMDidDocCurrent>),
+}
+
+impl SDMDidDocVersion {
+    /// Deserializes the document persisted in the layout of version,
+    /// trailing account storage is ignored
+    pub fn unpack(version: u16, data: &[u8]) -> Result<Self, SDMProgramError> {
+        let data = &mut &*data;
+        let doc = match version {
+            1 => SDMDidDocV1::deserialize(data).map(Self::V1),
+            2 => SDMDidDocCurrent::deserialize(data).map(|doc| Self::V2(Box::new(doc))),
+            _ => return Err(SDMProgramError::DidDataVersionInvalid),
+        };
+        doc.map_err(|_| SDMProgramError::DidDataVersionInvalid)
+    }
+
+    /// Upgrades the document to the current layout
+    pub fn upgrade(self) -> SDMDidDocCurrent {
+        match self {
+            Self::V1(doc) => doc.into(),
+            Self::V2(doc) => *doc,
+        }
+    }
+}
+
+/// No commitment was recorded, the next rotation establishes one. Only
+/// an incepted DID knows its latest event, which is the inception with
+/// the prefix as digest, otherwise the next event establishes it. Keys
+/// were interchangeable, each key holds every relationship and a single
+/// signature meets the threshold. DIDs published no services, could not
+/// be delegated or transferred and recorded no slots or time
+impl From<SDMDidDocV1> for SDMDidDocCurrent {
+    fn from(doc: SDMDidDocV1) -> Self {
+        let digest = match doc.state {
+            SDMDidState::Inception => doc.prefix,
+            _ => UNKNOWN_DIGEST,
+        };
+        Self {
+            state: doc.state,
+            keytype: doc.keytype,
+            authority: doc.authority,
+            pending_authority: None,
+            prefix: doc.prefix,
+            bump: doc.bump,
+            delegator: None,
+            sn: 0,
+            digest,
+            relationships: vec![ALL_RELATIONSHIPS; doc.keys.len()],
+            threshold: doc.keys.len().min(1) as u64,
+            keys: doc.keys.into_iter().map(SDMKey::Pubkey).collect(),
+            next_digest: UNKNOWN_DIGEST,
+            services: Vec::new(),
+            service_changes: 0,
+            created_slot: 0,
+            updated_slot: 0,
+            updated_unix_timestamp: 0,
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! Control of DIDs verified by a program invoking the DID program
+
+use solana_did_method::{
+    cpi,
+    error::SDMProgramError,
+    id,
+    instruction::{self, DIDDecommission, DIDInception, SMDKeyType},
+    keri::next_keys_digest,
+    process::process,
+    state::{SDMDid, SDMRevocationReason, ALL_RELATIONSHIPS},
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+};
+use solana_program_test::{processor, BanksClient, BanksClientError, ProgramTest};
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::Keypair,
+    signer::Signer,
+    transaction::{Transaction, TransactionError},
+};
+
+/// Program gated on control of the DID of the prefix in its data
+fn gate_process(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let account_iter = &mut accounts.iter();
+    let did_program = next_account_info(account_iter)?;
+    let signer = next_account_info(account_iter)?;
+    let did = next_account_info(account_iter)?;
+    let prefix = <[u8; 32]>::try_from(data).map_err(|_| ProgramError::InvalidInstructionData)?;
+    cpi::verify_controller(did_program, signer, did, &prefix)
+}
+
+/// Program approving every instruction, posing as the DID program
+fn permissive_process(
+    _program_id: &Pubkey,
+    _accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    Ok(())
+}
+
+struct TestContext {
+    banks_client: BanksClient,
+    payer: Keypair,
+    recent_blockhash: Hash,
+    gate_id: Pubkey,
+    permissive_id: Pubkey,
+}
+
+/// An Ed25519 DID of the authority and keys, decommissioned or revoked
+/// as the change requires
+fn did_account(
+    authority: &Pubkey,
+    keys: &[Pubkey],
+    change: impl FnOnce(&mut SDMDid),
+) -> ([u8; 32], Pubkey, Account) {
+    let prefix = Pubkey::new_unique().to_bytes();
+    let (pda, bump) = instruction::find_did_address(&prefix);
+    let mut did = SDMDid::from_inception(
+        DIDInception {
+            keytype: SMDKeyType::Ed25519,
+            prefix,
+            bump,
+            keys: keys.iter().map(|k| (*k).into()).collect(),
+            relationships: vec![ALL_RELATIONSHIPS; keys.len()],
+            threshold: 1,
+            next_digest: next_keys_digest::<Pubkey>(SMDKeyType::Ed25519, 1, &[]),
+        },
+        authority,
+    )
+    .unwrap();
+    change(&mut did);
+    let mut data = vec![0u8; did.size()];
+    did.pack(&mut data).unwrap();
+    let account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: id(),
+        ..Account::default()
+    };
+    (prefix, pda, account)
+}
+
+async fn start_with(accounts: Vec<(Pubkey, Account)>) -> TestContext {
+    let gate_id = Pubkey::new_unique();
+    let permissive_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new("solana_did_method", id(), processor!(process));
+    program_test.add_program("did_gate", gate_id, processor!(gate_process));
+    program_test.add_program("permissive", permissive_id, processor!(permissive_process));
+    for (address, account) in accounts {
+        program_test.add_account(address, account);
+    }
+    let (banks_client, payer, recent_blockhash) = program_test.start().await;
+    TestContext {
+        banks_client,
+        payer,
+        recent_blockhash,
+        gate_id,
+        permissive_id,
+    }
+}
+
+/// Gate instruction of the signer claiming control of the DID
+fn gate_instruction(
+    context: &TestContext,
+    signer: &Pubkey,
+    prefix: &[u8; 32],
+    pda: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: context.gate_id,
+        accounts: vec![
+            AccountMeta::new_readonly(id(), false),
+            AccountMeta::new_readonly(*signer, true),
+            AccountMeta::new_readonly(*pda, false),
+        ],
+        data: prefix.to_vec(),
+    }
+}
+
+async fn submit(
+    context: &mut TestContext,
+    instruction: Instruction,
+    signer: &Keypair,
+) -> Result<(), BanksClientError> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, signer],
+        context.recent_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+fn assert_sdm_error(result: Result<(), BanksClientError>, error: SDMProgramError) {
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_verify_controller_cpi_pass() {
+    let authority = Keypair::new();
+    let key = Keypair::new();
+    let (prefix, pda, account) = did_account(&authority.pubkey(), &[key.pubkey()], |_| ());
+    let mut context = start_with(vec![(pda, account)]).await;
+    // The authority and a current key both control the DID
+    let instruction = gate_instruction(&context, &authority.pubkey(), &prefix, &pda);
+    submit(&mut context, instruction, &authority).await.unwrap();
+    let instruction = gate_instruction(&context, &key.pubkey(), &prefix, &pda);
+    submit(&mut context, instruction, &key).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_verify_controller_direct_pass() {
+    let authority = Keypair::new();
+    let (prefix, pda, account) = did_account(&authority.pubkey(), &[Pubkey::new_unique()], |_| ());
+    let mut context = start_with(vec![(pda, account)]).await;
+    let instruction = instruction::verify_controller(&id(), &authority.pubkey(), &prefix);
+    submit(&mut context, instruction, &authority).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_verify_controller_not_controller_fail() {
+    let authority = Keypair::new();
+    let (prefix, pda, account) = did_account(&authority.pubkey(), &[Pubkey::new_unique()], |_| ());
+    let mut context = start_with(vec![(pda, account)]).await;
+    let other = Keypair::new();
+    let instruction = gate_instruction(&context, &other.pubkey(), &prefix, &pda);
+    assert_sdm_error(
+        submit(&mut context, instruction, &other).await,
+        SDMProgramError::SignerNotController,
+    );
+}
+
+#[tokio::test]
+async fn test_verify_controller_wrong_program_fail() {
+    let authority = Keypair::new();
+    let (prefix, pda, account) = did_account(&authority.pubkey(), &[Pubkey::new_unique()], |_| ());
+    let mut context = start_with(vec![(pda, account)]).await;
+    // A program approving any signer in place of the DID program
+    let other = Keypair::new();
+    let mut instruction = gate_instruction(&context, &other.pubkey(), &prefix, &pda);
+    instruction.accounts[0].pubkey = context.permissive_id;
+    assert_eq!(
+        submit(&mut context, instruction, &other)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(0, InstructionError::IncorrectProgramId)
+    );
+}
+
+#[tokio::test]
+async fn test_verify_controller_prefix_mismatch_fail() {
+    let authority = Keypair::new();
+    let (_, pda, account) = did_account(&authority.pubkey(), &[Pubkey::new_unique()], |_| ());
+    let mut context = start_with(vec![(pda, account)]).await;
+    let prefix = Pubkey::new_unique().to_bytes();
+    let instruction = gate_instruction(&context, &authority.pubkey(), &prefix, &pda);
+    assert_sdm_error(
+        submit(&mut context, instruction, &authority).await,
+        SDMProgramError::InvalidDidReference,
+    );
+}
+
+#[tokio::test]
+async fn test_verify_controller_inactive_fail() {
+    let authority = Keypair::new();
+    let key = Keypair::new();
+    let (decommissioned_prefix, decommissioned, decommissioned_account) =
+        did_account(&authority.pubkey(), &[key.pubkey()], |did| {
+            let prefix = *did.prefix();
+            did.decommission_with(DIDDecommission {
+                keytype: SMDKeyType::Ed25519,
+                prefix,
+                prior_digest: prefix,
+                digest: Pubkey::new_unique().to_bytes(),
+                sn: 1,
+            })
+            .unwrap();
+        });
+    let (revoked_prefix, revoked, revoked_account) =
+        did_account(&authority.pubkey(), &[key.pubkey()], |did| {
+            did.revoke_with(SDMRevocationReason::KeyCompromise).unwrap();
+        });
+    let mut context = start_with(vec![
+        (decommissioned, decommissioned_account),
+        (revoked, revoked_account),
+    ])
+    .await;
+    let instruction = gate_instruction(
+        &context,
+        &authority.pubkey(),
+        &decommissioned_prefix,
+        &decommissioned,
+    );
+    assert_sdm_error(
+        submit(&mut context, instruction, &authority).await,
+        SDMProgramError::DidDecommissioned,
+    );
+    let instruction = gate_instruction(&context, &key.pubkey(), &revoked_prefix, &revoked);
+    assert_sdm_error(
+        submit(&mut context, instruction, &key).await,
+        SDMProgramError::DidRevoked,
+    );
+}
@@ -0,0 +1,131 @@
+//! DID account sizes against the Borsh encoding of generated states
+
+use borsh::BorshSerialize;
+use solana_did_method::{
+    instruction::{DIDDecommission, DIDInception, SDMKey, SMDKeyType, Secp256k1Pubkey},
+    keri::next_keys_digest,
+    layout::{did_size, inception_size},
+    state::{
+        SDMDid, SDMDidState, SDMRevocationReason, SDMService, ALL_RELATIONSHIPS, MAX_SERVICES,
+    },
+};
+use solana_program::pubkey::Pubkey;
+
+const KEYTYPES: [SMDKeyType; 3] = [
+    SMDKeyType::Ed25519,
+    SMDKeyType::PASTA,
+    SMDKeyType::Secp256k1,
+];
+
+fn keys(keytype: SMDKeyType, count: usize) -> Vec<SDMKey> {
+    (0..count)
+        .map(|i| match keytype {
+            SMDKeyType::Secp256k1 => SDMKey::Secp256k1(Secp256k1Pubkey([i as u8 + 2; 33])),
+            _ => SDMKey::Pubkey(Pubkey::new_unique()),
+        })
+        .collect()
+}
+
+/// Services of growing field lengths
+fn services(count: usize) -> Vec<SDMService> {
+    (0..count)
+        .map(|i| SDMService {
+            id: format!("#service-{}", i),
+            service_type: format!("DIDCommMessaging{}", "v".repeat(i * 6)),
+            endpoint: format!("https://example.com/{}", "a".repeat(i * 29)),
+        })
+        .collect()
+}
+
+fn did(keytype: SMDKeyType, count: usize, delegated: bool) -> SDMDid {
+    let mut did = SDMDid::from_inception(
+        DIDInception {
+            keytype,
+            prefix: Pubkey::new_unique().to_bytes(),
+            bump: 255,
+            keys: keys(keytype, count),
+            relationships: vec![ALL_RELATIONSHIPS; count],
+            threshold: 1,
+            next_digest: next_keys_digest::<Pubkey>(keytype, 1, &[]),
+        },
+        &Pubkey::new_unique(),
+    )
+    .unwrap();
+    if delegated {
+        did.set_delegator(Some(Pubkey::new_unique().to_bytes()));
+    }
+    did
+}
+
+fn assert_size(did: &SDMDid) {
+    assert_eq!(did.size(), did.try_to_vec().unwrap().len());
+}
+
+#[test]
+fn test_inception_size_pass() {
+    for keytype in KEYTYPES {
+        for count in 1..=4 {
+            for delegated in [false, true] {
+                let did = did(keytype, count, delegated);
+                assert_size(&did);
+                assert_eq!(
+                    inception_size(keytype, count, delegated),
+                    did.try_to_vec().unwrap().len()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_services_size_pass() {
+    for keytype in KEYTYPES {
+        for count in 0..=MAX_SERVICES {
+            let mut did = did(keytype, 2, count % 2 == 0);
+            for service in services(count) {
+                did.add_service(service).unwrap();
+            }
+            assert_size(&did);
+        }
+    }
+}
+
+#[test]
+fn test_decommissioned_size_pass() {
+    for keytype in KEYTYPES {
+        let mut did = did(keytype, 3, false);
+        did.add_service(services(1).remove(0)).unwrap();
+        let prefix = *did.prefix();
+        did.decommission_with(DIDDecommission {
+            keytype,
+            prefix,
+            prior_digest: prefix,
+            digest: Pubkey::new_unique().to_bytes(),
+            sn: 1,
+        })
+        .unwrap();
+        assert_size(&did);
+    }
+}
+
+#[test]
+fn test_revoked_size_pass() {
+    for keytype in KEYTYPES {
+        let mut did = did(keytype, 2, true);
+        did.revoke_with(SDMRevocationReason::KeyCompromise).unwrap();
+        assert_size(&did);
+        let state = SDMDidState::Revoked(SDMRevocationReason::KeyCompromise);
+        assert_eq!(did.size(), did_size(&state, keytype, 2, false, true, &[]));
+    }
+}
+
+#[test]
+fn test_pending_authority_size_pass() {
+    for keytype in KEYTYPES {
+        let mut did = did(keytype, 2, false);
+        did.propose_authority(Pubkey::new_unique()).unwrap();
+        assert_size(&did);
+        did.cancel_authority().unwrap();
+        assert_size(&did);
+    }
+}
@@ -0,0 +1,96 @@
+//! Pasta Schnorr signature tests
+
+use pasta_curves::{
+    group::{ff::PrimeField, Group, GroupEncoding},
+    pallas::{Point, Scalar},
+};
+use solana_did_method::{
+    error::SDMProgramError,
+    pasta::{
+        sign, steps_within, PastaVerification, MAX_TRANSACTION_UNITS, PASTA_SCALAR_BITS,
+        PASTA_STEP_UNITS, PASTA_VERIFY_BASE_UNITS,
+    },
+};
+
+const MESSAGE: &[u8] = br#"{"v":"KERI10JSON000000_","t":"rot"}"#;
+
+/// Secret scalar and compressed key
+fn keypair(seed: u8) -> ([u8; 32], [u8; 32]) {
+    let mut secret = [seed; 32];
+    secret[31] &= 0x3f;
+    let key = Point::generator() * Scalar::from_repr(secret).unwrap();
+    (secret, key.to_bytes())
+}
+
+#[test]
+fn test_sign_verify_pass() {
+    let (secret, key) = keypair(7);
+    let signature = sign(&secret, 0, MESSAGE).unwrap();
+    // Signing is deterministic
+    assert_eq!(signature, sign(&secret, 0, MESSAGE).unwrap());
+    let mut verification = PastaVerification::new(&key, &signature, MESSAGE).unwrap();
+    assert_eq!(
+        verification.advance(PASTA_SCALAR_BITS).unwrap(),
+        PASTA_SCALAR_BITS
+    );
+    assert!(verification.is_complete());
+    assert_eq!(verification.advance(1).unwrap(), 0);
+}
+
+#[test]
+fn test_verify_in_steps_pass() {
+    let (secret, key) = keypair(9);
+    let signature = sign(&secret, 0, MESSAGE).unwrap();
+    let mut verification = PastaVerification::new(&key, &signature, MESSAGE).unwrap();
+    let mut taken = 0;
+    while !verification.is_complete() {
+        taken += verification.advance(64).unwrap();
+    }
+    assert_eq!(taken, PASTA_SCALAR_BITS);
+}
+
+#[test]
+fn test_verify_other_message_fail() {
+    let (secret, key) = keypair(11);
+    let signature = sign(&secret, 0, MESSAGE).unwrap();
+    let mut verification = PastaVerification::new(&key, &signature, b"other").unwrap();
+    verification.advance(PASTA_SCALAR_BITS - 1).unwrap();
+    assert!(matches!(
+        verification.advance(1),
+        Err(SDMProgramError::InvalidPastaSignature)
+    ));
+}
+
+#[test]
+fn test_verify_other_key_fail() {
+    let (secret, _) = keypair(13);
+    let (_, other) = keypair(17);
+    let signature = sign(&secret, 0, MESSAGE).unwrap();
+    // The y coordinate provided is not that of the key
+    assert!(matches!(
+        PastaVerification::new(&other, &signature, MESSAGE),
+        Err(SDMProgramError::InvalidPastaSignature)
+    ));
+}
+
+#[test]
+fn test_sign_invalid_secret_fail() {
+    assert!(matches!(
+        sign(&[0u8; 32], 0, MESSAGE),
+        Err(SDMProgramError::DidInvalidKey)
+    ));
+    assert!(matches!(
+        sign(&[0xff; 32], 0, MESSAGE),
+        Err(SDMProgramError::DidInvalidKey)
+    ));
+}
+
+#[test]
+fn test_steps_within() {
+    assert_eq!(steps_within(PASTA_VERIFY_BASE_UNITS), 0);
+    assert_eq!(
+        steps_within(PASTA_VERIFY_BASE_UNITS + 3 * PASTA_STEP_UNITS),
+        3
+    );
+    assert!(steps_within(MAX_TRANSACTION_UNITS) > 0);
+}
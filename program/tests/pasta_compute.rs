@@ -0,0 +1,256 @@
+//! Compute unit benchmarks of Pasta controller rotations run against the
+//! BPF program, `cargo test-bpf -- --nocapture pasta_compute` reports the
+//! units of each instruction for 1, 2 and 3 signatures and the step and
+//! base units of a verify instruction derived from them
+
+#![cfg(feature = "test-bpf")]
+
+use pasta_curves::{
+    group::{ff::PrimeField, Group, GroupEncoding},
+    pallas::{Point, Scalar},
+};
+use solana_did_method::{
+    id,
+    instruction::{self, DIDInception, DIDPastaSignatures, DIDRotation, SMDKeyType},
+    keri::next_keys_digest,
+    pasta::{self, SDMPastaSession, MAX_TRANSACTION_UNITS},
+    state::{SDMDid, ALL_RELATIONSHIPS},
+};
+use solana_program_test::{BanksClient, ProgramTest, ProgramTestBanksClientExt};
+use solana_sdk::{
+    account::Account, compute_budget::ComputeBudgetInstruction, hash::Hash,
+    instruction::Instruction, pubkey::Pubkey, rent::Rent, signature::Keypair, signer::Signer,
+    transaction::Transaction,
+};
+
+/// Margin in percent of the step and base units over the derived units
+const UNITS_MARGIN_PERCENT: u64 = 10;
+
+/// Asserts the units of the program lie between the derived units and
+/// the derived units with the margin
+fn assert_calibrated(name: &str, units: u64, derived: u64) {
+    let margin = derived * (100 + UNITS_MARGIN_PERCENT) / 100;
+    assert!(
+        derived <= units && units <= margin,
+        "{} is {}, derived units {} set it to {}",
+        name,
+        units,
+        derived,
+        margin
+    );
+}
+
+fn qb64(code: &str, raw: &[u8]) -> String {
+    [code, &base64::encode_config(raw, base64::URL_SAFE_NO_PAD)].concat()
+}
+
+/// Pasta secret scalars and their keys
+fn pasta_keys(count: usize) -> Vec<([u8; 32], Pubkey)> {
+    (0..count)
+        .map(|_| {
+            let mut secret = Pubkey::new_unique().to_bytes();
+            secret[31] &= 0x3f;
+            let key = Point::generator() * Scalar::from_repr(secret).unwrap();
+            (secret, Pubkey::new_from_array(key.to_bytes()))
+        })
+        .collect()
+}
+
+/// DID account of a Pasta inception of the keys with a threshold of all
+/// keys, committing to the next keys
+fn pasta_did(
+    prefix: [u8; 32],
+    authority: &Pubkey,
+    keys: &[Pubkey],
+    next_keys: &[Pubkey],
+) -> (Pubkey, Account) {
+    let (pda, bump) = instruction::find_did_address(&prefix);
+    let mut did = SDMDid::from_inception(
+        DIDInception {
+            keytype: SMDKeyType::PASTA,
+            prefix,
+            bump,
+            keys: keys.iter().map(|k| (*k).into()).collect(),
+            relationships: vec![ALL_RELATIONSHIPS; keys.len()],
+            threshold: keys.len() as u64,
+            next_digest: next_keys_digest(SMDKeyType::PASTA, keys.len() as u64, next_keys),
+        },
+        authority,
+    )
+    .unwrap();
+    let mut data = vec![0u8; did.size()];
+    did.pack(&mut data).unwrap();
+    let account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: id(),
+        ..Account::default()
+    };
+    (pda, account)
+}
+
+/// Rotation following the inception to the keys and its signed event
+fn rotation(prefix: [u8; 32], keys: &[Pubkey]) -> (DIDRotation, Vec<u8>) {
+    let rotation = DIDRotation {
+        keytype: SMDKeyType::PASTA,
+        prefix,
+        prior_digest: prefix,
+        digest: Pubkey::new_unique().to_bytes(),
+        sn: 1,
+        keys: keys.iter().map(|k| (*k).into()).collect(),
+        relationships: None,
+        threshold: keys.len() as u64,
+        next_digest: next_keys_digest::<Pubkey>(SMDKeyType::PASTA, keys.len() as u64, &[]),
+    };
+    let k = keys
+        .iter()
+        .map(|k| format!(r#""{}""#, qb64("1AAE", k.as_ref())))
+        .collect::<Vec<String>>()
+        .join(",");
+    let message = format!(
+        r#"{{"v":"KERI10JSON000000_","t":"rot","d":"{}","i":"{}","s":"1","p":"{}","kt":"{:x}","k":[{}],"n":"{}","bt":"0","b":[],"c":[],"a":[]}}"#,
+        qb64("E", &rotation.digest),
+        qb64("E", &prefix),
+        qb64("E", &prefix),
+        rotation.threshold,
+        k,
+        qb64("E", &rotation.next_digest)
+    );
+    (rotation, message.into_bytes())
+}
+
+/// Simulates the instruction with the maximum compute unit limit for its
+/// units, then processes it. The first signer pays
+async fn measure(
+    banks_client: &mut BanksClient,
+    signers: &[&Keypair],
+    recent_blockhash: &mut Hash,
+    instruction: Instruction,
+) -> u64 {
+    *recent_blockhash = banks_client
+        .get_new_latest_blockhash(recent_blockhash)
+        .await
+        .unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            ComputeBudgetInstruction::set_compute_unit_limit(MAX_TRANSACTION_UNITS),
+            instruction,
+        ],
+        Some(&signers[0].pubkey()),
+        &signers.to_vec(),
+        *recent_blockhash,
+    );
+    let simulation = banks_client
+        .simulate_transaction(transaction.clone())
+        .await
+        .unwrap();
+    let units = simulation.simulation_details.unwrap().units_consumed;
+    banks_client.process_transaction(transaction).await.unwrap();
+    units
+}
+
+/// Rotates a Pasta DID of count keys authorized by all of them and
+/// returns the units of the begin, each verify and the rotation
+async fn pasta_rotation_units(count: usize) -> (u64, Vec<u64>, u64) {
+    let controllers = pasta_keys(count);
+    let keys = controllers.iter().map(|(_, k)| *k).collect::<Vec<Pubkey>>();
+    let next_keys = pasta_keys(count)
+        .iter()
+        .map(|(_, k)| *k)
+        .collect::<Vec<Pubkey>>();
+    let prefix = Pubkey::new_unique().to_bytes();
+    let authority = Keypair::new();
+    let (pda, account) = pasta_did(prefix, &authority.pubkey(), &keys, &next_keys);
+    let mut program_test = ProgramTest::new("solana_did_method", id(), None);
+    program_test.add_account(pda, account);
+    let (mut banks_client, payer, mut recent_blockhash) = program_test.start().await;
+
+    let (rotation, message) = rotation(prefix, &next_keys);
+    let signatures = controllers
+        .iter()
+        .enumerate()
+        .map(|(index, (secret, _))| pasta::sign(secret, index as u8, &message).unwrap())
+        .collect();
+    let session = SDMPastaSession::address(&prefix, &payer.pubkey(), &id()).0;
+    let begin = instruction::pasta_begin(
+        &id(),
+        &payer.pubkey(),
+        DIDPastaSignatures {
+            keytype: SMDKeyType::PASTA,
+            prefix,
+            message,
+            signatures,
+        },
+    );
+    let begin_units = measure(&mut banks_client, &[&payer], &mut recent_blockhash, begin).await;
+
+    let steps = pasta::steps_within(MAX_TRANSACTION_UNITS);
+    let mut verify_units = Vec::new();
+    loop {
+        let account = banks_client.get_account(session).await.unwrap().unwrap();
+        if SDMPastaSession::unpack(&account.data)
+            .unwrap()
+            .is_complete()
+        {
+            break;
+        }
+        let verify = instruction::pasta_verify(&id(), &payer.pubkey(), &prefix, steps);
+        verify_units
+            .push(measure(&mut banks_client, &[&payer], &mut recent_blockhash, verify).await);
+    }
+
+    let rotate = instruction::pasta_rotation(&id(), &payer.pubkey(), &authority.pubkey(), rotation);
+    let signers = [&payer, &authority];
+    let rotation_units = measure(&mut banks_client, &signers, &mut recent_blockhash, rotate).await;
+    (begin_units, verify_units, rotation_units)
+}
+
+#[tokio::test]
+async fn test_pasta_compute_units() {
+    let steps = pasta::steps_within(MAX_TRANSACTION_UNITS);
+    println!("steps per verify: {}", steps);
+    for count in 1..=3 {
+        let (begin, verify, rotation) = pasta_rotation_units(count).await;
+        let total = begin + verify.iter().sum::<u64>() + rotation;
+        println!(
+            "signatures: {} begin: {} verify: {} x {:?} rotation: {} total: {}",
+            count,
+            begin,
+            verify.len(),
+            verify,
+            rotation,
+            total
+        );
+        // Each full verify stays within its budget, the steps estimate
+        // must be lowered if not
+        assert!(verify
+            .iter()
+            .all(|units| *units <= MAX_TRANSACTION_UNITS as u64));
+        let bits = pasta::PASTA_SCALAR_BITS as u64 * count as u64;
+        let steps = steps as u64;
+        assert_eq!(verify.len() as u64, (bits + steps - 1) / steps);
+        // The last verify takes the remaining steps, the difference to a
+        // full verify are the units of the steps it does not take
+        let partial = bits % steps;
+        if verify.len() > 1 && partial > 0 {
+            let full = verify[verify.len() - 2];
+            let last = verify[verify.len() - 1];
+            let step_units = full.saturating_sub(last) / (steps - partial);
+            let base_units = full.saturating_sub(steps * step_units);
+            println!(
+                "signatures: {} step units: {} base units: {}",
+                count, step_units, base_units
+            );
+            assert_calibrated(
+                "PASTA_STEP_UNITS",
+                pasta::PASTA_STEP_UNITS as u64,
+                step_units,
+            );
+            assert_calibrated(
+                "PASTA_VERIFY_BASE_UNITS",
+                pasta::PASTA_VERIFY_BASE_UNITS as u64,
+                base_units,
+            );
+        }
+    }
+}
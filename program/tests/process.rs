@@ -0,0 +1,3790 @@
+//! Program processing tests run against the native processor
+
+use assert_matches::assert_matches;
+use borsh::{BorshDeserialize, BorshSerialize};
+use hbkr_rs::{
+    basic::Basic,
+    basicpre::BasicPrefix,
+    event::Event,
+    event_data::EventData,
+    event_message::EventMessage,
+    event_msg_builder::EventMsgBuilder,
+    key_config::nxt_commitment,
+    key_manage::Publickey,
+    said::{SelfAddressing, SelfAddressingPrefix},
+    said_event::SaidEvent,
+    threshold::SignatureThreshold,
+    EventTypeTag,
+};
+use pasta_curves::{
+    group::{ff::PrimeField, Group, GroupEncoding},
+    pallas::{Point, Scalar},
+};
+use solana_did_method::{
+    error::SDMProgramError,
+    event_log::{key_digest, SDMEventLog, SDMEventRecord, SDMEventType},
+    events::{SDMEventKind, SDMProgramEvent, PROGRAM_EVENT_TAG},
+    id,
+    instruction::{
+        self, DIDAuthorityTransfer, DIDDecommission, DIDDelegatedInception, DIDInception,
+        DIDInteraction, DIDPastaSignatures, DIDProposeAuthority, DIDRevoke, DIDRotation,
+        DIDService, DIDServiceRemove, InitializeDidAccount, PastaSignature, SDMInstruction, SDMKey,
+        SMDKeyType, Secp256k1Pubkey,
+    },
+    keri::next_keys_digest,
+    pasta::{self, SDMPastaSession},
+    process::process,
+    state::{
+        SDMDid, SDMDidState, SDMRevocationReason, SDMService, ALL_RELATIONSHIPS, ASSERTION_METHOD,
+        AUTHENTICATION, CAPABILITY_INVOCATION, CURRENT_DATA_VERSION, KEY_AGREEMENT,
+        MAX_INTERACTION_SEALS, MAX_SERVICES, MAX_SERVICE_ENDPOINT_LEN,
+    },
+    versions::SDMDidDocV1,
+};
+use solana_program_test::{
+    processor, BanksClient, BanksClientError, ProgramTest, ProgramTestBanksClientExt,
+};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    compute_budget::ComputeBudgetInstruction,
+    ed25519_instruction::new_ed25519_instruction,
+    ed25519_program,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    keccak,
+    pubkey::Pubkey,
+    rent::Rent,
+    secp256k1_instruction::new_secp256k1_instruction,
+    secp256k1_program,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction, system_program, sysvar,
+    transaction::{Transaction, TransactionError},
+};
+use std::sync::Mutex;
+
+/// Test context for a program with a funded authority
+struct TestContext {
+    banks_client: BanksClient,
+    authority: Keypair,
+    recent_blockhash: Hash,
+}
+
+async fn start() -> TestContext {
+    start_with(Vec::new()).await
+}
+
+/// Start with accounts preloaded
+async fn start_with(accounts: Vec<(Pubkey, Account)>) -> TestContext {
+    let mut program_test = ProgramTest::new("solana_did_method", id(), processor!(process));
+    for (address, account) in accounts {
+        program_test.add_account(address, account);
+    }
+    let (banks_client, authority, recent_blockhash) = program_test.start().await;
+    TestContext {
+        banks_client,
+        authority,
+        recent_blockhash,
+    }
+}
+
+/// Keypairs of the keys created by `controller`, which sign the events
+/// of the DIDs they control
+static CONTROLLERS: Mutex<Vec<Keypair>> = Mutex::new(Vec::new());
+
+/// A new Ed25519 key whose keypair signs for the DIDs it controls
+fn controller() -> Pubkey {
+    let keypair = Keypair::new();
+    let key = keypair.pubkey();
+    CONTROLLERS.lock().unwrap().push(keypair);
+    key
+}
+
+/// A key event of a test DID
+#[derive(Clone, Debug)]
+struct TestEvent {
+    ilk: &'static str,
+    keytype: SMDKeyType,
+    prefix: [u8; 32],
+    sn: u64,
+    digest: [u8; 32],
+    prior_digest: Option<[u8; 32]>,
+    keys: Vec<SDMKey>,
+    relationships: Option<Vec<u8>>,
+    threshold: u64,
+    next_keys: Vec<SDMKey>,
+    /// Threshold committed to for the next keys, if not the threshold
+    next_threshold: Option<u64>,
+    delegator: Option<[u8; 32]>,
+    seals: Vec<[u8; 32]>,
+    /// Controllers of the DID before the event, signing it
+    signers: Vec<SDMKey>,
+    /// Controllers of the delegator, approving the event
+    approvers: Vec<SDMKey>,
+}
+
+impl TestEvent {
+    /// Inception of a new prefix, the prefix is the event digest
+    fn inception<K: Copy + Into<SDMKey>>(keys: &[K]) -> Self {
+        let prefix = Pubkey::new_unique().to_bytes();
+        Self {
+            ilk: "icp",
+            keytype: SMDKeyType::Ed25519,
+            prefix,
+            sn: 0,
+            digest: prefix,
+            prior_digest: None,
+            keys: keys.iter().map(|k| (*k).into()).collect(),
+            relationships: Some(vec![ALL_RELATIONSHIPS; keys.len()]),
+            threshold: 1,
+            next_keys: Vec::new(),
+            next_threshold: None,
+            delegator: None,
+            seals: Vec::new(),
+            signers: Vec::new(),
+            approvers: Vec::new(),
+        }
+    }
+
+    /// Delegated inception of a new prefix by the delegator
+    fn delegated_inception<K: Copy + Into<SDMKey>>(keys: &[K], delegator: &TestEvent) -> Self {
+        Self {
+            ilk: "dip",
+            delegator: Some(delegator.prefix),
+            approvers: delegator.controllers(),
+            ..Self::inception(keys)
+        }
+    }
+
+    /// Rotation following this event, keeping the threshold, delegated
+    /// if the DID is delegated
+    fn rotation<K: Copy + Into<SDMKey>>(&self, keys: &[K]) -> Self {
+        Self {
+            ilk: match self.delegator {
+                Some(_) => "drt",
+                None => "rot",
+            },
+            keytype: self.keytype,
+            prefix: self.prefix,
+            sn: self.sn + 1,
+            digest: Pubkey::new_unique().to_bytes(),
+            prior_digest: Some(self.digest),
+            keys: keys.iter().map(|k| (*k).into()).collect(),
+            relationships: Some(vec![ALL_RELATIONSHIPS; keys.len()]),
+            threshold: self.threshold,
+            next_keys: Vec::new(),
+            next_threshold: None,
+            delegator: self.delegator,
+            seals: Vec::new(),
+            signers: self.controllers(),
+            approvers: self.approvers.clone(),
+        }
+    }
+
+    /// Interaction following this event anchoring the seals, keeping
+    /// the keys
+    fn interaction(&self, seals: &[[u8; 32]]) -> Self {
+        Self {
+            ilk: "ixn",
+            seals: seals.to_vec(),
+            ..self.rotation(&self.keys)
+        }
+    }
+
+    /// Decommission following this event
+    fn decommission(&self) -> Self {
+        let mut event = self.rotation::<SDMKey>(&[]);
+        event.ilk = "rot";
+        event.threshold = 0;
+        event
+    }
+
+    /// This event committing to the next keys at its threshold
+    fn committing<K: Copy + Into<SDMKey>>(self, next_keys: &[K]) -> Self {
+        Self {
+            next_keys: next_keys.iter().map(|k| (*k).into()).collect(),
+            ..self
+        }
+    }
+
+    /// The current keys after this event up to the threshold, the fewest
+    /// controllers signing for the DID
+    fn controllers(&self) -> Vec<SDMKey> {
+        self.keys
+            .iter()
+            .take(self.threshold as usize)
+            .copied()
+            .collect()
+    }
+
+    /// Commitment to the next keys rendered as the `n` field
+    fn next_digest(&self) -> [u8; 32] {
+        let threshold = self.next_threshold.unwrap_or(self.threshold);
+        next_keys_digest(self.keytype, threshold, &self.next_keys)
+    }
+
+    fn pda(&self) -> (Pubkey, u8) {
+        instruction::find_did_address(&self.prefix)
+    }
+
+    /// The event log PDA of the DID
+    fn log(&self) -> Pubkey {
+        SDMEventLog::address(&self.prefix, &id()).0
+    }
+
+    /// Render the KERI event message with the fields the program verifies
+    fn message(&self) -> Vec<u8> {
+        let qb64 = |code: &str, raw: &[u8]| {
+            [code, &base64::encode_config(raw, base64::URL_SAFE_NO_PAD)].concat()
+        };
+        let code = match self.keytype {
+            SMDKeyType::Ed25519 => "D",
+            SMDKeyType::PASTA => "1AAE",
+            SMDKeyType::Secp256k1 => "1AAB",
+        };
+        let keys = self
+            .keys
+            .iter()
+            .map(|k| format!(r#""{}""#, qb64(code, k.as_ref())))
+            .collect::<Vec<String>>()
+            .join(",");
+        let prior = self
+            .prior_digest
+            .map_or(String::new(), |p| format!(r#""p":"{}","#, qb64("E", &p)));
+        let delegator = match (self.ilk, self.delegator) {
+            ("dip", Some(d)) => format!(r#","di":"{}""#, qb64("E", &d)),
+            _ => String::new(),
+        };
+        if self.ilk == "ixn" {
+            let seals = self
+                .seals
+                .iter()
+                .map(|s| format!(r#"{{"d":"{}"}}"#, qb64("E", s)))
+                .collect::<Vec<String>>()
+                .join(",");
+            return format!(
+                r#"{{"v":"KERI10JSON000000_","t":"ixn","d":"{}","i":"{}","s":"{:x}",{}"a":[{}]}}"#,
+                qb64("E", &self.digest),
+                qb64("E", &self.prefix),
+                self.sn,
+                prior,
+                seals
+            )
+            .into_bytes();
+        }
+        format!(
+            r#"{{"v":"KERI10JSON000000_","t":"{}","d":"{}","i":"{}","s":"{:x}",{}"kt":"{:x}","k":[{}],"n":"{}","bt":"0","b":[],"c":[],"a":[]{}}}"#,
+            self.ilk,
+            qb64("E", &self.digest),
+            qb64("E", &self.prefix),
+            self.sn,
+            prior,
+            self.threshold,
+            keys,
+            qb64("E", &self.next_digest()),
+            delegator
+        )
+        .into_bytes()
+    }
+}
+
+/// Ed25519 signature instruction over a message
+fn sign_message(signer: &Keypair, message: &[u8]) -> Instruction {
+    new_ed25519_instruction(
+        &ed25519_dalek::Keypair::from_bytes(&signer.to_bytes()).unwrap(),
+        message,
+    )
+}
+
+/// Ed25519 signature instruction over the event message
+fn sign_instruction(signer: &Keypair, event: &TestEvent) -> Instruction {
+    sign_message(signer, &event.message())
+}
+
+/// Ed25519 signature instruction over a message by each signer and by
+/// the controllers among the keys, the signatures sharing the message
+fn sign_controlled(signers: &[&Keypair], keys: &[SDMKey], message: &[u8]) -> Instruction {
+    let controllers = CONTROLLERS.lock().unwrap();
+    let mut signers = signers.to_vec();
+    signers.extend(
+        controllers
+            .iter()
+            .filter(|c| keys.contains(&SDMKey::Pubkey(c.pubkey()))),
+    );
+    // Offsets of each signature, then each key and signature, then the
+    // message
+    let signed_start = 2 + signers.len() * 14;
+    let message_offset = signed_start + signers.len() * 96;
+    let mut data = vec![signers.len() as u8, 0];
+    let mut signed = Vec::new();
+    for (index, signer) in signers.iter().enumerate() {
+        let key_offset = signed_start + index * 96;
+        for value in [
+            key_offset + 32,
+            u16::MAX as usize,
+            key_offset,
+            u16::MAX as usize,
+            message_offset,
+            message.len(),
+            u16::MAX as usize,
+        ] {
+            data.extend((value as u16).to_le_bytes());
+        }
+        signed.extend(signer.pubkey().to_bytes());
+        signed.extend(signer.sign_message(message).as_ref());
+    }
+    data.extend(signed);
+    data.extend(message);
+    Instruction::new_with_bytes(ed25519_program::id(), &data, Vec::new())
+}
+
+/// Ed25519 signature instruction over the event message by each signer
+/// and by the controllers signing and approving the event
+fn sign_event(signers: &[&Keypair], event: &TestEvent) -> Instruction {
+    let keys = [event.signers.as_slice(), &event.approvers].concat();
+    sign_controlled(signers, &keys, &event.message())
+}
+
+/// Build an inception instruction committing to the event's next keys
+fn inception_instruction(authority: &Pubkey, event: &TestEvent) -> Instruction {
+    committed_inception_instruction(authority, event, event.next_digest())
+}
+
+/// Build an inception instruction with the next keys digest
+fn committed_inception_instruction(
+    authority: &Pubkey,
+    event: &TestEvent,
+    next_digest: [u8; 32],
+) -> Instruction {
+    let (_, bump) = event.pda();
+    let did = DIDInception {
+        keytype: event.keytype,
+        prefix: event.prefix,
+        bump,
+        keys: event.keys.clone(),
+        relationships: event.relationships.clone().unwrap(),
+        threshold: event.threshold,
+        next_digest,
+    };
+    // Exact storage, falling back to any storage for invalid inceptions
+    let storage = SDMDid::from_inception(did.clone(), authority).map_or(0, |d| d.size());
+    instruction::inception(
+        &id(),
+        authority,
+        authority,
+        InitializeDidAccount {
+            rent: Rent::default().minimum_balance(storage),
+            storage: storage as u64,
+        },
+        did,
+    )
+}
+
+/// Rotation data of the event committing to its next keys
+fn did_rotation(event: &TestEvent) -> DIDRotation {
+    DIDRotation {
+        keytype: event.keytype,
+        prefix: event.prefix,
+        prior_digest: event.prior_digest.unwrap(),
+        digest: event.digest,
+        sn: event.sn,
+        keys: event.keys.clone(),
+        relationships: event.relationships.clone(),
+        threshold: event.threshold,
+        next_digest: event.next_digest(),
+    }
+}
+
+/// Build a rotation instruction committing to the event's next keys
+fn rotation_instruction(authority: &Pubkey, event: &TestEvent) -> Instruction {
+    instruction::rotation(&id(), authority, authority, did_rotation(event))
+}
+
+/// Build a decommission instruction
+fn decommission_instruction(authority: &Pubkey, event: &TestEvent) -> Instruction {
+    let decommission = DIDDecommission {
+        keytype: event.keytype,
+        prefix: event.prefix,
+        prior_digest: event.prior_digest.unwrap(),
+        digest: event.digest,
+        sn: event.sn,
+    };
+    instruction::decommission(&id(), authority, authority, decommission)
+}
+
+/// Signature instruction over the latest event digest by the controllers
+/// of the event, authorizing the close of a DID with current keys
+fn sign_close(event: &TestEvent) -> Instruction {
+    sign_controlled(&[], &event.controllers(), &event.digest)
+}
+
+/// Build a close instruction of the DID of the event
+fn close_instruction(
+    authority: &Pubkey,
+    event: &TestEvent,
+    recipient: &Pubkey,
+    force: bool,
+) -> Instruction {
+    instruction::close(&id(), authority, &event.prefix, recipient, force)
+}
+
+async fn submit(
+    context: &mut TestContext,
+    instructions: &[Instruction],
+) -> Result<(), BanksClientError> {
+    submit_with(context, instructions, &[]).await
+}
+
+/// Submit instructions also signed by additional signers
+async fn submit_with(
+    context: &mut TestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let mut all_signers = vec![&context.authority];
+    all_signers.extend(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.authority.pubkey()),
+        &all_signers,
+        context.recent_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+/// Submit an instruction preceded by the signatures of event by the
+/// authority and the controllers
+async fn submit_signed(
+    context: &mut TestContext,
+    event: &TestEvent,
+    instruction: Instruction,
+) -> Result<(), BanksClientError> {
+    let instructions = [sign_event(&[&context.authority], event), instruction];
+    submit(context, &instructions).await
+}
+
+/// Submit an instruction preceded by the authority's signature of
+/// event and a signature of message by each controller
+async fn submit_controlled(
+    context: &mut TestContext,
+    event: &TestEvent,
+    message: &[u8],
+    controllers: &[&Keypair],
+    instruction: Instruction,
+) -> Result<(), BanksClientError> {
+    let mut instructions = vec![sign_instruction(&context.authority, event)];
+    instructions.extend(controllers.iter().map(|c| sign_message(c, message)));
+    instructions.push(instruction);
+    submit(context, &instructions).await
+}
+
+/// Submit an instruction preceded by the signatures of event by the
+/// authority and the controllers as a new transaction, not the failed
+/// transaction of an earlier identical submission
+async fn resubmit_signed(
+    context: &mut TestContext,
+    event: &TestEvent,
+    instruction: Instruction,
+) -> Result<(), BanksClientError> {
+    let instructions = [
+        ComputeBudgetInstruction::set_compute_unit_limit(400_000),
+        sign_event(&[&context.authority], event),
+        instruction,
+    ];
+    submit(context, &instructions).await
+}
+
+/// Incept a DID with a signed event
+async fn incept(context: &mut TestContext, keys: &[Pubkey], next_keys: &[Pubkey]) -> TestEvent {
+    let event = TestEvent::inception(keys).committing(next_keys);
+    incept_event(context, &event).await;
+    event
+}
+
+/// Incept a DID from the signed inception event
+async fn incept_event(context: &mut TestContext, event: &TestEvent) {
+    let authority = context.authority.pubkey();
+    let instruction = inception_instruction(&authority, event);
+    submit_signed(context, event, instruction).await.unwrap();
+}
+
+/// Incept an Ed25519 DID controlled by keypairs with a threshold
+async fn incept_ed25519(
+    context: &mut TestContext,
+    controllers: &[Keypair],
+    threshold: u64,
+    next_keys: &[Pubkey],
+) -> TestEvent {
+    let mut event = TestEvent::inception(&pubkeys(controllers)).committing(next_keys);
+    event.threshold = threshold;
+    incept_event(context, &event).await;
+    event
+}
+
+fn keypairs(count: usize) -> Vec<Keypair> {
+    (0..count).map(|_| Keypair::new()).collect()
+}
+
+fn pubkeys(keypairs: &[Keypair]) -> Vec<Pubkey> {
+    keypairs.iter().map(|k| k.pubkey()).collect()
+}
+
+async fn get_did(context: &mut TestContext, event: &TestEvent) -> SDMDid {
+    let account = context
+        .banks_client
+        .get_account(event.pda().0)
+        .await
+        .unwrap()
+        .unwrap();
+    SDMDid::unpack(&account.data).unwrap()
+}
+
+/// Asserts the transaction failed with the program error, the program
+/// instruction is the last of the transaction
+fn assert_sdm_error(result: Result<(), BanksClientError>, expected: SDMProgramError) {
+    match result.unwrap_err().unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => {
+            assert_eq!(code, expected as u32)
+        }
+        error => panic!("unexpected error {:?}", error),
+    }
+}
+
+#[tokio::test]
+async fn test_inception_signed_event_pass() {
+    let mut context = start().await;
+    let keys = vec![controller(), controller()];
+    let next_keys = vec![controller(), controller()];
+    let event = incept(&mut context, &keys, &next_keys).await;
+    let did = get_did(&mut context, &event).await;
+    assert_eq!(did.did_doc.keys, keys);
+    assert_eq!(did.did_doc.next_digest, event.next_digest());
+    assert_eq!(did.did_doc.sn, 0);
+    assert_eq!(did.did_doc.digest, event.prefix);
+}
+
+#[tokio::test]
+async fn test_inception_missing_signature_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = TestEvent::inception(&[controller()]);
+    // Preceded by an instruction that is not a signature verification
+    let instructions = [
+        system_instruction::transfer(&authority, &Pubkey::new_unique(), 1_000_000),
+        inception_instruction(&authority, &event),
+    ];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::MissingSignatureInstruction,
+    );
+    // Not preceded by any instruction
+    let instructions = [inception_instruction(&authority, &event)];
+    assert_eq!(
+        submit(&mut context, &instructions)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(SDMProgramError::MissingSignatureInstruction as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_inception_signer_not_authority_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = TestEvent::inception(&[controller()]);
+    let instructions = [
+        sign_instruction(&Keypair::new(), &event),
+        inception_instruction(&authority, &event),
+    ];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::SignerNotAuthority,
+    );
+}
+
+#[tokio::test]
+async fn test_inception_event_mismatch_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = TestEvent::inception(&[controller()]);
+    let instruction = || inception_instruction(&authority, &event);
+    // Signed keys differ from the payload keys
+    let mut signed = event.clone();
+    signed.keys = vec![Pubkey::new_unique().into()];
+    assert_sdm_error(
+        submit_signed(&mut context, &signed, instruction()).await,
+        SDMProgramError::EventKeysMismatch,
+    );
+    // Signed event is not an inception
+    let mut signed = event.clone();
+    signed.ilk = "rot";
+    assert_sdm_error(
+        submit_signed(&mut context, &signed, instruction()).await,
+        SDMProgramError::EventTypeMismatch,
+    );
+    // Signed prefix differs from the payload prefix
+    let mut signed = event.clone();
+    signed.prefix = Pubkey::new_unique().to_bytes();
+    assert_sdm_error(
+        submit_signed(&mut context, &signed, instruction()).await,
+        SDMProgramError::EventPrefixMismatch,
+    );
+    // Signed event is not the first event
+    let mut signed = event.clone();
+    signed.sn = 1;
+    assert_sdm_error(
+        submit_signed(&mut context, &signed, instruction()).await,
+        SDMProgramError::EventSequenceMismatch,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_signed_event_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller(), controller()];
+    let inception = incept(&mut context, &[controller()], &keys).await;
+    let next_keys = vec![controller()];
+    let rotation = inception.rotation(&keys).committing(&next_keys);
+    let instruction = rotation_instruction(&authority, &rotation);
+    submit_signed(&mut context, &rotation, instruction)
+        .await
+        .unwrap();
+    let did = get_did(&mut context, &inception).await;
+    assert_eq!(did.did_doc.keys, keys);
+    assert_eq!(did.did_doc.next_digest, rotation.next_digest());
+    assert_eq!(did.did_doc.sn, rotation.sn);
+    assert_eq!(did.did_doc.digest, rotation.digest);
+}
+
+/// Basic prefix of an Ed25519 key as hbkr renders it
+fn hbkr_key(key: &Pubkey) -> BasicPrefix {
+    BasicPrefix::new(Basic::ED25519, Publickey::new(key.to_bytes().to_vec()))
+}
+
+/// Digest of a self-addressing prefix as 32 byte array
+fn hbkr_digest(digest: &SelfAddressingPrefix) -> [u8; 32] {
+    digest.digest.as_slice().try_into().unwrap()
+}
+
+/// Next keys commitment of an hbkr establishment event
+fn hbkr_next_digest(event: &EventMessage<SaidEvent<Event>>) -> [u8; 32] {
+    let key_config = match &event.event.content.event_data {
+        EventData::Icp(icp) => &icp.key_config,
+        EventData::Rot(rot) => &rot.key_config,
+        _ => unreachable!(),
+    };
+    hbkr_digest(key_config.threshold_key_digest.as_ref().unwrap())
+}
+
+#[test]
+fn test_next_keys_digest_matches_hbkr() {
+    let keys = vec![controller(), controller()];
+    let hbkr_keys = keys.iter().map(hbkr_key).collect::<Vec<BasicPrefix>>();
+    for threshold in [1, 2, 16] {
+        let commitment = nxt_commitment(
+            &SignatureThreshold::simple(threshold),
+            &hbkr_keys,
+            &SelfAddressing::Blake3_256,
+        );
+        assert_eq!(
+            next_keys_digest(SMDKeyType::Ed25519, threshold, &keys),
+            hbkr_digest(&commitment)
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_hbkr_events_pass() {
+    // Inception and rotation built and committed to by hbkr
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = keypairs(2);
+    let next_keys = vec![controller()];
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(pubkeys(&controllers).iter().map(hbkr_key).collect())
+        .with_threshold(&SignatureThreshold::simple(2))
+        .with_next_keys(pubkeys(&controllers)[..1].iter().map(hbkr_key).collect())
+        .with_next_threshold(&SignatureThreshold::simple(1))
+        .build()
+        .unwrap();
+    let prefix = hbkr_digest(&icp.get_digest());
+    let (pda, bump) = instruction::find_did_address(&prefix);
+    let inception = DIDInception {
+        keytype: SMDKeyType::Ed25519,
+        prefix,
+        bump,
+        keys: pubkeys(&controllers).iter().map(|k| (*k).into()).collect(),
+        relationships: vec![ALL_RELATIONSHIPS; 2],
+        threshold: 2,
+        next_digest: hbkr_next_digest(&icp),
+    };
+    let storage = SDMDid::from_inception(inception.clone(), &authority)
+        .unwrap()
+        .size();
+    let message = icp.serialize().unwrap();
+    let instructions = [
+        sign_message(&context.authority, &message),
+        instruction::inception(
+            &id(),
+            &authority,
+            &authority,
+            InitializeDidAccount {
+                rent: Rent::default().minimum_balance(storage),
+                storage: storage as u64,
+            },
+            inception,
+        ),
+    ];
+    submit(&mut context, &instructions).await.unwrap();
+    // The current keys sign the rotation to the committed key and threshold
+    let rot = EventMsgBuilder::new(EventTypeTag::Rot)
+        .with_prefix(&icp.event.get_prefix())
+        .with_sn(1)
+        .with_previous_event(&icp.get_digest())
+        .with_keys(pubkeys(&controllers)[..1].iter().map(hbkr_key).collect())
+        .with_threshold(&SignatureThreshold::simple(1))
+        .with_next_keys(next_keys.iter().map(hbkr_key).collect())
+        .with_next_threshold(&SignatureThreshold::simple(1))
+        .build()
+        .unwrap();
+    let rotation = DIDRotation {
+        keytype: SMDKeyType::Ed25519,
+        prefix,
+        prior_digest: prefix,
+        digest: hbkr_digest(&rot.get_digest()),
+        sn: 1,
+        keys: vec![controllers[0].pubkey().into()],
+        relationships: Some(vec![ALL_RELATIONSHIPS]),
+        threshold: 1,
+        next_digest: hbkr_next_digest(&rot),
+    };
+    let message = rot.serialize().unwrap();
+    let instructions = [
+        sign_message(&context.authority, &message),
+        sign_message(&controllers[0], &message),
+        sign_message(&controllers[1], &message),
+        instruction::rotation(&id(), &authority, &authority, rotation),
+    ];
+    submit(&mut context, &instructions).await.unwrap();
+    let account = context
+        .banks_client
+        .get_account(pda)
+        .await
+        .unwrap()
+        .unwrap();
+    let did = SDMDid::unpack(&account.data).unwrap();
+    assert_eq!(did.did_doc.keys, vec![controllers[0].pubkey()]);
+    assert_eq!(
+        did.did_doc.next_digest,
+        next_keys_digest(SMDKeyType::Ed25519, 1, &next_keys)
+    );
+}
+
+#[tokio::test]
+async fn test_inception_commitment_mismatch_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = TestEvent::inception(&[controller()]).committing(&[controller()]);
+    let signed = event.clone().committing(&[controller()]);
+    let instruction = inception_instruction(&authority, &event);
+    assert_sdm_error(
+        submit_signed(&mut context, &signed, instruction).await,
+        SDMProgramError::EventCommitmentMismatch,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_commitment_mismatch_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller()];
+    let inception = incept(&mut context, &[controller()], &keys).await;
+    // The rotation data commits to keys other than the signed event
+    let rotation = inception.rotation(&keys).committing(&[controller()]);
+    let signed = rotation.clone().committing(&[controller()]);
+    let instruction = rotation_instruction(&authority, &rotation);
+    assert_sdm_error(
+        submit_signed(&mut context, &signed, instruction).await,
+        SDMProgramError::EventCommitmentMismatch,
+    );
+    // As does a threshold other than committed to
+    let mut rotation = inception.rotation(&keys);
+    rotation.next_threshold = Some(1);
+    let mut signed = rotation.clone();
+    signed.next_threshold = Some(2);
+    let instruction = rotation_instruction(&authority, &rotation);
+    assert_sdm_error(
+        submit_signed(&mut context, &signed, instruction).await,
+        SDMProgramError::EventCommitmentMismatch,
+    );
+}
+
+#[tokio::test]
+async fn test_clock_recorded_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller()];
+    let inception = incept(&mut context, &[controller()], &keys).await;
+    let clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    let did = get_did(&mut context, &inception).await;
+    assert_eq!(did.created_slot(), clock.slot);
+    assert_eq!(did.updated_slot(), clock.slot);
+    assert_eq!(did.updated_unix_timestamp(), clock.unix_timestamp);
+
+    let rotation = inception.rotation(&keys);
+    let instruction = rotation_instruction(&authority, &rotation);
+    submit_signed(&mut context, &rotation, instruction)
+        .await
+        .unwrap();
+    let clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    let rotated = get_did(&mut context, &inception).await;
+    assert_eq!(rotated.created_slot(), did.created_slot());
+    assert_eq!(rotated.updated_slot(), clock.slot);
+    assert_eq!(rotated.updated_unix_timestamp(), clock.unix_timestamp);
+}
+
+#[tokio::test]
+async fn test_rotation_unsigned_keys_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller()];
+    let inception = incept(&mut context, &[controller()], &keys).await;
+    let rotation = inception.rotation(&keys);
+    let mut signed = rotation.clone();
+    signed.keys = vec![Pubkey::new_unique().into()];
+    let instruction = rotation_instruction(&authority, &rotation);
+    assert_sdm_error(
+        submit_signed(&mut context, &signed, instruction).await,
+        SDMProgramError::EventKeysMismatch,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_nested_or_duplicate_fields_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller()];
+    let inception = incept(&mut context, &[controller()], &keys).await;
+    let rotation = inception.rotation(&keys);
+    let message = String::from_utf8(rotation.message()).unwrap();
+    // The rotation fields are nested in a seal of an interaction
+    let interaction = String::from_utf8(inception.interaction(&[]).message()).unwrap();
+    let nested = interaction.replace(r#""a":[]"#, &format!(r#""a":[{}]"#, message));
+    let instructions = [
+        sign_message(&context.authority, nested.as_bytes()),
+        rotation_instruction(&authority, &rotation),
+    ];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::EventTypeMismatch,
+    );
+    // The rotation repeats its keys field with other keys
+    let duplicate = message.replacen('{', r#"{"k":[],"#, 1);
+    let instructions = [
+        sign_message(&context.authority, duplicate.as_bytes()),
+        rotation_instruction(&authority, &rotation),
+    ];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::InvalidEventMessage,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_uncommitted_keys_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let inception = incept(&mut context, &[controller()], &[controller()]).await;
+    // Keys are signed but were not committed to at inception
+    let rotation = inception.rotation(&[controller()]);
+    let instruction = rotation_instruction(&authority, &rotation);
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, instruction).await,
+        SDMProgramError::PreRotationMismatch,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_sequence_mismatch_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller()];
+    let inception = incept(&mut context, &[controller()], &keys).await;
+    let rotation = inception.rotation(&keys);
+    // Signed event is at a different position than the payload
+    let mut signed = rotation.clone();
+    signed.sn += 1;
+    let instruction = rotation_instruction(&authority, &rotation);
+    assert_sdm_error(
+        submit_signed(&mut context, &signed, instruction).await,
+        SDMProgramError::EventSequenceMismatch,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_replay_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    // The keys rotate to themselves, signing the replay as current keys
+    let keys = vec![controller()];
+    let next_keys = vec![controller()];
+    let inception = incept(&mut context, &keys, &keys).await;
+    let rotation = inception.rotation(&keys).committing(&next_keys);
+    let instruction = rotation_instruction(&authority, &rotation);
+    submit_signed(&mut context, &rotation, instruction)
+        .await
+        .unwrap();
+    // Replaying the rotation, with a fresh transaction, is rejected
+    let instructions = [
+        ComputeBudgetInstruction::set_compute_unit_limit(400_000),
+        sign_event(&[&context.authority], &rotation),
+        rotation_instruction(&authority, &rotation),
+    ];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::EventOutOfSequence,
+    );
+    // A stale sequence number following the latest digest is rejected
+    let mut stale = rotation.rotation(&next_keys);
+    stale.sn = rotation.sn;
+    let instruction = rotation_instruction(&authority, &stale);
+    assert_sdm_error(
+        submit_signed(&mut context, &stale, instruction).await,
+        SDMProgramError::EventOutOfSequence,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_gap_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller()];
+    let inception = incept(&mut context, &[controller()], &keys).await;
+    // Rotation skipping a sequence number after the latest event
+    let mut gap = inception.rotation(&keys);
+    gap.sn += 1;
+    let instruction = rotation_instruction(&authority, &gap);
+    assert_sdm_error(
+        submit_signed(&mut context, &gap, instruction).await,
+        SDMProgramError::EventOutOfSequence,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_fork_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller()];
+    let inception = incept(&mut context, &[controller()], &keys).await;
+    // Rotation from an event that is not the latest
+    let mut fork = inception.rotation(&keys);
+    fork.prior_digest = Some(Pubkey::new_unique().to_bytes());
+    let instruction = rotation_instruction(&authority, &fork);
+    assert_sdm_error(
+        submit_signed(&mut context, &fork, instruction).await,
+        SDMProgramError::EventOutOfSequence,
+    );
+}
+
+#[tokio::test]
+async fn test_decommission_signed_event_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let inception = incept(&mut context, &[controller()], &[controller()]).await;
+    let decommission = inception.decommission();
+    let instruction = decommission_instruction(&authority, &decommission);
+    submit_signed(&mut context, &decommission, instruction)
+        .await
+        .unwrap();
+    let did = get_did(&mut context, &inception).await;
+    assert!(did.did_doc.keys.is_empty());
+    assert_eq!(did.did_doc.sn, decommission.sn);
+}
+
+#[tokio::test]
+async fn test_inception_invalid_threshold_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    for threshold in [0, 3] {
+        let mut event = TestEvent::inception(&[controller(), controller()]);
+        event.threshold = threshold;
+        let instruction = inception_instruction(&authority, &event);
+        assert_sdm_error(
+            submit_signed(&mut context, &event, instruction).await,
+            SDMProgramError::InvalidThreshold,
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_inception_event_threshold_mismatch_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = TestEvent::inception(&[controller(), controller()]);
+    let mut signed = event.clone();
+    signed.threshold = 2;
+    let instruction = inception_instruction(&authority, &event);
+    assert_sdm_error(
+        submit_signed(&mut context, &signed, instruction).await,
+        SDMProgramError::EventThresholdMismatch,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_threshold_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = keypairs(3);
+    let next_keys = vec![controller(), controller()];
+    let inception = incept_ed25519(&mut context, &controllers, 2, &next_keys).await;
+    // Two of three current keys sign and the threshold is raised
+    let mut rotation = inception.rotation(&next_keys);
+    rotation.threshold = 2;
+    let instruction = rotation_instruction(&authority, &rotation);
+    submit_controlled(
+        &mut context,
+        &rotation,
+        &rotation.message(),
+        &[&controllers[0], &controllers[2]],
+        instruction,
+    )
+    .await
+    .unwrap();
+    let did = get_did(&mut context, &inception).await;
+    assert_eq!(did.did_doc.keys, next_keys);
+    assert_eq!(did.did_doc.threshold, 2);
+}
+
+#[tokio::test]
+async fn test_rotation_threshold_not_met_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = keypairs(3);
+    let next_keys = vec![controller()];
+    let inception = incept_ed25519(&mut context, &controllers, 2, &next_keys).await;
+    let rotation = inception.rotation(&next_keys);
+    let instruction = || rotation_instruction(&authority, &rotation);
+    // One current key signed
+    assert_sdm_error(
+        submit_controlled(
+            &mut context,
+            &rotation,
+            &rotation.message(),
+            &[&controllers[1]],
+            instruction(),
+        )
+        .await,
+        SDMProgramError::ThresholdNotMet,
+    );
+    // The same key signing twice counts once
+    assert_sdm_error(
+        submit_controlled(
+            &mut context,
+            &rotation,
+            &rotation.message(),
+            &[&controllers[1], &controllers[1]],
+            instruction(),
+        )
+        .await,
+        SDMProgramError::ThresholdNotMet,
+    );
+    // Keys that are not current keys do not count
+    let others = keypairs(2);
+    assert_sdm_error(
+        submit_controlled(
+            &mut context,
+            &rotation,
+            &rotation.message(),
+            &[&others[0], &others[1]],
+            instruction(),
+        )
+        .await,
+        SDMProgramError::ThresholdNotMet,
+    );
+    // Current keys signing a different message do not count
+    assert_sdm_error(
+        submit_controlled(
+            &mut context,
+            &rotation,
+            &inception.message(),
+            &[&controllers[0], &controllers[1]],
+            instruction(),
+        )
+        .await,
+        SDMProgramError::ThresholdNotMet,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_invalid_threshold_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller()];
+    // Committing to a threshold beyond the next keys
+    let mut inception = TestEvent::inception(&[controller()]).committing(&keys);
+    inception.next_threshold = Some(2);
+    incept_event(&mut context, &inception).await;
+    let mut rotation = inception.rotation(&keys);
+    rotation.threshold = 2;
+    let instruction = rotation_instruction(&authority, &rotation);
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, instruction).await,
+        SDMProgramError::InvalidThreshold,
+    );
+}
+
+#[tokio::test]
+async fn test_decommission_threshold_not_met_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = keypairs(2);
+    let inception = incept_ed25519(&mut context, &controllers, 2, &[]).await;
+    let decommission = inception.decommission();
+    let instruction = decommission_instruction(&authority, &decommission);
+    assert_sdm_error(
+        submit_controlled(
+            &mut context,
+            &decommission,
+            &decommission.message(),
+            &[&controllers[0]],
+            instruction,
+        )
+        .await,
+        SDMProgramError::ThresholdNotMet,
+    );
+    let instruction = decommission_instruction(&authority, &decommission);
+    submit_controlled(
+        &mut context,
+        &decommission,
+        &decommission.message(),
+        &[&controllers[0], &controllers[1]],
+        instruction,
+    )
+    .await
+    .unwrap();
+    let did = get_did(&mut context, &inception).await;
+    assert_eq!(did.did_doc.threshold, 0);
+}
+
+#[tokio::test]
+async fn test_close_threshold() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = keypairs(2);
+    let inception = incept_ed25519(&mut context, &controllers, 2, &[]).await;
+    // Current keys sign the latest event digest
+    let instructions = [
+        sign_message(&controllers[0], &inception.digest),
+        close_instruction(&authority, &inception, &authority, true),
+    ];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::ThresholdNotMet,
+    );
+    let instructions = [
+        sign_message(&controllers[0], &inception.digest),
+        sign_message(&controllers[1], &inception.digest),
+        close_instruction(&authority, &inception, &authority, true),
+    ];
+    submit(&mut context, &instructions).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_close_decommissioned_without_controllers_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = keypairs(1);
+    let inception = incept_ed25519(&mut context, &controllers, 1, &[]).await;
+    let decommission = inception.decommission();
+    let instruction = decommission_instruction(&authority, &decommission);
+    submit_controlled(
+        &mut context,
+        &decommission,
+        &decommission.message(),
+        &[&controllers[0]],
+        instruction,
+    )
+    .await
+    .unwrap();
+    let instructions = [close_instruction(&authority, &inception, &authority, false)];
+    submit(&mut context, &instructions).await.unwrap();
+}
+
+/// Incept and decommission a DID
+async fn decommissioned(context: &mut TestContext) -> TestEvent {
+    let authority = context.authority.pubkey();
+    let inception = incept(context, &[controller()], &[]).await;
+    let decommission = inception.decommission();
+    let instruction = decommission_instruction(&authority, &decommission);
+    submit_signed(context, &decommission, instruction)
+        .await
+        .unwrap();
+    inception
+}
+
+#[tokio::test]
+async fn test_close_recipient_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let inception = decommissioned(&mut context).await;
+    let pda = inception.pda().0;
+    let log = inception.log();
+    // The event log closes with the DID
+    let lamports = context.banks_client.get_balance(pda).await.unwrap()
+        + context.banks_client.get_balance(log).await.unwrap();
+    let recipient = Pubkey::new_unique();
+    let instructions = [close_instruction(&authority, &inception, &recipient, false)];
+    submit(&mut context, &instructions).await.unwrap();
+    assert_eq!(
+        context.banks_client.get_balance(recipient).await.unwrap(),
+        lamports
+    );
+    for account in [pda, log] {
+        assert!(context
+            .banks_client
+            .get_account(account)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_close_not_decommissioned_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let inception = incept(&mut context, &[controller()], &[]).await;
+    let instructions = [close_instruction(&authority, &inception, &authority, false)];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::DidNotDecommissioned,
+    );
+    // Forced close is allowed
+    let instructions = [
+        sign_close(&inception),
+        close_instruction(&authority, &inception, &authority, true),
+    ];
+    submit(&mut context, &instructions).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_close_invalid_authority_fail() {
+    let mut context = start().await;
+    let inception = decommissioned(&mut context).await;
+    let other = Keypair::new();
+    let instructions = [close_instruction(
+        &other.pubkey(),
+        &inception,
+        &other.pubkey(),
+        false,
+    )];
+    assert_sdm_error(
+        submit_with(&mut context, &instructions, &[&other]).await,
+        SDMProgramError::InvalidAuthority,
+    );
+}
+
+#[tokio::test]
+async fn test_close_not_program_owned_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let instructions = [close_instruction(
+        &authority,
+        &TestEvent::inception(&[controller()]),
+        &authority,
+        true,
+    )];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::IncorrectDidOwner,
+    );
+}
+
+#[tokio::test]
+async fn test_close_recipient_is_did_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let inception = decommissioned(&mut context).await;
+    let pda = inception.pda().0;
+    let instructions = [close_instruction(&authority, &inception, &pda, false)];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::InvalidCloseRecipient,
+    );
+}
+
+#[tokio::test]
+async fn test_close_event_log_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let inception = decommissioned(&mut context).await;
+    let log = inception.log();
+    let instructions = [close_instruction(&authority, &inception, &log, false)];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::InvalidCloseRecipient,
+    );
+    // The log of another DID
+    let mut instruction = close_instruction(&authority, &inception, &authority, false);
+    instruction.accounts[4] = AccountMeta::new(decommissioned(&mut context).await.log(), false);
+    assert_sdm_error(
+        submit(&mut context, &[instruction]).await,
+        SDMProgramError::InvalidEventLog,
+    );
+}
+
+#[tokio::test]
+async fn test_inception_account_validation_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = TestEvent::inception(&[controller()]);
+    let instruction = || inception_instruction(&authority, &event);
+    // Authority did not sign
+    let mut unsigned = instruction();
+    unsigned.accounts[1] = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+    assert_eq!(
+        submit_signed(&mut context, &event, unsigned)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(1, InstructionError::MissingRequiredSignature)
+    );
+    // DID account is read only
+    let mut readonly = instruction();
+    readonly.accounts[2].is_writable = false;
+    assert_sdm_error(
+        submit_signed(&mut context, &event, readonly).await,
+        SDMProgramError::AccountNotWritable,
+    );
+    // DID account is not derived from the prefix
+    let mut underived = instruction();
+    underived.accounts[2].pubkey = Pubkey::new_unique();
+    assert_sdm_error(
+        submit_signed(&mut context, &event, underived).await,
+        SDMProgramError::InvalidDidAddress,
+    );
+    // System program is substituted
+    let mut system = instruction();
+    system.accounts[3].pubkey = Pubkey::new_unique();
+    assert_sdm_error(
+        submit_signed(&mut context, &event, system).await,
+        SDMProgramError::InvalidSystemProgram,
+    );
+    // Instructions sysvar is substituted
+    let mut instructions = instruction();
+    instructions.accounts[4].pubkey = sysvar::clock::id();
+    assert_sdm_error(
+        submit_signed(&mut context, &event, instructions).await,
+        SDMProgramError::InvalidSysvar,
+    );
+}
+
+#[tokio::test]
+async fn test_inception_non_canonical_bump_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = TestEvent::inception(&[controller()]);
+    let (_, canonical) = event.pda();
+    let (pda, bump) = (0..canonical)
+        .rev()
+        .find_map(|bump| {
+            Pubkey::create_program_address(&[&event.prefix, &[bump]], &id())
+                .ok()
+                .map(|pda| (pda, bump))
+        })
+        .unwrap();
+    let mut instruction = inception_instruction(&authority, &event);
+    instruction.accounts[2].pubkey = pda;
+    if let SDMInstruction::SDMInception(init, mut did) =
+        SDMInstruction::try_from_slice(&instruction.data).unwrap()
+    {
+        did.bump = bump;
+        instruction.data = SDMInstruction::SDMInception(init, did)
+            .try_to_vec()
+            .unwrap();
+    }
+    assert_sdm_error(
+        submit_signed(&mut context, &event, instruction).await,
+        SDMProgramError::InvalidDidAddress,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_account_validation_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller()];
+    let inception = incept(&mut context, &[controller()], &keys).await;
+    let rotation = inception.rotation(&keys);
+    let instruction = || rotation_instruction(&authority, &rotation);
+    // DID account is read only
+    let mut readonly = instruction();
+    readonly.accounts[2].is_writable = false;
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, readonly).await,
+        SDMProgramError::AccountNotWritable,
+    );
+    // DID account is not owned by the program
+    let mut unowned = instruction();
+    unowned.accounts[2].pubkey = authority;
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, unowned).await,
+        SDMProgramError::IncorrectDidOwner,
+    );
+    // Instructions sysvar is substituted
+    let mut instructions = instruction();
+    instructions.accounts[4].pubkey = sysvar::clock::id();
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, instructions).await,
+        SDMProgramError::InvalidSysvar,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_did_address_mismatch_fail() {
+    // Copy a DID account to an address not derived from its prefix
+    let mut context = start().await;
+    let keys = vec![controller()];
+    let inception = incept(&mut context, &[controller()], &keys).await;
+    let account = context
+        .banks_client
+        .get_account(inception.pda().0)
+        .await
+        .unwrap()
+        .unwrap();
+    let copy = Pubkey::new_unique();
+    let mut context = start_with(vec![(copy, account)]).await;
+    let authority = context.authority.pubkey();
+    let rotation = inception.rotation(&keys);
+    let mut instruction = rotation_instruction(&authority, &rotation);
+    instruction.accounts[2].pubkey = copy;
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, instruction).await,
+        SDMProgramError::InvalidDidAddress,
+    );
+}
+
+#[tokio::test]
+async fn test_close_recipient_readonly_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let inception = decommissioned(&mut context).await;
+    let mut instruction = close_instruction(&authority, &inception, &authority, false);
+    instruction.accounts[2] = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+    assert_sdm_error(
+        submit(&mut context, &[instruction]).await,
+        SDMProgramError::AccountNotWritable,
+    );
+}
+
+/// Asserts the DID account holds exactly its state and rent exemption
+async fn assert_exact_account(context: &mut TestContext, event: &TestEvent) -> usize {
+    let account = context
+        .banks_client
+        .get_account(event.pda().0)
+        .await
+        .unwrap()
+        .unwrap();
+    let did = SDMDid::unpack(&account.data).unwrap();
+    assert_eq!(account.data.len(), did.size());
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(did.size())
+    );
+    account.data.len()
+}
+
+#[tokio::test]
+async fn test_inception_exact_size_pass() {
+    let mut context = start().await;
+    let event = incept(&mut context, &[controller()], &[]).await;
+    assert_exact_account(&mut context, &event).await;
+}
+
+#[tokio::test]
+async fn test_inception_prefunded_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = TestEvent::inception(&[controller()]);
+    // Lamports sent to the DID address before the inception
+    let prefund = [system_instruction::transfer(
+        &authority,
+        &event.pda().0,
+        Rent::default().minimum_balance(0),
+    )];
+    submit(&mut context, &prefund).await.unwrap();
+    incept_event(&mut context, &event).await;
+    assert_exact_account(&mut context, &event).await;
+}
+
+#[tokio::test]
+async fn test_inception_invalid_size_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = TestEvent::inception(&[controller()]);
+    let mut instruction = inception_instruction(&authority, &event);
+    if let SDMInstruction::SDMInception(mut init, did) =
+        SDMInstruction::try_from_slice(&instruction.data).unwrap()
+    {
+        init.storage += 1024;
+        init.rent = Rent::default().minimum_balance(init.storage as usize);
+        instruction.data = SDMInstruction::SDMInception(init, did)
+            .try_to_vec()
+            .unwrap();
+    }
+    assert_sdm_error(
+        submit_signed(&mut context, &event, instruction).await,
+        SDMProgramError::InvalidAccountSize,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_resize_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let more_keys = vec![controller(), controller(), controller()];
+    let fewer_keys = vec![controller()];
+    let inception = incept(&mut context, &[controller()], &more_keys).await;
+    let size = assert_exact_account(&mut context, &inception).await;
+    // Growth is paid by the authority
+    let rotation = inception.rotation(&more_keys).committing(&fewer_keys);
+    let instruction = rotation_instruction(&authority, &rotation);
+    submit_signed(&mut context, &rotation, instruction)
+        .await
+        .unwrap();
+    let grown = assert_exact_account(&mut context, &inception).await;
+    assert!(grown > size);
+    // Shrink refunds the authority more than the transaction fees, net
+    // of the rent of the event record
+    let before = context.banks_client.get_balance(authority).await.unwrap();
+    let log_before = context
+        .banks_client
+        .get_balance(inception.log())
+        .await
+        .unwrap();
+    let shrink = rotation.rotation(&fewer_keys);
+    let instruction = rotation_instruction(&authority, &shrink);
+    submit_signed(&mut context, &shrink, instruction)
+        .await
+        .unwrap();
+    let shrunk = assert_exact_account(&mut context, &inception).await;
+    assert!(shrunk < grown);
+    let log_growth = context
+        .banks_client
+        .get_balance(inception.log())
+        .await
+        .unwrap()
+        - log_before;
+    assert!(context.banks_client.get_balance(authority).await.unwrap() + log_growth > before);
+}
+
+#[tokio::test]
+async fn test_decommission_shrink_pass() {
+    let mut context = start().await;
+    let inception = decommissioned(&mut context).await;
+    assert_exact_account(&mut context, &inception).await;
+}
+
+/// A version 1 DID account of the event prefix with ten times its
+/// storage, controlled by a funded legacy authority
+fn legacy_accounts(event: &TestEvent, state: SDMDidState) -> (Keypair, Vec<(Pubkey, Account)>) {
+    let authority = Keypair::new();
+    let (pda, bump) = event.pda();
+    let doc = SDMDidDocV1 {
+        state,
+        keytype: event.keytype,
+        authority: authority.pubkey(),
+        prefix: event.prefix,
+        bump,
+        keys: event.keys.iter().map(|k| Pubkey::new(k.as_ref())).collect(),
+    };
+    let mut data = (true, 1u16, doc).try_to_vec().unwrap();
+    data.resize(data.len() * 10, 0);
+    let accounts = vec![
+        (
+            authority.pubkey(),
+            Account::new(1_000_000_000, 0, &system_program::id()),
+        ),
+        (
+            pda,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        ),
+    ];
+    (authority, accounts)
+}
+
+fn migrate_instruction(authority: &Pubkey, event: &TestEvent) -> Instruction {
+    instruction::migrate(&id(), authority, authority, &event.pda().0)
+}
+
+#[tokio::test]
+async fn test_migrate_v1_pass() {
+    let event = TestEvent::inception(&[controller()]);
+    let (authority, accounts) = legacy_accounts(&event, SDMDidState::Inception);
+    let mut context = start_with(accounts).await;
+    let before = context
+        .banks_client
+        .get_balance(authority.pubkey())
+        .await
+        .unwrap();
+    let instructions = [migrate_instruction(&authority.pubkey(), &event)];
+    submit_with(&mut context, &instructions, &[&authority])
+        .await
+        .unwrap();
+    let data = context
+        .banks_client
+        .get_account(event.pda().0)
+        .await
+        .unwrap()
+        .unwrap()
+        .data;
+    assert_eq!(u16::from_le_bytes([data[1], data[2]]), CURRENT_DATA_VERSION);
+    assert_exact_account(&mut context, &event).await;
+    // The excess storage rent is refunded
+    assert!(
+        context
+            .banks_client
+            .get_balance(authority.pubkey())
+            .await
+            .unwrap()
+            > before
+    );
+}
+
+#[tokio::test]
+async fn test_migrate_invalid_authority_fail() {
+    let event = TestEvent::inception(&[controller()]);
+    let (_, accounts) = legacy_accounts(&event, SDMDidState::Inception);
+    let mut context = start_with(accounts).await;
+    let authority = context.authority.pubkey();
+    let instructions = [migrate_instruction(&authority, &event)];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::InvalidAuthority,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_v1_rotated_pass() {
+    // Version 1 recorded neither the latest event nor a commitment
+    let mut event = TestEvent::inception(&[controller()]);
+    event.sn = 3;
+    event.digest = Pubkey::new_unique().to_bytes();
+    let (authority, accounts) = legacy_accounts(&event, SDMDidState::Rotated);
+    let mut context = start_with(accounts).await;
+    let rotation = event.rotation(&[controller()]);
+    let instructions = [
+        sign_event(&[&authority], &rotation),
+        rotation_instruction(&authority.pubkey(), &rotation),
+    ];
+    submit_with(&mut context, &instructions, &[&authority])
+        .await
+        .unwrap();
+    let did = get_did(&mut context, &event).await;
+    assert_eq!(did.did_doc.sn, rotation.sn);
+    assert_eq!(did.did_doc.digest, rotation.digest);
+    assert_eq!(did.did_doc.keys, rotation.keys);
+    assert_exact_account(&mut context, &event).await;
+}
+
+#[tokio::test]
+async fn test_rotation_decommissioned_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let inception = decommissioned(&mut context).await;
+    let decommission = inception.decommission();
+    let rotation = decommission.rotation(&[controller()]);
+    let instruction = rotation_instruction(&authority, &rotation);
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, instruction).await,
+        SDMProgramError::DidDecommissioned,
+    );
+}
+
+fn service(id: &str, endpoint: &str) -> SDMService {
+    SDMService {
+        id: id.to_string(),
+        service_type: "DIDCommMessaging".to_string(),
+        endpoint: endpoint.to_string(),
+    }
+}
+
+fn add_service_instruction(
+    authority: &Pubkey,
+    event: &TestEvent,
+    service: SDMService,
+) -> Instruction {
+    let add = DIDService {
+        keytype: event.keytype,
+        prefix: event.prefix,
+        service,
+    };
+    instruction::add_service(&id(), authority, authority, add)
+}
+
+fn update_service_instruction(
+    authority: &Pubkey,
+    event: &TestEvent,
+    service: SDMService,
+) -> Instruction {
+    let update = DIDService {
+        keytype: event.keytype,
+        prefix: event.prefix,
+        service,
+    };
+    instruction::update_service(&id(), authority, authority, update)
+}
+
+fn remove_service_instruction(authority: &Pubkey, event: &TestEvent, id: &str) -> Instruction {
+    let remove = DIDServiceRemove {
+        keytype: event.keytype,
+        prefix: event.prefix,
+        id: id.to_string(),
+    };
+    instruction::remove_service(&solana_did_method::id(), authority, authority, remove)
+}
+
+/// Signatures of the service message of the instruction by the
+/// authority, each controller and the controllers of the event
+/// followed by the instruction
+async fn service_instructions(
+    context: &mut TestContext,
+    event: &TestEvent,
+    controllers: &[&Keypair],
+    instruction: Instruction,
+) -> Vec<Instruction> {
+    let message = get_did(context, event)
+        .await
+        .service_message(&instruction.data)
+        .unwrap();
+    let mut signers = vec![&context.authority];
+    signers.extend(controllers);
+    vec![
+        sign_controlled(&signers, &event.controllers(), &message),
+        instruction,
+    ]
+}
+
+/// Submit a service change signed by the authority and controllers
+async fn submit_service(
+    context: &mut TestContext,
+    event: &TestEvent,
+    controllers: &[&Keypair],
+    instruction: Instruction,
+) -> Result<(), BanksClientError> {
+    let instructions = service_instructions(context, event, controllers, instruction).await;
+    submit(context, &instructions).await
+}
+
+#[tokio::test]
+async fn test_service_add_update_remove_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = incept(&mut context, &[controller()], &[]).await;
+    let size = assert_exact_account(&mut context, &event).await;
+    let mediator = service("#mediator", "https://mediator.example.com");
+    let hub = service("#hub", "https://hub.example.com");
+    for added in [&mediator, &hub] {
+        let instruction = add_service_instruction(&authority, &event, added.clone());
+        submit_service(&mut context, &event, &[], instruction)
+            .await
+            .unwrap();
+    }
+    let grown = assert_exact_account(&mut context, &event).await;
+    assert!(grown > size);
+    assert_eq!(
+        get_did(&mut context, &event).await.services(),
+        &[mediator.clone(), hub.clone()]
+    );
+    let moved = service("#mediator", "https://mediator.example.org/didcomm");
+    let instruction = update_service_instruction(&authority, &event, moved.clone());
+    submit_service(&mut context, &event, &[], instruction)
+        .await
+        .unwrap();
+    let instruction = remove_service_instruction(&authority, &event, "#hub");
+    submit_service(&mut context, &event, &[], instruction)
+        .await
+        .unwrap();
+    assert_eq!(get_did(&mut context, &event).await.services(), &[moved]);
+    assert!(assert_exact_account(&mut context, &event).await < grown);
+}
+
+#[tokio::test]
+async fn test_service_exists_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = incept(&mut context, &[controller()], &[]).await;
+    let instruction = add_service_instruction(&authority, &event, service("#hub", "https://a"));
+    submit_service(&mut context, &event, &[], instruction)
+        .await
+        .unwrap();
+    let instruction = add_service_instruction(&authority, &event, service("#hub", "https://b"));
+    assert_sdm_error(
+        submit_service(&mut context, &event, &[], instruction).await,
+        SDMProgramError::ServiceAlreadyExists,
+    );
+}
+
+#[tokio::test]
+async fn test_service_not_found_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = incept(&mut context, &[controller()], &[]).await;
+    let instruction = update_service_instruction(&authority, &event, service("#hub", "https://a"));
+    assert_sdm_error(
+        submit_service(&mut context, &event, &[], instruction).await,
+        SDMProgramError::ServiceNotFound,
+    );
+    let instruction = remove_service_instruction(&authority, &event, "#hub");
+    assert_sdm_error(
+        submit_service(&mut context, &event, &[], instruction).await,
+        SDMProgramError::ServiceNotFound,
+    );
+}
+
+#[tokio::test]
+async fn test_service_invalid_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = incept(&mut context, &[controller()], &[]).await;
+    let too_long = format!("https://{}", "a".repeat(MAX_SERVICE_ENDPOINT_LEN));
+    for invalid in [service("", "https://a"), service("#hub", &too_long)] {
+        let instruction = add_service_instruction(&authority, &event, invalid);
+        assert_sdm_error(
+            submit_service(&mut context, &event, &[], instruction).await,
+            SDMProgramError::InvalidService,
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_service_limit_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = incept(&mut context, &[controller()], &[]).await;
+    for index in 0..MAX_SERVICES {
+        let added = service(&format!("#{}", index), "https://a");
+        let instruction = add_service_instruction(&authority, &event, added);
+        submit_service(&mut context, &event, &[], instruction)
+            .await
+            .unwrap();
+    }
+    let instruction = add_service_instruction(&authority, &event, service("#hub", "https://a"));
+    assert_sdm_error(
+        submit_service(&mut context, &event, &[], instruction).await,
+        SDMProgramError::ServiceLimitExceeded,
+    );
+}
+
+#[tokio::test]
+async fn test_service_replay_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = incept(&mut context, &[controller()], &[]).await;
+    let instruction = add_service_instruction(&authority, &event, service("#hub", "https://a"));
+    let add = service_instructions(&mut context, &event, &[], instruction).await;
+    submit(&mut context, &add).await.unwrap();
+    let instruction = remove_service_instruction(&authority, &event, "#hub");
+    submit_service(&mut context, &event, &[], instruction)
+        .await
+        .unwrap();
+    let did = get_did(&mut context, &event).await;
+    assert!(did.services().is_empty());
+    assert_eq!(did.service_changes(), 2);
+    // The services are as the add was signed against, the count is not
+    context.recent_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.recent_blockhash)
+        .await
+        .unwrap();
+    assert_sdm_error(
+        submit(&mut context, &add).await,
+        SDMProgramError::SignerNotAuthority,
+    );
+}
+
+#[tokio::test]
+async fn test_service_signer_not_authority_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = incept(&mut context, &[controller()], &[]).await;
+    let instruction = add_service_instruction(&authority, &event, service("#hub", "https://a"));
+    let message = get_did(&mut context, &event)
+        .await
+        .service_message(&instruction.data)
+        .unwrap();
+    let instructions = [sign_message(&Keypair::new(), &message), instruction];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::SignerNotAuthority,
+    );
+}
+
+#[tokio::test]
+async fn test_service_stale_message_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = incept(&mut context, &[controller()], &[]).await;
+    // Signed against the services before another change
+    let instruction = add_service_instruction(&authority, &event, service("#hub", "https://a"));
+    let stale = service_instructions(&mut context, &event, &[], instruction).await;
+    let instruction =
+        add_service_instruction(&authority, &event, service("#mediator", "https://b"));
+    submit_service(&mut context, &event, &[], instruction)
+        .await
+        .unwrap();
+    assert_sdm_error(
+        submit(&mut context, &stale).await,
+        SDMProgramError::SignerNotAuthority,
+    );
+}
+
+#[tokio::test]
+async fn test_service_threshold() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = keypairs(3);
+    let event = incept_ed25519(&mut context, &controllers, 2, &[]).await;
+    let added = service("#hub", "https://a");
+    let instruction = add_service_instruction(&authority, &event, added.clone());
+    assert_sdm_error(
+        submit_service(
+            &mut context,
+            &event,
+            &[&controllers[0]],
+            instruction.clone(),
+        )
+        .await,
+        SDMProgramError::ThresholdNotMet,
+    );
+    submit_service(
+        &mut context,
+        &event,
+        &[&controllers[0], &controllers[2]],
+        instruction,
+    )
+    .await
+    .unwrap();
+    assert_eq!(get_did(&mut context, &event).await.services(), &[added]);
+}
+
+#[tokio::test]
+async fn test_service_decommissioned_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = decommissioned(&mut context).await;
+    let instruction = add_service_instruction(&authority, &event, service("#hub", "https://a"));
+    assert_sdm_error(
+        submit_service(&mut context, &event, &[], instruction).await,
+        SDMProgramError::DidDecommissioned,
+    );
+}
+
+#[tokio::test]
+async fn test_inception_relationships_pass() {
+    let mut context = start().await;
+    let keys = vec![controller(), controller()];
+    let mut event = TestEvent::inception(&keys);
+    event.relationships = Some(vec![AUTHENTICATION | CAPABILITY_INVOCATION, KEY_AGREEMENT]);
+    incept_event(&mut context, &event).await;
+    let did = get_did(&mut context, &event).await;
+    assert_eq!(did.keys_with(AUTHENTICATION), vec![&keys[0]]);
+    assert_eq!(did.keys_with(KEY_AGREEMENT), vec![&keys[1]]);
+    assert!(did.keys_with(ASSERTION_METHOD).is_empty());
+}
+
+#[tokio::test]
+async fn test_inception_invalid_relationships_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller(), controller()];
+    for relationships in [
+        vec![AUTHENTICATION],
+        vec![AUTHENTICATION, 0],
+        vec![AUTHENTICATION, ALL_RELATIONSHIPS + 1],
+    ] {
+        let mut event = TestEvent::inception(&keys);
+        event.relationships = Some(relationships);
+        let instruction = inception_instruction(&authority, &event);
+        assert_sdm_error(
+            submit_signed(&mut context, &event, instruction).await,
+            SDMProgramError::InvalidRelationships,
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_rotation_relationships() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let next_keys = vec![controller(), controller()];
+    let last_keys = vec![controller()];
+    let mut inception = TestEvent::inception(&[controller(), controller()]).committing(&next_keys);
+    inception.relationships = Some(vec![AUTHENTICATION, KEY_AGREEMENT]);
+    incept_event(&mut context, &inception).await;
+    // Preserved by key position
+    let mut rotation = inception.rotation(&next_keys).committing(&last_keys);
+    rotation.relationships = None;
+    let instruction = rotation_instruction(&authority, &rotation);
+    submit_signed(&mut context, &rotation, instruction)
+        .await
+        .unwrap();
+    let did = get_did(&mut context, &inception).await;
+    assert_eq!(did.keys_with(AUTHENTICATION), vec![&next_keys[0]]);
+    assert_eq!(did.keys_with(KEY_AGREEMENT), vec![&next_keys[1]]);
+    // Preserving requires a relationship for each key
+    let mut fewer = rotation.rotation(&last_keys);
+    fewer.relationships = None;
+    let instruction = rotation_instruction(&authority, &fewer);
+    assert_sdm_error(
+        submit_signed(&mut context, &fewer, instruction).await,
+        SDMProgramError::InvalidRelationships,
+    );
+    // Reassigned
+    fewer.relationships = Some(vec![AUTHENTICATION | KEY_AGREEMENT]);
+    let instruction = rotation_instruction(&authority, &fewer);
+    submit_signed(&mut context, &fewer, instruction)
+        .await
+        .unwrap();
+    let did = get_did(&mut context, &inception).await;
+    assert_eq!(
+        did.keys_with(AUTHENTICATION | KEY_AGREEMENT),
+        vec![&last_keys[0]]
+    );
+}
+
+/// Build a delegated inception instruction of the event's delegator
+fn delegated_inception_instruction(authority: &Pubkey, event: &TestEvent) -> Instruction {
+    let (_, bump) = event.pda();
+    let inception = DIDInception {
+        keytype: event.keytype,
+        prefix: event.prefix,
+        bump,
+        keys: event.keys.clone(),
+        relationships: event.relationships.clone().unwrap(),
+        threshold: event.threshold,
+        next_digest: event.next_digest(),
+    };
+    let mut did = SDMDid::from_inception(inception.clone(), authority).unwrap();
+    did.set_delegator(event.delegator);
+    instruction::delegated_inception(
+        &id(),
+        authority,
+        authority,
+        InitializeDidAccount {
+            rent: Rent::default().minimum_balance(did.size()),
+            storage: did.size() as u64,
+        },
+        DIDDelegatedInception {
+            delegator: event.delegator.unwrap(),
+            inception,
+        },
+    )
+}
+
+/// Build a delegated rotation instruction of the delegator prefix
+fn delegated_rotation_instruction(
+    authority: &Pubkey,
+    event: &TestEvent,
+    delegator: &[u8; 32],
+) -> Instruction {
+    instruction::delegated_rotation(&id(), authority, authority, delegator, did_rotation(event))
+}
+
+/// A keypair funded by the test authority
+async fn funded_keypair(context: &mut TestContext) -> Keypair {
+    let keypair = Keypair::new();
+    let instruction = system_instruction::transfer(
+        &context.authority.pubkey(),
+        &keypair.pubkey(),
+        1_000_000_000,
+    );
+    submit(context, &[instruction]).await.unwrap();
+    keypair
+}
+
+/// Incept a delegator DID under a separate authority
+async fn incept_delegator(context: &mut TestContext) -> (Keypair, TestEvent) {
+    let delegator_authority = funded_keypair(context).await;
+    let event = TestEvent::inception(&[controller()]);
+    let instructions = [
+        sign_instruction(&delegator_authority, &event),
+        inception_instruction(&delegator_authority.pubkey(), &event),
+    ];
+    submit_with(context, &instructions, &[&delegator_authority])
+        .await
+        .unwrap();
+    (delegator_authority, event)
+}
+
+/// Incept a DID delegated by the delegator approving it
+async fn incept_delegated(
+    context: &mut TestContext,
+    delegator_authority: &Keypair,
+    delegator: &TestEvent,
+    next_keys: &[Pubkey],
+) -> TestEvent {
+    let authority = context.authority.pubkey();
+    let event = TestEvent::delegated_inception(&[controller()], delegator).committing(next_keys);
+    let instructions = [
+        sign_event(&[&context.authority, delegator_authority], &event),
+        delegated_inception_instruction(&authority, &event),
+    ];
+    submit(context, &instructions).await.unwrap();
+    event
+}
+
+#[tokio::test]
+async fn test_delegated_inception_pass() {
+    let mut context = start().await;
+    let (delegator_authority, delegator) = incept_delegator(&mut context).await;
+    let event = incept_delegated(&mut context, &delegator_authority, &delegator, &[]).await;
+    let did = get_did(&mut context, &event).await;
+    assert_eq!(did.delegator(), Some(&delegator.prefix));
+    assert_exact_account(&mut context, &event).await;
+}
+
+#[tokio::test]
+async fn test_delegated_inception_not_approved_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let (_, delegator) = incept_delegator(&mut context).await;
+    let event = TestEvent::delegated_inception(&[controller()], &delegator);
+    let instruction = delegated_inception_instruction(&authority, &event);
+    assert_sdm_error(
+        submit_signed(&mut context, &event, instruction).await,
+        SDMProgramError::DelegatorNotApproved,
+    );
+}
+
+#[tokio::test]
+async fn test_delegated_inception_pasta_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let (delegator_authority, delegator) = incept_delegator(&mut context).await;
+    let mut event = TestEvent::delegated_inception(&[controller()], &delegator);
+    event.keytype = SMDKeyType::PASTA;
+    let instructions = [
+        sign_event(&[&context.authority, &delegator_authority], &event),
+        delegated_inception_instruction(&authority, &event),
+    ];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::PastaNotDelegable,
+    );
+}
+
+#[tokio::test]
+async fn test_delegated_inception_invalid_delegator_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let (delegator_authority, delegator) = incept_delegator(&mut context).await;
+    let other = incept(&mut context, &[controller()], &[]).await;
+    let event = TestEvent::delegated_inception(&[controller()], &delegator);
+    let mut instruction = delegated_inception_instruction(&authority, &event);
+    // Another DID in place of the delegator
+    instruction.accounts[5] = AccountMeta::new_readonly(other.pda().0, false);
+    let instructions = [
+        sign_event(&[&context.authority, &delegator_authority], &event),
+        instruction,
+    ];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::InvalidDelegator,
+    );
+}
+
+#[tokio::test]
+async fn test_delegated_inception_event_delegator_mismatch_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let (delegator_authority, delegator) = incept_delegator(&mut context).await;
+    let mut event = TestEvent::delegated_inception(&[controller()], &delegator);
+    let instruction = delegated_inception_instruction(&authority, &event);
+    // The signed event names another delegator
+    event.delegator = Some(Pubkey::new_unique().to_bytes());
+    let instructions = [
+        sign_event(&[&context.authority, &delegator_authority], &event),
+        instruction,
+    ];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::EventDelegatorMismatch,
+    );
+}
+
+#[tokio::test]
+async fn test_delegated_rotation_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let (delegator_authority, delegator) = incept_delegator(&mut context).await;
+    let next_keys = [controller()];
+    let event = incept_delegated(&mut context, &delegator_authority, &delegator, &next_keys).await;
+    let rotation = event.rotation(&next_keys);
+    // Rotation requires the delegator approval
+    let instruction = delegated_rotation_instruction(&authority, &rotation, &delegator.prefix);
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, instruction.clone()).await,
+        SDMProgramError::DelegatorNotApproved,
+    );
+    let instructions = [
+        sign_event(&[&context.authority, &delegator_authority], &rotation),
+        instruction,
+    ];
+    submit(&mut context, &instructions).await.unwrap();
+    let did = get_did(&mut context, &event).await;
+    assert_eq!(did.did_doc.keys, rotation.keys);
+    assert_eq!(did.delegator(), Some(&delegator.prefix));
+}
+
+#[tokio::test]
+async fn test_rotation_delegation_mismatch_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let (delegator_authority, delegator) = incept_delegator(&mut context).await;
+    // A delegated DID can not rotate without its delegator
+    let event = incept_delegated(&mut context, &delegator_authority, &delegator, &[]).await;
+    let mut rotation = event.rotation(&[controller()]);
+    rotation.ilk = "rot";
+    let instruction = rotation_instruction(&authority, &rotation);
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, instruction).await,
+        SDMProgramError::DidDelegated,
+    );
+    // A DID not delegated has no delegator to approve
+    let event = incept(&mut context, &[controller()], &[]).await;
+    let mut rotation = event.rotation(&[controller()]);
+    rotation.ilk = "drt";
+    let instruction = delegated_rotation_instruction(&authority, &rotation, &delegator.prefix);
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, instruction).await,
+        SDMProgramError::DidNotDelegated,
+    );
+}
+
+#[tokio::test]
+async fn test_delegated_inception_decommissioned_delegator_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let delegator = decommissioned(&mut context).await;
+    let event = TestEvent::delegated_inception(&[controller()], &delegator);
+    let instruction = delegated_inception_instruction(&authority, &event);
+    assert_sdm_error(
+        submit_signed(&mut context, &event, instruction).await,
+        SDMProgramError::DidDecommissioned,
+    );
+}
+
+/// Build a revoke instruction
+fn revoke_instruction(
+    authority: &Pubkey,
+    event: &TestEvent,
+    reason: SDMRevocationReason,
+) -> Instruction {
+    let revoke = DIDRevoke {
+        keytype: event.keytype,
+        prefix: event.prefix,
+        reason,
+    };
+    instruction::revoke(&id(), authority, authority, revoke)
+}
+
+#[tokio::test]
+async fn test_revoke_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = [controller()];
+    let event = incept(&mut context, &keys, &[controller()]).await;
+    let instruction = add_service_instruction(&authority, &event, service("#hub", "https://hub"));
+    submit_service(&mut context, &event, &[], instruction)
+        .await
+        .unwrap();
+    let size = assert_exact_account(&mut context, &event).await;
+    let instructions = [revoke_instruction(
+        &authority,
+        &event,
+        SDMRevocationReason::KeyCompromise,
+    )];
+    submit(&mut context, &instructions).await.unwrap();
+    assert!(assert_exact_account(&mut context, &event).await < size);
+    let did = get_did(&mut context, &event).await;
+    assert_eq!(did.revocation(), Some(SDMRevocationReason::KeyCompromise));
+    assert_eq!(did.did_doc.keys, keys);
+    assert_eq!(did.did_doc.sn, event.sn);
+    assert!(did.services().is_empty());
+    // Revoked DIDs can not be revoked again or rotated
+    let instructions = [revoke_instruction(
+        &authority,
+        &event,
+        SDMRevocationReason::Unspecified,
+    )];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::DidRevoked,
+    );
+    let rotation = event.rotation(&[controller()]);
+    let instruction = rotation_instruction(&authority, &rotation);
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, instruction).await,
+        SDMProgramError::DidRevoked,
+    );
+}
+
+#[tokio::test]
+async fn test_revoke_without_controllers_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = keypairs(2);
+    let event = incept_ed25519(&mut context, &controllers, 2, &[]).await;
+    let instructions = [revoke_instruction(
+        &authority,
+        &event,
+        SDMRevocationReason::Superseded,
+    )];
+    submit(&mut context, &instructions).await.unwrap();
+    assert_eq!(
+        get_did(&mut context, &event).await.revocation(),
+        Some(SDMRevocationReason::Superseded)
+    );
+}
+
+#[tokio::test]
+async fn test_revoke_invalid_authority_fail() {
+    let mut context = start().await;
+    let event = incept(&mut context, &[controller()], &[]).await;
+    let other = funded_keypair(&mut context).await;
+    let instructions = [revoke_instruction(
+        &other.pubkey(),
+        &event,
+        SDMRevocationReason::KeyCompromise,
+    )];
+    assert_sdm_error(
+        submit_with(&mut context, &instructions, &[&other]).await,
+        SDMProgramError::InvalidAuthority,
+    );
+}
+
+#[tokio::test]
+async fn test_revoke_decommissioned_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = decommissioned(&mut context).await;
+    let instructions = [revoke_instruction(
+        &authority,
+        &event,
+        SDMRevocationReason::KeyCompromise,
+    )];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::DidDecommissioned,
+    );
+}
+
+#[tokio::test]
+async fn test_close_revoked_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = incept(&mut context, &[controller()], &[]).await;
+    let instructions = [revoke_instruction(
+        &authority,
+        &event,
+        SDMRevocationReason::CessationOfOperation,
+    )];
+    submit(&mut context, &instructions).await.unwrap();
+    let pda = event.pda().0;
+    let instructions = [
+        sign_close(&event),
+        close_instruction(&authority, &event, &authority, false),
+    ];
+    submit(&mut context, &instructions).await.unwrap();
+    assert!(context
+        .banks_client
+        .get_account(pda)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+/// Get the event log of the DID
+async fn get_event_log(context: &mut TestContext, event: &TestEvent) -> SDMEventLog {
+    let account = context
+        .banks_client
+        .get_account(event.log())
+        .await
+        .unwrap()
+        .unwrap();
+    let log = SDMEventLog::unpack(&account.data).unwrap();
+    assert_eq!(account.owner, id());
+    assert_eq!(account.data.len(), log.try_to_vec().unwrap().len());
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(account.data.len())
+    );
+    log
+}
+
+/// Asserts the record is of the event, its keys and its seals
+fn assert_record(record: &SDMEventRecord, event_type: SDMEventType, event: &TestEvent) {
+    assert_eq!(record.event_type, event_type);
+    assert_eq!(record.sn, event.sn);
+    assert_eq!(record.digest, event.digest);
+    let keys = match event_type {
+        SDMEventType::Interaction => Vec::new(),
+        _ => event.keys.iter().map(key_digest).collect::<Vec<[u8; 32]>>(),
+    };
+    assert_eq!(record.key_digests, keys);
+    assert_eq!(record.seal_digests, event.seals);
+}
+
+#[tokio::test]
+async fn test_event_log_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller(), controller()];
+    let inception = incept(&mut context, &[controller()], &keys).await;
+    let log = get_event_log(&mut context, &inception).await;
+    assert_eq!(log.prefix, inception.prefix);
+    assert_eq!(log.records.len(), 1);
+    assert_record(&log.records[0], SDMEventType::Inception, &inception);
+    let rotation = inception.rotation(&keys);
+    let instruction = rotation_instruction(&authority, &rotation);
+    submit_signed(&mut context, &rotation, instruction)
+        .await
+        .unwrap();
+    let decommission = rotation.decommission();
+    let instruction = decommission_instruction(&authority, &decommission);
+    submit_signed(&mut context, &decommission, instruction)
+        .await
+        .unwrap();
+    let log = get_event_log(&mut context, &inception).await;
+    assert_eq!(log.records.len(), 3);
+    assert_record(&log.records[0], SDMEventType::Inception, &inception);
+    assert_record(&log.records[1], SDMEventType::Rotation, &rotation);
+    assert_record(&log.records[2], SDMEventType::Decommission, &decommission);
+    assert!(log.records.windows(2).all(|r| r[0].slot <= r[1].slot));
+}
+
+#[tokio::test]
+async fn test_event_log_prefunded_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = TestEvent::inception(&[controller()]);
+    // Lamports sent to the event log address before the inception
+    let prefund = [system_instruction::transfer(
+        &authority,
+        &event.log(),
+        Rent::default().minimum_balance(0),
+    )];
+    submit(&mut context, &prefund).await.unwrap();
+    incept_event(&mut context, &event).await;
+    let log = get_event_log(&mut context, &event).await;
+    assert_eq!(log.records.len(), 1);
+    assert_record(&log.records[0], SDMEventType::Inception, &event);
+}
+
+#[tokio::test]
+async fn test_event_log_delegated_pass() {
+    let mut context = start().await;
+    let (delegator_authority, delegator) = incept_delegator(&mut context).await;
+    let next_keys = [controller()];
+    let event = incept_delegated(&mut context, &delegator_authority, &delegator, &next_keys).await;
+    let rotation = event.rotation(&next_keys);
+    let instruction =
+        delegated_rotation_instruction(&context.authority.pubkey(), &rotation, &delegator.prefix);
+    let instructions = [
+        sign_event(&[&context.authority, &delegator_authority], &rotation),
+        instruction,
+    ];
+    submit(&mut context, &instructions).await.unwrap();
+    let log = get_event_log(&mut context, &event).await;
+    assert_eq!(log.records.len(), 2);
+    assert_record(&log.records[0], SDMEventType::DelegatedInception, &event);
+    assert_record(&log.records[1], SDMEventType::DelegatedRotation, &rotation);
+}
+
+#[tokio::test]
+async fn test_event_log_created_on_legacy_rotation_pass() {
+    let mut event = TestEvent::inception(&[controller()]);
+    event.sn = 3;
+    event.digest = Pubkey::new_unique().to_bytes();
+    let (authority, accounts) = legacy_accounts(&event, SDMDidState::Rotated);
+    let mut context = start_with(accounts).await;
+    let rotation = event.rotation(&[controller()]);
+    let instructions = [
+        sign_event(&[&authority], &rotation),
+        rotation_instruction(&authority.pubkey(), &rotation),
+    ];
+    submit_with(&mut context, &instructions, &[&authority])
+        .await
+        .unwrap();
+    let log = get_event_log(&mut context, &event).await;
+    assert_eq!(log.records.len(), 1);
+    assert_record(&log.records[0], SDMEventType::Rotation, &rotation);
+}
+
+#[tokio::test]
+async fn test_event_log_invalid_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller()];
+    let inception = incept(&mut context, &[controller()], &keys).await;
+    let rotation = inception.rotation(&keys);
+    // Not the event log address of the DID
+    let mut instruction = rotation_instruction(&authority, &rotation);
+    instruction.accounts[5].pubkey = Pubkey::new_unique();
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, instruction).await,
+        SDMProgramError::InvalidEventLog,
+    );
+    // The event log of another DID
+    let other = incept(&mut context, &[controller()], &[]).await;
+    let mut instruction = rotation_instruction(&authority, &rotation);
+    instruction.accounts[5].pubkey = other.log();
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, instruction).await,
+        SDMProgramError::InvalidEventLog,
+    );
+    // The DID account itself
+    let mut instruction = rotation_instruction(&authority, &rotation);
+    instruction.accounts[5].pubkey = inception.pda().0;
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, instruction).await,
+        SDMProgramError::InvalidEventLog,
+    );
+    // A read-only event log
+    let mut instruction = rotation_instruction(&authority, &rotation);
+    instruction.accounts[5].is_writable = false;
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, instruction).await,
+        SDMProgramError::AccountNotWritable,
+    );
+}
+
+/// Build an interaction instruction anchoring the seals of the event
+fn interaction_instruction(authority: &Pubkey, event: &TestEvent) -> Instruction {
+    let interaction = DIDInteraction {
+        keytype: event.keytype,
+        prefix: event.prefix,
+        prior_digest: event.prior_digest.unwrap(),
+        digest: event.digest,
+        sn: event.sn,
+        seals: event.seals.clone(),
+    };
+    instruction::interaction(&id(), authority, authority, interaction)
+}
+
+#[tokio::test]
+async fn test_interaction_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller()];
+    let next_keys = vec![controller()];
+    let inception = incept(&mut context, &keys, &next_keys).await;
+    let seals = [
+        Pubkey::new_unique().to_bytes(),
+        Pubkey::new_unique().to_bytes(),
+    ];
+    let interaction = inception.interaction(&seals);
+    let instruction = interaction_instruction(&authority, &interaction);
+    submit_signed(&mut context, &interaction, instruction)
+        .await
+        .unwrap();
+    let did = get_did(&mut context, &inception).await;
+    assert_eq!(did.did_doc.sn, 1);
+    assert_eq!(did.did_doc.digest, interaction.digest);
+    assert_eq!(did.did_doc.keys, keys);
+    assert_eq!(did.did_doc.next_digest, inception.next_digest());
+    let log = get_event_log(&mut context, &inception).await;
+    assert_eq!(log.records.len(), 2);
+    assert_record(&log.records[1], SDMEventType::Interaction, &interaction);
+    // Rotation follows the interaction
+    let rotation = interaction.rotation(&next_keys);
+    let instruction = rotation_instruction(&authority, &rotation);
+    submit_signed(&mut context, &rotation, instruction)
+        .await
+        .unwrap();
+    let did = get_did(&mut context, &inception).await;
+    assert_eq!(did.did_doc.sn, 2);
+    assert_eq!(did.did_doc.keys, next_keys);
+}
+
+#[tokio::test]
+async fn test_interaction_seals_mismatch_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let inception = incept(&mut context, &[controller()], &[]).await;
+    let signed = inception.interaction(&[Pubkey::new_unique().to_bytes()]);
+    let mut interaction = signed.clone();
+    interaction.seals = vec![Pubkey::new_unique().to_bytes()];
+    let instruction = interaction_instruction(&authority, &interaction);
+    assert_sdm_error(
+        submit_signed(&mut context, &signed, instruction).await,
+        SDMProgramError::EventSealsMismatch,
+    );
+}
+
+#[tokio::test]
+async fn test_interaction_invalid_seals_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let inception = incept(&mut context, &[controller()], &[]).await;
+    let interaction = inception.interaction(&[]);
+    let instruction = interaction_instruction(&authority, &interaction);
+    assert_sdm_error(
+        submit_signed(&mut context, &interaction, instruction).await,
+        SDMProgramError::InvalidSeals,
+    );
+    let seals = vec![[1u8; 32]; MAX_INTERACTION_SEALS + 1];
+    let interaction = inception.interaction(&seals);
+    let instruction = interaction_instruction(&authority, &interaction);
+    assert_sdm_error(
+        submit_signed(&mut context, &interaction, instruction).await,
+        SDMProgramError::InvalidSeals,
+    );
+}
+
+#[tokio::test]
+async fn test_interaction_stale_sequence_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let inception = incept(&mut context, &[controller()], &[]).await;
+    let interaction = inception.interaction(&[Pubkey::new_unique().to_bytes()]);
+    let instruction = interaction_instruction(&authority, &interaction);
+    submit_signed(&mut context, &interaction, instruction)
+        .await
+        .unwrap();
+    // Replaying an interaction from the inception
+    let replay = inception.interaction(&[Pubkey::new_unique().to_bytes()]);
+    let instruction = interaction_instruction(&authority, &replay);
+    assert_sdm_error(
+        submit_signed(&mut context, &replay, instruction).await,
+        SDMProgramError::EventOutOfSequence,
+    );
+}
+
+/// Asserts the program event describes the DID of the event
+fn assert_program_event(
+    program_event: &SDMProgramEvent,
+    kind: SDMEventKind,
+    event: &TestEvent,
+    state: SDMDidState,
+) {
+    assert_eq!(program_event.kind, kind);
+    assert_eq!(program_event.pda, event.pda().0);
+    assert_eq!(program_event.prefix, event.prefix);
+    assert_eq!(program_event.state, state);
+    assert_eq!(program_event.sn, event.sn);
+    assert_eq!(
+        program_event.key_digests,
+        event.keys.iter().map(key_digest).collect::<Vec<[u8; 32]>>()
+    );
+}
+
+/// The native test runtime prints data logged by programs rather than
+/// recording it in the transaction logs, so the events of processed
+/// DIDs are built from the accounts
+#[tokio::test]
+async fn test_program_event_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let keys = vec![controller()];
+    let inception = incept(&mut context, &[controller()], &keys).await;
+    let did = get_did(&mut context, &inception).await;
+    let event = SDMProgramEvent::new(SDMEventKind::Inception, &inception.pda().0, &did);
+    assert_program_event(
+        &event,
+        SDMEventKind::Inception,
+        &inception,
+        SDMDidState::Inception,
+    );
+    let rotation = inception.rotation(&keys);
+    let instruction = rotation_instruction(&authority, &rotation);
+    submit_signed(&mut context, &rotation, instruction)
+        .await
+        .unwrap();
+    let instruction = revoke_instruction(&authority, &rotation, SDMRevocationReason::Superseded);
+    submit(&mut context, &[instruction]).await.unwrap();
+    let did = get_did(&mut context, &inception).await;
+    let event = SDMProgramEvent::new(SDMEventKind::Revocation, &rotation.pda().0, &did);
+    assert_program_event(
+        &event,
+        SDMEventKind::Revocation,
+        &rotation,
+        SDMDidState::Revoked(SDMRevocationReason::Superseded),
+    );
+}
+
+/// Inception event of a DID and its program data log line
+fn logged_event() -> (SDMProgramEvent, String) {
+    let did = SDMDid::from_inception(
+        DIDInception {
+            keytype: SMDKeyType::PASTA,
+            prefix: [1u8; 32],
+            bump: 255,
+            keys: vec![Pubkey::new_unique().into()],
+            relationships: vec![ALL_RELATIONSHIPS],
+            threshold: 1,
+            next_digest: [2u8; 32],
+        },
+        &Pubkey::new_unique(),
+    )
+    .unwrap();
+    let event = SDMProgramEvent::new(SDMEventKind::Inception, &Pubkey::new_unique(), &did);
+    let data = format!(
+        "Program data: {} {}",
+        base64::encode(PROGRAM_EVENT_TAG),
+        base64::encode(event.try_to_vec().unwrap())
+    );
+    (event, data)
+}
+
+#[test]
+fn test_program_events_from_logs() {
+    let (event, data) = logged_event();
+    let other = Pubkey::new_unique();
+    let logs = [
+        format!("Program {} invoke [1]", id()),
+        format!("Program {} invoke [2]", system_program::id()),
+        // Data logged by another program is skipped
+        format!("Program {} invoke [3]", other),
+        data.clone(),
+        format!("Program {} success", other),
+        format!("Program {} success", system_program::id()),
+        "Program log: invoke [1]".to_string(),
+        data.clone(),
+        // Data not tagged as a program event is skipped
+        format!("Program data: {}", base64::encode(b"other")),
+        format!("Program {} consumed 5000 of 200000 compute units", id()),
+        format!("Program {} success", id()),
+        data,
+    ];
+    assert_eq!(
+        SDMProgramEvent::from_logs(&id(), &logs),
+        vec![event.clone()]
+    );
+    let fields = [PROGRAM_EVENT_TAG.to_vec(), event.try_to_vec().unwrap()];
+    assert_eq!(SDMProgramEvent::decode(&fields).unwrap(), event);
+    assert_matches!(
+        SDMProgramEvent::decode(&[PROGRAM_EVENT_TAG.to_vec(), vec![0u8; 3]]),
+        Err(SDMProgramError::InvalidProgramEvent)
+    );
+}
+
+#[test]
+fn test_program_events_from_logs_status_like_lines() {
+    let (event, data) = logged_event();
+    let other = Pubkey::new_unique();
+    let logs = [
+        format!("Program {} invoke [1]", id()),
+        // Logs reading as a status do not end the invocation
+        "Program log: success".to_string(),
+        "Program log: failed: nothing".to_string(),
+        format!("Program return: {} AQ==", id()),
+        data.clone(),
+        // Statuses of programs not invoked last are ignored
+        format!("Program {} success", other),
+        data.clone(),
+        format!("Program {} success", id()),
+        data,
+    ];
+    assert_eq!(
+        SDMProgramEvent::from_logs(&id(), &logs),
+        vec![event.clone(), event]
+    );
+}
+
+#[tokio::test]
+async fn test_malformed_instruction_data_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let inception = incept(&mut context, &[controller()], &[]).await;
+    let rotation = inception.rotation::<SDMKey>(&[]);
+    let valid = rotation_instruction(&authority, &rotation);
+    // Unknown instruction, trailing bytes, then truncations of a valid
+    // instruction
+    let mut malformed = vec![vec![u8::MAX], vec![], [&valid.data[..], &[0]].concat()];
+    malformed.extend(
+        (1..valid.data.len())
+            .step_by(16)
+            .map(|len| valid.data[..len].to_vec()),
+    );
+    for data in malformed {
+        let mut instruction = valid.clone();
+        instruction.data = data;
+        assert_sdm_error(
+            submit_signed(&mut context, &rotation, instruction).await,
+            SDMProgramError::InvalidInstructionData,
+        );
+    }
+}
+
+fn secp256k1_keys(count: usize) -> Vec<libsecp256k1::SecretKey> {
+    (0..count)
+        .map(|_| libsecp256k1::SecretKey::parse(&Pubkey::new_unique().to_bytes()).unwrap())
+        .collect()
+}
+
+fn secp256k1_pubkeys(keys: &[libsecp256k1::SecretKey]) -> Vec<SDMKey> {
+    keys.iter()
+        .map(|k| {
+            let public = libsecp256k1::PublicKey::from_secret_key(k);
+            Secp256k1Pubkey(public.serialize_compressed()).into()
+        })
+        .collect()
+}
+
+/// Secp256k1 signature instruction at index over a message by each
+/// signer, the signatures share the message
+fn secp256k1_instruction(
+    signers: &[&libsecp256k1::SecretKey],
+    message: &[u8],
+    index: u8,
+) -> Instruction {
+    const OFFSETS_SIZE: usize = 11;
+    const SIGNED_SIZE: usize = 20 + 64 + 1;
+    let digest = libsecp256k1::Message::parse(&keccak::hash(message).to_bytes());
+    let data_start = 1 + signers.len() * OFFSETS_SIZE;
+    let message_offset = (data_start + signers.len() * SIGNED_SIZE) as u16;
+    let mut offsets = vec![signers.len() as u8];
+    let mut signed = Vec::new();
+    for (position, signer) in signers.iter().enumerate() {
+        let address_offset = (data_start + position * SIGNED_SIZE) as u16;
+        let signature_offset = address_offset + 20;
+        offsets.extend_from_slice(&signature_offset.to_le_bytes());
+        offsets.push(index);
+        offsets.extend_from_slice(&address_offset.to_le_bytes());
+        offsets.push(index);
+        offsets.extend_from_slice(&message_offset.to_le_bytes());
+        offsets.extend_from_slice(&(message.len() as u16).to_le_bytes());
+        offsets.push(index);
+        let public = libsecp256k1::PublicKey::from_secret_key(signer).serialize();
+        signed.extend_from_slice(&keccak::hash(&public[1..]).to_bytes()[12..]);
+        let (signature, recovery_id) = libsecp256k1::sign(&digest, signer);
+        signed.extend_from_slice(&signature.serialize());
+        signed.push(recovery_id.serialize());
+    }
+    offsets.extend(signed);
+    offsets.extend_from_slice(message);
+    Instruction {
+        program_id: secp256k1_program::id(),
+        accounts: vec![],
+        data: offsets,
+    }
+}
+
+/// Incept a secp256k1 DID controlled by keys with a threshold
+async fn incept_secp256k1(
+    context: &mut TestContext,
+    controllers: &[libsecp256k1::SecretKey],
+    threshold: u64,
+    next_keys: &[SDMKey],
+) -> TestEvent {
+    let mut event = TestEvent::inception(&secp256k1_pubkeys(controllers)).committing(next_keys);
+    event.keytype = SMDKeyType::Secp256k1;
+    event.threshold = threshold;
+    let authority = context.authority.pubkey();
+    let instruction = inception_instruction(&authority, &event);
+    submit_signed(context, &event, instruction).await.unwrap();
+    event
+}
+
+/// Submit an instruction preceded by the controllers' secp256k1
+/// signatures of the event, at the start of the transaction, and the
+/// authority's signature
+async fn submit_secp256k1(
+    context: &mut TestContext,
+    event: &TestEvent,
+    controllers: &[&libsecp256k1::SecretKey],
+    instruction: Instruction,
+) -> Result<(), BanksClientError> {
+    let instructions = [
+        secp256k1_instruction(controllers, &event.message(), 0),
+        sign_instruction(&context.authority, event),
+        instruction,
+    ];
+    submit(context, &instructions).await
+}
+
+#[tokio::test]
+async fn test_rotation_secp256k1_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = secp256k1_keys(3);
+    let next_controllers = secp256k1_keys(2);
+    let next_keys = secp256k1_pubkeys(&next_controllers);
+    let inception = incept_secp256k1(&mut context, &controllers, 2, &next_keys).await;
+    let did = get_did(&mut context, &inception).await;
+    assert_eq!(did.keytype(), SMDKeyType::Secp256k1);
+    assert_eq!(did.did_doc.keys, secp256k1_pubkeys(&controllers));
+    assert_exact_account(&mut context, &inception).await;
+    // Two of three current keys sign
+    let rotation = inception.rotation(&next_keys);
+    let instruction = rotation_instruction(&authority, &rotation);
+    submit_secp256k1(
+        &mut context,
+        &rotation,
+        &[&controllers[0], &controllers[2]],
+        instruction,
+    )
+    .await
+    .unwrap();
+    let did = get_did(&mut context, &inception).await;
+    assert_eq!(did.did_doc.keys, next_keys);
+    // A single signature of the precompile's own construction
+    let decommission = rotation.decommission();
+    let instructions = [
+        new_secp256k1_instruction(&next_controllers[1], &decommission.message()),
+        sign_instruction(&context.authority, &decommission),
+        decommission_instruction(&authority, &decommission),
+    ];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::ThresholdNotMet,
+    );
+    submit_secp256k1(
+        &mut context,
+        &decommission,
+        &[&next_controllers[0], &next_controllers[1]],
+        decommission_instruction(&authority, &decommission),
+    )
+    .await
+    .unwrap();
+    let did = get_did(&mut context, &inception).await;
+    assert!(did.did_doc.keys.is_empty());
+    assert_matches!(did.verify_active(), Err(SDMProgramError::DidDecommissioned));
+}
+
+#[tokio::test]
+async fn test_rotation_secp256k1_threshold_not_met_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = secp256k1_keys(2);
+    let next_keys = secp256k1_pubkeys(&secp256k1_keys(1));
+    let inception = incept_secp256k1(&mut context, &controllers, 2, &next_keys).await;
+    let rotation = inception.rotation(&next_keys);
+    let instruction = || rotation_instruction(&authority, &rotation);
+    // The same key signing twice counts once
+    assert_sdm_error(
+        submit_secp256k1(
+            &mut context,
+            &rotation,
+            &[&controllers[1], &controllers[1]],
+            instruction(),
+        )
+        .await,
+        SDMProgramError::ThresholdNotMet,
+    );
+    // Keys that are not current keys do not count
+    let others = secp256k1_keys(2);
+    assert_sdm_error(
+        submit_secp256k1(
+            &mut context,
+            &rotation,
+            &[&others[0], &others[1]],
+            instruction(),
+        )
+        .await,
+        SDMProgramError::ThresholdNotMet,
+    );
+}
+
+#[tokio::test]
+async fn test_inception_key_type_mismatch_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    // Secp256k1 DIDs with 32 byte keys and Ed25519 DIDs with secp256k1 keys
+    let mut secp256k1 = TestEvent::inception(&[controller()]);
+    secp256k1.keytype = SMDKeyType::Secp256k1;
+    let mut ed25519 = TestEvent::inception(&secp256k1_pubkeys(&secp256k1_keys(1)));
+    ed25519.keytype = SMDKeyType::Ed25519;
+    for event in [secp256k1, ed25519] {
+        let instruction = inception_instruction(&authority, &event);
+        assert_sdm_error(
+            submit_signed(&mut context, &event, instruction).await,
+            SDMProgramError::KeyTypeMismatch,
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_secp256k1_instruction_index_mismatch_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = secp256k1_keys(1);
+    let inception = incept_secp256k1(&mut context, &controllers, 1, &[]).await;
+    let rotation = inception.rotation::<SDMKey>(&[]);
+    // The signature data is that of the preceding instruction
+    let instructions = [
+        secp256k1_instruction(&[&controllers[0]], &rotation.message(), 0),
+        secp256k1_instruction(&[&controllers[0]], &rotation.message(), 0),
+        sign_instruction(&context.authority, &rotation),
+        rotation_instruction(&authority, &rotation),
+    ];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::InvalidSignatureInstruction,
+    );
+}
+
+/// Pasta secret scalars
+fn pasta_secrets(count: usize) -> Vec<[u8; 32]> {
+    (0..count)
+        .map(|_| {
+            let mut secret = Pubkey::new_unique().to_bytes();
+            secret[31] &= 0x3f;
+            secret
+        })
+        .collect()
+}
+
+fn pasta_pubkeys(secrets: &[[u8; 32]]) -> Vec<Pubkey> {
+    secrets
+        .iter()
+        .map(|s| {
+            let key = Point::generator() * Scalar::from_repr(*s).unwrap();
+            Pubkey::new_from_array(key.to_bytes())
+        })
+        .collect()
+}
+
+/// Signatures of the event by each secret at its key index
+fn pasta_signatures(event: &TestEvent, signers: &[(u8, &[u8; 32])]) -> Vec<PastaSignature> {
+    pasta_message_signatures(&event.message(), signers)
+}
+
+/// Signatures of the message by each secret at its key index
+fn pasta_message_signatures(message: &[u8], signers: &[(u8, &[u8; 32])]) -> Vec<PastaSignature> {
+    signers
+        .iter()
+        .map(|(index, secret)| pasta::sign(secret, *index, message).unwrap())
+        .collect()
+}
+
+/// Incept a Pasta DID controlled by the secrets with a threshold
+async fn incept_pasta(
+    context: &mut TestContext,
+    controllers: &[[u8; 32]],
+    threshold: u64,
+    next_keys: &[Pubkey],
+) -> TestEvent {
+    let mut event = TestEvent::inception(&pasta_pubkeys(controllers)).committing(next_keys);
+    event.keytype = SMDKeyType::PASTA;
+    event.threshold = threshold;
+    incept_event(context, &event).await;
+    event
+}
+
+/// The Pasta session PDA of the DID and payer
+fn pasta_session(event: &TestEvent, payer: &Pubkey) -> Pubkey {
+    SDMPastaSession::address(&event.prefix, payer, &id()).0
+}
+
+/// Build a Pasta session begin instruction for the signatures of event
+fn pasta_begin_instruction(
+    payer: &Pubkey,
+    event: &TestEvent,
+    signatures: Vec<PastaSignature>,
+) -> Instruction {
+    pasta_message_begin_instruction(payer, event, event.message(), signatures)
+}
+
+/// Build a Pasta session begin instruction for the signatures of a
+/// message of the event's DID
+fn pasta_message_begin_instruction(
+    payer: &Pubkey,
+    event: &TestEvent,
+    message: Vec<u8>,
+    signatures: Vec<PastaSignature>,
+) -> Instruction {
+    let signatures = DIDPastaSignatures {
+        keytype: event.keytype,
+        prefix: event.prefix,
+        message,
+        signatures,
+    };
+    instruction::pasta_begin(&id(), payer, signatures)
+}
+
+/// Build a Pasta session verify instruction
+fn pasta_verify_instruction(payer: &Pubkey, event: &TestEvent, steps: u16) -> Instruction {
+    instruction::pasta_verify(&id(), payer, &event.prefix, steps)
+}
+
+/// Build a Pasta rotation instruction committing to the event's next keys
+fn pasta_rotation_instruction(
+    payer: &Pubkey,
+    authority: &Pubkey,
+    event: &TestEvent,
+) -> Instruction {
+    instruction::pasta_rotation(&id(), payer, authority, did_rotation(event))
+}
+
+/// Submit instructions paid and signed by the payer alone
+async fn submit_paid(
+    context: &mut TestContext,
+    payer: &Keypair,
+    instructions: &[Instruction],
+) -> Result<(), BanksClientError> {
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &[payer],
+        context.recent_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+/// Begin a Pasta session of the payer and verify it to completion
+async fn verified_pasta_session(
+    context: &mut TestContext,
+    payer: &Keypair,
+    event: &TestEvent,
+    signatures: Vec<PastaSignature>,
+) {
+    let message = event.message();
+    verified_pasta_message_session(context, payer, event, message, signatures).await;
+}
+
+/// Begin a Pasta session of the payer over a message of the event's DID
+/// and verify it to completion
+async fn verified_pasta_message_session(
+    context: &mut TestContext,
+    payer: &Keypair,
+    event: &TestEvent,
+    message: Vec<u8>,
+    signatures: Vec<PastaSignature>,
+) {
+    let instructions = [
+        pasta_message_begin_instruction(&payer.pubkey(), event, message, signatures),
+        pasta_verify_instruction(&payer.pubkey(), event, u16::MAX),
+    ];
+    submit_paid(context, payer, &instructions).await.unwrap();
+}
+
+/// The test authority, paying for its own Pasta sessions
+fn authority_payer(context: &TestContext) -> Keypair {
+    Keypair::from_bytes(&context.authority.to_bytes()).unwrap()
+}
+
+#[tokio::test]
+async fn test_rotation_pasta_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = pasta_secrets(3);
+    let next_keys = pasta_pubkeys(&pasta_secrets(2));
+    let inception = incept_pasta(&mut context, &controllers, 2, &next_keys).await;
+    let rotation = inception.rotation(&next_keys);
+    // The controllers authorize the rotation, paid by other than the
+    // authority
+    let payer = funded_keypair(&mut context).await;
+    let signatures = pasta_signatures(&rotation, &[(0, &controllers[0]), (2, &controllers[2])]);
+    let begin = pasta_begin_instruction(&payer.pubkey(), &rotation, signatures);
+    submit_paid(&mut context, &payer, &[begin]).await.unwrap();
+    let session = pasta_session(&rotation, &payer.pubkey());
+    // Verification resumes across transactions
+    for steps in [200, 150, 160] {
+        let verify = pasta_verify_instruction(&payer.pubkey(), &rotation, steps);
+        submit_paid(&mut context, &payer, &[verify]).await.unwrap();
+    }
+    let account = context
+        .banks_client
+        .get_account(session)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(SDMPastaSession::unpack(&account.data)
+        .unwrap()
+        .is_complete());
+    let instruction = pasta_rotation_instruction(&payer.pubkey(), &authority, &rotation);
+    submit_with(&mut context, &[instruction], &[&payer])
+        .await
+        .unwrap();
+    let did = get_did(&mut context, &rotation).await;
+    assert_eq!(did.did_doc.keys, rotation.keys);
+    assert_eq!(did.did_doc.sn, rotation.sn);
+    assert_eq!(did.did_doc.digest, rotation.digest);
+    let log = get_event_log(&mut context, &rotation).await;
+    assert_record(
+        log.records.last().unwrap(),
+        SDMEventType::Rotation,
+        &rotation,
+    );
+    // The session is closed
+    assert!(context
+        .banks_client
+        .get_account(session)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_rotation_pasta_incomplete_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = pasta_secrets(1);
+    let inception = incept_pasta(&mut context, &controllers, 1, &[]).await;
+    let rotation = inception.rotation::<SDMKey>(&[]);
+    let payer = funded_keypair(&mut context).await;
+    let instructions = [
+        pasta_begin_instruction(
+            &payer.pubkey(),
+            &rotation,
+            pasta_signatures(&rotation, &[(0, &controllers[0])]),
+        ),
+        pasta_verify_instruction(&payer.pubkey(), &rotation, 100),
+    ];
+    submit_paid(&mut context, &payer, &instructions)
+        .await
+        .unwrap();
+    let instruction = pasta_rotation_instruction(&payer.pubkey(), &authority, &rotation);
+    assert_sdm_error(
+        submit_with(&mut context, &[instruction], &[&payer]).await,
+        SDMProgramError::PastaVerificationIncomplete,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_pasta_threshold_not_met_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = pasta_secrets(2);
+    let inception = incept_pasta(&mut context, &controllers, 2, &[]).await;
+    let rotation = inception.rotation::<SDMKey>(&[]);
+    let payer = funded_keypair(&mut context).await;
+    let signatures = pasta_signatures(&rotation, &[(1, &controllers[1])]);
+    verified_pasta_session(&mut context, &payer, &rotation, signatures).await;
+    let instruction = pasta_rotation_instruction(&payer.pubkey(), &authority, &rotation);
+    assert_sdm_error(
+        submit_with(&mut context, &[instruction], &[&payer]).await,
+        SDMProgramError::ThresholdNotMet,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_pasta_event_mismatch_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = pasta_secrets(1);
+    let inception = incept_pasta(&mut context, &controllers, 1, &[]).await;
+    let rotation = inception.rotation::<SDMKey>(&[]);
+    let payer = funded_keypair(&mut context).await;
+    let signatures = pasta_signatures(&rotation, &[(0, &controllers[0])]);
+    verified_pasta_session(&mut context, &payer, &rotation, signatures).await;
+    // The session verified the signatures of another rotation
+    let other = inception.rotation::<SDMKey>(&[]);
+    let instruction = pasta_rotation_instruction(&payer.pubkey(), &authority, &other);
+    assert_sdm_error(
+        submit_with(&mut context, &[instruction], &[&payer]).await,
+        SDMProgramError::EventSequenceMismatch,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_pasta_authority_fail() {
+    let mut context = start().await;
+    let controllers = pasta_secrets(1);
+    let inception = incept_pasta(&mut context, &controllers, 1, &[]).await;
+    let rotation = inception.rotation::<SDMKey>(&[]);
+    let payer = funded_keypair(&mut context).await;
+    let signatures = pasta_signatures(&rotation, &[(0, &controllers[0])]);
+    verified_pasta_session(&mut context, &payer, &rotation, signatures).await;
+    // The payer of the session is not the authority of the DID
+    let instruction = pasta_rotation_instruction(&payer.pubkey(), &payer.pubkey(), &rotation);
+    assert_sdm_error(
+        submit_paid(&mut context, &payer, &[instruction]).await,
+        SDMProgramError::InvalidAuthority,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_pasta_commitment_mismatch_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = pasta_secrets(1);
+    let inception = incept_pasta(&mut context, &controllers, 1, &[]).await;
+    let rotation = inception
+        .rotation::<SDMKey>(&[])
+        .committing(&[controller()]);
+    let payer = funded_keypair(&mut context).await;
+    let signatures = pasta_signatures(&rotation, &[(0, &controllers[0])]);
+    verified_pasta_session(&mut context, &payer, &rotation, signatures).await;
+    // The rotation data commits to next keys the controllers did not sign
+    let unsigned = rotation.clone().committing(&[controller()]);
+    let instruction = pasta_rotation_instruction(&payer.pubkey(), &authority, &unsigned);
+    assert_sdm_error(
+        submit_with(&mut context, &[instruction], &[&payer]).await,
+        SDMProgramError::EventCommitmentMismatch,
+    );
+}
+
+#[tokio::test]
+async fn test_pasta_invalid_signature_fail() {
+    let mut context = start().await;
+    let controllers = pasta_secrets(2);
+    let inception = incept_pasta(&mut context, &controllers, 1, &[]).await;
+    let rotation = inception.rotation::<SDMKey>(&[]);
+    let payer = funded_keypair(&mut context).await;
+    let begin = |signatures| pasta_begin_instruction(&payer.pubkey(), &rotation, signatures);
+    // Signatures by a key other than that at the index, repeating a key
+    // or with no signatures fail to begin
+    for signatures in [
+        pasta_signatures(&rotation, &[(0, &controllers[1])]),
+        pasta_signatures(&rotation, &[(0, &controllers[0]), (0, &controllers[0])]),
+        pasta_signatures(&rotation, &[(2, &controllers[0])]),
+        Vec::new(),
+    ] {
+        assert_sdm_error(
+            submit_paid(&mut context, &payer, &[begin(signatures)]).await,
+            SDMProgramError::InvalidPastaSignature,
+        );
+    }
+    // A signature of another message fails once verified
+    let mut signatures = pasta_signatures(&rotation, &[(0, &controllers[0])]);
+    signatures[0].s = pasta::sign(&controllers[0], 0, b"other").unwrap().s;
+    submit_paid(&mut context, &payer, &[begin(signatures)])
+        .await
+        .unwrap();
+    let verify = pasta_verify_instruction(&payer.pubkey(), &rotation, u16::MAX);
+    assert_sdm_error(
+        submit_paid(&mut context, &payer, &[verify]).await,
+        SDMProgramError::InvalidPastaSignature,
+    );
+}
+
+#[tokio::test]
+async fn test_pasta_session_prefunded_pass() {
+    let mut context = start().await;
+    let controllers = pasta_secrets(1);
+    let inception = incept_pasta(&mut context, &controllers, 1, &[]).await;
+    let rotation = inception.rotation::<SDMKey>(&[]);
+    let payer = funded_keypair(&mut context).await;
+    // Lamports sent to the session address before the session begins
+    let prefund = [system_instruction::transfer(
+        &context.authority.pubkey(),
+        &pasta_session(&rotation, &payer.pubkey()),
+        Rent::default().minimum_balance(0),
+    )];
+    submit(&mut context, &prefund).await.unwrap();
+    let signatures = pasta_signatures(&rotation, &[(0, &controllers[0])]);
+    verified_pasta_session(&mut context, &payer, &rotation, signatures).await;
+}
+
+#[tokio::test]
+async fn test_pasta_session_payer_mismatch_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = pasta_secrets(1);
+    let inception = incept_pasta(&mut context, &controllers, 1, &[]).await;
+    let rotation = inception.rotation::<SDMKey>(&[]);
+    let payer = funded_keypair(&mut context).await;
+    let begin = pasta_begin_instruction(
+        &payer.pubkey(),
+        &rotation,
+        pasta_signatures(&rotation, &[(0, &controllers[0])]),
+    );
+    submit_paid(&mut context, &payer, &[begin]).await.unwrap();
+    // Another payer can not advance or consume the payer's session
+    let other = funded_keypair(&mut context).await;
+    let mut verify = pasta_verify_instruction(&payer.pubkey(), &rotation, u16::MAX);
+    verify.accounts[0] = AccountMeta::new_readonly(other.pubkey(), true);
+    assert_sdm_error(
+        submit_paid(&mut context, &other, &[verify]).await,
+        SDMProgramError::InvalidPastaSession,
+    );
+    let mut instruction = pasta_rotation_instruction(&payer.pubkey(), &authority, &rotation);
+    instruction.accounts[0] = AccountMeta::new(other.pubkey(), true);
+    assert_sdm_error(
+        submit_with(&mut context, &[instruction], &[&other]).await,
+        SDMProgramError::InvalidPastaSession,
+    );
+}
+
+#[tokio::test]
+async fn test_pasta_begin_key_type_mismatch_fail() {
+    let mut context = start().await;
+    let controllers = keypairs(1);
+    let inception = incept_ed25519(&mut context, &controllers, 1, &[]).await;
+    let rotation = inception.rotation::<SDMKey>(&[]);
+    let payer = funded_keypair(&mut context).await;
+    let begin = pasta_begin_instruction(
+        &payer.pubkey(),
+        &rotation,
+        pasta_signatures(&rotation, &[(0, &pasta_secrets(1)[0])]),
+    );
+    assert_sdm_error(
+        submit_paid(&mut context, &payer, &[begin]).await,
+        SDMProgramError::KeyTypeMismatch,
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_pasta_controllers_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let controllers = pasta_secrets(1);
+    let inception = incept_pasta(&mut context, &controllers, 1, &[]).await;
+    let rotation = inception.rotation::<SDMKey>(&[]);
+    // The authority alone does not rotate a Pasta DID
+    let instruction = rotation_instruction(&authority, &rotation);
+    assert_sdm_error(
+        submit_signed(&mut context, &rotation, instruction).await,
+        SDMProgramError::ThresholdNotMet,
+    );
+}
+
+#[tokio::test]
+async fn test_decommission_pasta_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let payer = authority_payer(&context);
+    let controllers = pasta_secrets(1);
+    let inception = incept_pasta(&mut context, &controllers, 1, &[]).await;
+    let decommission = inception.decommission();
+    let instruction = decommission_instruction(&authority, &decommission);
+    // A session verifying another event does not authorize it
+    let rotation = inception.rotation::<SDMKey>(&[]);
+    let signatures = pasta_signatures(&rotation, &[(0, &controllers[0])]);
+    verified_pasta_session(&mut context, &payer, &rotation, signatures).await;
+    assert_sdm_error(
+        submit_signed(&mut context, &decommission, instruction.clone()).await,
+        SDMProgramError::InvalidPastaSession,
+    );
+    let signatures = pasta_signatures(&decommission, &[(0, &controllers[0])]);
+    verified_pasta_session(&mut context, &payer, &decommission, signatures).await;
+    resubmit_signed(&mut context, &decommission, instruction)
+        .await
+        .unwrap();
+    let did = get_did(&mut context, &inception).await;
+    assert!(did.did_doc.keys.is_empty());
+    // The session is closed
+    assert!(context
+        .banks_client
+        .get_account(pasta_session(&inception, &authority))
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_interaction_pasta_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let payer = authority_payer(&context);
+    let controllers = pasta_secrets(1);
+    let inception = incept_pasta(&mut context, &controllers, 1, &[]).await;
+    let interaction = inception.interaction(&[Pubkey::new_unique().to_bytes()]);
+    let instruction = interaction_instruction(&authority, &interaction);
+    // The authority alone does not interact for a Pasta DID
+    assert_sdm_error(
+        submit_signed(&mut context, &interaction, instruction.clone()).await,
+        SDMProgramError::InvalidPastaSession,
+    );
+    let signatures = pasta_signatures(&interaction, &[(0, &controllers[0])]);
+    verified_pasta_session(&mut context, &payer, &interaction, signatures).await;
+    resubmit_signed(&mut context, &interaction, instruction)
+        .await
+        .unwrap();
+    let did = get_did(&mut context, &inception).await;
+    assert_eq!(did.did_doc.digest, interaction.digest);
+}
+
+#[tokio::test]
+async fn test_service_pasta_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let payer = authority_payer(&context);
+    let controllers = pasta_secrets(1);
+    let event = incept_pasta(&mut context, &controllers, 1, &[]).await;
+    let added = service("#hub", "https://a");
+    let instruction = add_service_instruction(&authority, &event, added.clone());
+    let mut instructions = service_instructions(&mut context, &event, &[], instruction).await;
+    // The authority alone does not change the services of a Pasta DID
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::InvalidPastaSession,
+    );
+    let message = get_did(&mut context, &event)
+        .await
+        .service_message(&instructions[1].data)
+        .unwrap();
+    let signatures = pasta_message_signatures(&message, &[(0, &controllers[0])]);
+    verified_pasta_message_session(&mut context, &payer, &event, message.to_vec(), signatures)
+        .await;
+    // A new transaction, the failed one is not processed again
+    instructions.insert(0, ComputeBudgetInstruction::set_compute_unit_limit(400_000));
+    submit(&mut context, &instructions).await.unwrap();
+    assert_eq!(get_did(&mut context, &event).await.services(), &[added]);
+}
+
+#[tokio::test]
+async fn test_close_pasta_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let payer = authority_payer(&context);
+    let controllers = pasta_secrets(1);
+    let event = incept_pasta(&mut context, &controllers, 1, &[]).await;
+    let instruction = instruction::pasta_close(&id(), &authority, &event.prefix, &authority, true);
+    // The authority alone does not close a Pasta DID with current keys
+    assert_sdm_error(
+        submit(&mut context, std::slice::from_ref(&instruction)).await,
+        SDMProgramError::InvalidPastaSession,
+    );
+    // The current keys sign the latest event digest
+    let signatures = pasta_message_signatures(&event.digest, &[(0, &controllers[0])]);
+    verified_pasta_message_session(
+        &mut context,
+        &payer,
+        &event,
+        event.digest.to_vec(),
+        signatures,
+    )
+    .await;
+    let instructions = [
+        ComputeBudgetInstruction::set_compute_unit_limit(400_000),
+        instruction,
+    ];
+    submit(&mut context, &instructions).await.unwrap();
+    assert!(context
+        .banks_client
+        .get_account(event.pda().0)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+/// Build a proposal of the transfer of the DID to the new authority
+fn propose_authority_instruction(
+    authority: &Pubkey,
+    event: &TestEvent,
+    new_authority: &Pubkey,
+) -> Instruction {
+    let propose = DIDProposeAuthority {
+        keytype: event.keytype,
+        prefix: event.prefix,
+        authority: *new_authority,
+    };
+    instruction::propose_authority(&id(), authority, authority, propose)
+}
+
+fn authority_transfer(event: &TestEvent) -> DIDAuthorityTransfer {
+    DIDAuthorityTransfer {
+        keytype: event.keytype,
+        prefix: event.prefix,
+    }
+}
+
+#[tokio::test]
+async fn test_authority_transfer_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = incept(&mut context, &[controller()], &[]).await;
+    let size = assert_exact_account(&mut context, &event).await;
+    let new_authority = funded_keypair(&mut context).await;
+    let instructions = [propose_authority_instruction(
+        &authority,
+        &event,
+        &new_authority.pubkey(),
+    )];
+    submit(&mut context, &instructions).await.unwrap();
+    assert!(assert_exact_account(&mut context, &event).await > size);
+    let did = get_did(&mut context, &event).await;
+    assert_eq!(did.pending_authority(), Some(&new_authority.pubkey()));
+    // The authority is unchanged until the transfer is accepted
+    assert!(did.verify_authority(authority).is_ok());
+
+    let instructions = [instruction::accept_authority(
+        &id(),
+        &new_authority.pubkey(),
+        &new_authority.pubkey(),
+        authority_transfer(&event),
+    )];
+    submit_paid(&mut context, &new_authority, &instructions)
+        .await
+        .unwrap();
+    assert_eq!(assert_exact_account(&mut context, &event).await, size);
+    let did = get_did(&mut context, &event).await;
+    assert_eq!(did.pending_authority(), None);
+    assert!(did.verify_authority(new_authority.pubkey()).is_ok());
+
+    // The previous authority no longer controls the DID
+    let instructions = [revoke_instruction(
+        &authority,
+        &event,
+        SDMRevocationReason::Superseded,
+    )];
+    assert_sdm_error(
+        submit(&mut context, &instructions).await,
+        SDMProgramError::InvalidAuthority,
+    );
+    let instructions = [revoke_instruction(
+        &new_authority.pubkey(),
+        &event,
+        SDMRevocationReason::Superseded,
+    )];
+    submit_paid(&mut context, &new_authority, &instructions)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_propose_authority_signer_not_authority_fail() {
+    let mut context = start().await;
+    let event = incept(&mut context, &[controller()], &[]).await;
+    let other = funded_keypair(&mut context).await;
+    let instructions = [propose_authority_instruction(
+        &other.pubkey(),
+        &event,
+        &other.pubkey(),
+    )];
+    assert_sdm_error(
+        submit_paid(&mut context, &other, &instructions).await,
+        SDMProgramError::InvalidAuthority,
+    );
+}
+
+#[tokio::test]
+async fn test_accept_authority_not_pending_fail() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = incept(&mut context, &[controller()], &[]).await;
+    let other = funded_keypair(&mut context).await;
+    let accept = [instruction::accept_authority(
+        &id(),
+        &other.pubkey(),
+        &other.pubkey(),
+        authority_transfer(&event),
+    )];
+    assert_sdm_error(
+        submit_paid(&mut context, &other, &accept).await,
+        SDMProgramError::NoPendingAuthority,
+    );
+    // Only the proposed authority may accept
+    let instructions = [propose_authority_instruction(
+        &authority,
+        &event,
+        &Pubkey::new_unique(),
+    )];
+    submit(&mut context, &instructions).await.unwrap();
+    let accept = [
+        instruction::accept_authority(
+            &id(),
+            &other.pubkey(),
+            &other.pubkey(),
+            authority_transfer(&event),
+        ),
+        // Distinct from the transaction submitted before the proposal
+        system_instruction::transfer(&other.pubkey(), &authority, 1),
+    ];
+    assert_sdm_error(
+        submit_paid(&mut context, &other, &accept).await,
+        SDMProgramError::InvalidAuthority,
+    );
+}
+
+#[tokio::test]
+async fn test_cancel_authority_pass() {
+    let mut context = start().await;
+    let authority = context.authority.pubkey();
+    let event = incept(&mut context, &[controller()], &[]).await;
+    let size = assert_exact_account(&mut context, &event).await;
+    let new_authority = funded_keypair(&mut context).await;
+    let instructions = [propose_authority_instruction(
+        &authority,
+        &event,
+        &new_authority.pubkey(),
+    )];
+    submit(&mut context, &instructions).await.unwrap();
+    let instructions = [instruction::cancel_authority(
+        &id(),
+        &authority,
+        &authority,
+        authority_transfer(&event),
+    )];
+    submit(&mut context, &instructions).await.unwrap();
+    assert_eq!(assert_exact_account(&mut context, &event).await, size);
+    assert_eq!(
+        get_did(&mut context, &event).await.pending_authority(),
+        None
+    );
+    let instructions = [instruction::accept_authority(
+        &id(),
+        &new_authority.pubkey(),
+        &new_authority.pubkey(),
+        authority_transfer(&event),
+    )];
+    assert_sdm_error(
+        submit_paid(&mut context, &new_authority, &instructions).await,
+        SDMProgramError::NoPendingAuthority,
+    );
+}
+
+#[tokio::test]
+async fn test_sponsored_payer_pass() {
+    // A sponsor pays for the DID of an authority holding no lamports
+    let mut context = start().await;
+    let sponsor = funded_keypair(&mut context).await;
+    let authority = Keypair::new();
+    let keys = vec![controller()];
+    let event = TestEvent::inception(&[controller()]).committing(&keys);
+    let mut inception = inception_instruction(&authority.pubkey(), &event);
+    inception.accounts[0] = AccountMeta::new(sponsor.pubkey(), true);
+    let instructions = [sign_instruction(&authority, &event), inception];
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&sponsor.pubkey()),
+        &[&sponsor, &authority],
+        context.recent_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    assert_exact_account(&mut context, &event).await;
+    let did = get_did(&mut context, &event).await;
+    assert!(did.verify_authority(authority.pubkey()).is_ok());
+
+    let rotation = event.rotation(&keys);
+    let instructions = [
+        sign_event(&[&authority], &rotation),
+        instruction::rotation(
+            &id(),
+            &sponsor.pubkey(),
+            &authority.pubkey(),
+            did_rotation(&rotation),
+        ),
+    ];
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&sponsor.pubkey()),
+        &[&sponsor, &authority],
+        context.recent_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    assert_exact_account(&mut context, &event).await;
+    assert_eq!(
+        context
+            .banks_client
+            .get_balance(authority.pubkey())
+            .await
+            .unwrap(),
+        0
+    );
+}
@@ -0,0 +1,231 @@
+//! DID account state decoding of every persisted version and key changes
+
+use assert_matches::assert_matches;
+use borsh::BorshSerialize;
+use solana_did_method::{
+    error::SDMProgramError,
+    instruction::{DIDInception, DIDRotation, SDMKey, SMDKeyType},
+    keri::next_keys_digest,
+    state::{SDMDid, SDMDidState, ALL_RELATIONSHIPS, CURRENT_DATA_VERSION, UNKNOWN_DIGEST},
+    versions::SDMDidDocV1,
+};
+use solana_program::pubkey::Pubkey;
+
+/// Account data of a document persisted in version with spare storage
+fn account_data<T: BorshSerialize>(version: u16, doc: &T) -> Vec<u8> {
+    let mut data = (true, version, doc).try_to_vec().unwrap();
+    data.resize(data.len() + 256, 0);
+    data
+}
+
+fn v1(state: SDMDidState, keys: Vec<Pubkey>) -> SDMDidDocV1 {
+    SDMDidDocV1 {
+        state,
+        keytype: SMDKeyType::PASTA,
+        authority: Pubkey::new_unique(),
+        prefix: Pubkey::new_unique().to_bytes(),
+        bump: 255,
+        keys,
+    }
+}
+
+#[test]
+fn test_unpack_v1_inception_pass() {
+    let keys = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+    let doc = v1(SDMDidState::Inception, keys.clone());
+    let did = SDMDid::unpack(&account_data(1, &doc)).unwrap();
+    assert_eq!(did.version, CURRENT_DATA_VERSION);
+    assert_eq!(did.prefix(), &doc.prefix);
+    assert_eq!(did.bump(), doc.bump);
+    let keys = keys
+        .into_iter()
+        .map(SDMKey::Pubkey)
+        .collect::<Vec<SDMKey>>();
+    assert_eq!(did.did_doc.keys, keys);
+    assert_eq!(did.did_doc.sn, 0);
+    assert_eq!(did.did_doc.digest, doc.prefix);
+    assert_eq!(did.did_doc.threshold, 1);
+    assert_eq!(did.did_doc.next_digest, UNKNOWN_DIGEST);
+    assert_eq!(did.did_doc.relationships, vec![ALL_RELATIONSHIPS; 2]);
+    assert_eq!(did.keys_with(ALL_RELATIONSHIPS).len(), 2);
+    assert!(did.services().is_empty());
+    assert_eq!(did.delegator(), None);
+    assert_eq!(did.pending_authority(), None);
+    assert!(did.verify_authority(doc.authority).is_ok());
+    // Changes before version 2 recorded no slots or time
+    assert_eq!(did.created_slot(), 0);
+    assert_eq!(did.updated_slot(), 0);
+    assert_eq!(did.updated_unix_timestamp(), 0);
+}
+
+#[test]
+fn test_unpack_v1_rotated_pass() {
+    let doc = v1(SDMDidState::Rotated, vec![Pubkey::new_unique()]);
+    let did = SDMDid::unpack(&account_data(1, &doc)).unwrap();
+    assert_eq!(did.did_doc.digest, UNKNOWN_DIGEST);
+}
+
+#[test]
+fn test_unpack_v1_decommissioned_pass() {
+    let doc = v1(SDMDidState::Decommissioned, vec![]);
+    let did = SDMDid::unpack(&account_data(1, &doc)).unwrap();
+    assert_eq!(did.did_doc.digest, UNKNOWN_DIGEST);
+    assert_eq!(did.did_doc.threshold, 0);
+    assert_matches!(did.verify_active(), Err(SDMProgramError::DidDecommissioned));
+}
+
+#[test]
+fn test_pack_upgrades_to_current_pass() {
+    let doc = v1(SDMDidState::Inception, vec![Pubkey::new_unique()]);
+    let mut did = SDMDid::unpack(&account_data(1, &doc)).unwrap();
+    let mut data = vec![0u8; did.size()];
+    did.pack(&mut data).unwrap();
+    assert_eq!(u16::from_le_bytes([data[1], data[2]]), CURRENT_DATA_VERSION);
+    let current = SDMDid::unpack(&data).unwrap();
+    assert_eq!(current.did_doc, did.did_doc);
+}
+
+#[test]
+fn test_unpack_unknown_version_fail() {
+    let doc = v1(SDMDidState::Inception, vec![Pubkey::new_unique()]);
+    for version in [0, CURRENT_DATA_VERSION + 1] {
+        assert_matches!(
+            SDMDid::unpack(&account_data(version, &doc)),
+            Err(SDMProgramError::DidDataVersionInvalid)
+        );
+    }
+}
+
+#[test]
+fn test_unpack_malformed_fail() {
+    assert_matches!(SDMDid::unpack(&[]), Err(SDMProgramError::DidNotInitialized));
+    assert_matches!(
+        SDMDid::unpack(&[0]),
+        Err(SDMProgramError::DidNotInitialized)
+    );
+    for data in [&[1][..], &[1, 1], &[1, 1, 0], &[1, 1, 0, 0, 1]] {
+        assert_matches!(
+            SDMDid::unpack(data),
+            Err(SDMProgramError::DidDataVersionInvalid)
+        );
+    }
+    // Truncated documents of the current version
+    let doc = v1(SDMDidState::Inception, vec![Pubkey::new_unique()]);
+    let mut did = SDMDid::unpack(&account_data(1, &doc)).unwrap();
+    let mut data = vec![0u8; did.size()];
+    did.pack(&mut data).unwrap();
+    for len in 1..data.len() {
+        assert!(SDMDid::unpack(&data[..len]).is_err());
+    }
+}
+
+#[test]
+fn test_pack_undersized_fail() {
+    let doc = v1(SDMDidState::Inception, vec![Pubkey::new_unique()]);
+    let mut did = SDMDid::unpack(&account_data(1, &doc)).unwrap();
+    let mut data = vec![0u8; did.size() - 1];
+    assert_matches!(
+        did.pack(&mut data),
+        Err(SDMProgramError::InvalidAccountSize)
+    );
+}
+
+/// Incepted Ed25519 DID committed to the next keys with a threshold of one
+fn incepted(next_keys: &[SDMKey]) -> SDMDid {
+    let prefix = Pubkey::new_unique().to_bytes();
+    let inception = DIDInception {
+        keytype: SMDKeyType::Ed25519,
+        prefix,
+        bump: 255,
+        keys: vec![SDMKey::Pubkey(Pubkey::new_unique())],
+        relationships: vec![ALL_RELATIONSHIPS],
+        threshold: 1,
+        next_digest: next_keys_digest(SMDKeyType::Ed25519, 1, next_keys),
+    };
+    SDMDid::from_inception(inception, &Pubkey::new_unique()).unwrap()
+}
+
+fn rotation(did: &SDMDid, keys: Vec<SDMKey>) -> DIDRotation {
+    DIDRotation {
+        keytype: SMDKeyType::Ed25519,
+        prefix: *did.prefix(),
+        prior_digest: did.did_doc.digest,
+        digest: Pubkey::new_unique().to_bytes(),
+        sn: 1,
+        relationships: Some(vec![ALL_RELATIONSHIPS; keys.len()]),
+        keys,
+        threshold: 1,
+        next_digest: UNKNOWN_DIGEST,
+    }
+}
+
+#[test]
+fn test_rotate_committed_keys_pass() {
+    let committed = SDMKey::Pubkey(Pubkey::new_unique());
+    let mut did = incepted(&[committed]);
+    did.rotate_with(rotation(&did, vec![committed])).unwrap();
+    assert_eq!(did.did_doc.keys, vec![committed]);
+}
+
+#[test]
+fn test_rotate_duplicate_keys_fail() {
+    // A pair of equal keys cancels out of the commitment to the keys
+    let committed = SDMKey::Pubkey(Pubkey::new_unique());
+    let added = SDMKey::Pubkey(Pubkey::new_unique());
+    let mut did = incepted(&[committed]);
+    let keys = vec![committed, added, added];
+    assert_eq!(
+        next_keys_digest(SMDKeyType::Ed25519, 1, &keys),
+        did.did_doc.next_digest
+    );
+    assert_matches!(
+        did.rotate_with(rotation(&did, keys)),
+        Err(SDMProgramError::DuplicateKeys)
+    );
+    assert_eq!(did.did_doc.sn, 0);
+}
+
+#[test]
+fn test_verify_controller_pasta_key_fail() {
+    let key = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    for (keytype, key_signs) in [(SMDKeyType::Ed25519, true), (SMDKeyType::PASTA, false)] {
+        let inception = DIDInception {
+            keytype,
+            prefix: Pubkey::new_unique().to_bytes(),
+            bump: 255,
+            keys: vec![SDMKey::Pubkey(key)],
+            relationships: vec![ALL_RELATIONSHIPS],
+            threshold: 1,
+            next_digest: UNKNOWN_DIGEST,
+        };
+        let did = SDMDid::from_inception(inception, &authority).unwrap();
+        assert!(did.verify_controller(&authority).is_ok());
+        // Key bytes of other key types are not transaction signers
+        match key_signs {
+            true => assert!(did.verify_controller(&key).is_ok()),
+            false => assert_matches!(
+                did.verify_controller(&key),
+                Err(SDMProgramError::SignerNotController)
+            ),
+        }
+    }
+}
+
+#[test]
+fn test_inception_duplicate_keys_fail() {
+    let key = SDMKey::Pubkey(Pubkey::new_unique());
+    let inception = DIDInception {
+        keytype: SMDKeyType::Ed25519,
+        prefix: Pubkey::new_unique().to_bytes(),
+        bump: 255,
+        keys: vec![key, key],
+        relationships: vec![ALL_RELATIONSHIPS; 2],
+        threshold: 1,
+        next_digest: UNKNOWN_DIGEST,
+    };
+    assert_matches!(
+        SDMDid::from_inception(inception, &Pubkey::new_unique()),
+        Err(SDMProgramError::DuplicateKeys)
+    );
+}
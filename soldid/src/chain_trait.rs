@@ -3,35 +3,72 @@
 use hbkr_rs::{
     event::Event,
     event_message::EventMessage,
-    key_manage::{KeySet, Publickey},
+    key_manage::{Privatekey, Publickey},
     said_event::SaidEvent,
 };
 
-use crate::errors::SolDidResult;
+use solana_did_method::state::SDMRevocationReason;
+
+use crate::{
+    errors::SolDidResult,
+    wallet::{interaction::InteractionEvent, wallet_enums::WalletKeySet},
+};
 
 /// DIdSigner is a type able to sign transactions
 pub type DidSigner = Vec<u8>;
 pub type ChainSignature = String;
+/// Chain instructions are approved by the signer as the authority, DIDs
+/// with keys verifiable on chain also require the signatures of their
+/// controllers, the current keys of the DID ahead of the event
 pub trait Chain: std::fmt::Debug {
     /// Inception instruction put on the chain
+    /// commits to the key set's next public keys and assigns the
+    /// verification relationships of each current key. A delegated
+    /// inception is approved by the approvers, the current keys of the
+    /// delegator
     fn inception_inst(
         &self,
-        key_set: &dyn KeySet,
+        key_set: &dyn WalletKeySet,
+        relationships: &[u8],
+        approvers: &[Privatekey],
         event_msg: &EventMessage<SaidEvent<Event>>,
     ) -> SolDidResult<(ChainSignature, Publickey)>;
-    /// Rotation instruction put on the chain
+    /// Rotation instruction put on the chain, the key set's current
+    /// keys must match the previous commitment and its next public
+    /// keys are committed to. Relationships not reassigned are preserved
+    /// by key position. A delegated rotation is approved by the
+    /// approvers, the current keys of the delegator
     fn rotation_inst(
         &self,
         rotation_digest: &Vec<u8>,
-        key_set: &dyn KeySet,
+        key_set: &dyn WalletKeySet,
+        controllers: &[Privatekey],
+        relationships: Option<&[u8]>,
+        approvers: &[Privatekey],
         event_msg: &EventMessage<SaidEvent<Event>>,
     ) -> SolDidResult<ChainSignature>;
     /// Decommission instruction put on the chain
     fn decommission_inst(
         &self,
         decommission_digest: &Vec<u8>,
+        controllers: &[Privatekey],
         event_msg: &EventMessage<SaidEvent<Event>>,
     ) -> SolDidResult<ChainSignature>;
+    /// Revoke instruction put on the chain, signed by the authority
+    /// alone with the reason of the revocation
+    fn revoke_inst(
+        &self,
+        inception_digest: &Vec<u8>,
+        reason: SDMRevocationReason,
+    ) -> SolDidResult<ChainSignature>;
+    /// Interaction instruction put on the chain, anchors the seal
+    /// digests of the event without changing the keys
+    fn interaction_inst(
+        &self,
+        inception_digest: &Vec<u8>,
+        controllers: &[Privatekey],
+        event: &InteractionEvent,
+    ) -> SolDidResult<ChainSignature>;
 
     /// Get the signer bytes
     fn inst_signer(&self) -> DidSigner;
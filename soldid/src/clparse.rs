@@ -2,7 +2,9 @@
 
 use std::path::PathBuf;
 
-use clap::{crate_description, crate_name, crate_version, value_parser, Arg, ArgAction, Command};
+use clap::{
+    crate_description, crate_name, crate_version, value_parser, Arg, ArgAction, ArgGroup, Command,
+};
 use solana_clap_v3_utils::input_validators::is_pubkey;
 
 pub const DID_LIST: &str = "did-list";
@@ -10,7 +12,58 @@ pub const KEYS_LIST: &str = "keys-list";
 pub const DID_CREATE: &str = "did-create";
 pub const DID_ROTATE: &str = "did-rotate";
 pub const DID_DECOMMISION: &str = "did-decommission";
+pub const DID_REVOKE: &str = "did-revoke";
+pub const DID_ANCHOR: &str = "did-anchor";
 pub const DID_CLOSE: &str = "did-close";
+pub const DID_TRANSFER_AUTHORITY: &str = "did-transfer-authority";
+pub const DID_SERVICE_ADD: &str = "did-service-add";
+pub const DID_SERVICE_UPDATE: &str = "did-service-update";
+pub const DID_SERVICE_REMOVE: &str = "did-service-remove";
+
+/// Arguments identifying a service of a wallet's DID
+fn service_args(command: Command<'static>) -> Command<'static> {
+    command
+        .arg(
+            Arg::new("name")
+                .short('n')
+                .takes_value(true)
+                .required(true)
+                .value_parser(value_parser!(String))
+                .help("Name of keyset of the DID"),
+        )
+        .arg(
+            Arg::new("id")
+                .short('i')
+                .long("id")
+                .takes_value(true)
+                .required(true)
+                .value_parser(value_parser!(String))
+                .help("Service id, e.g. #mediator"),
+        )
+}
+
+/// Arguments describing a service endpoint
+fn endpoint_args(command: Command<'static>) -> Command<'static> {
+    service_args(command)
+        .arg(
+            Arg::new("type")
+                .short('t')
+                .long("type")
+                .takes_value(true)
+                .required(true)
+                .value_parser(value_parser!(String))
+                .help("Service type, e.g. DIDCommMessaging"),
+        )
+        .arg(
+            Arg::new("endpoint")
+                .short('e')
+                .long("endpoint")
+                .takes_value(true)
+                .required(true)
+                .value_parser(value_parser!(String))
+                .help("Service endpoint URI"),
+        )
+}
 
 #[allow(dead_code)]
 pub fn command_line() -> Command<'static> {
@@ -29,6 +82,30 @@ pub fn command_line() -> Command<'static> {
                 .default_value("~/.solwall")
                 .help("Use wallet configuration in path"),
         )
+        .arg(
+            Arg::new("fee-payer")
+                .long("fee-payer")
+                .global(true)
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true)
+                .help("Keypair file paying fees and rent, defaults to the configured keypair"),
+        )
+        .arg(
+            Arg::new("authority")
+                .long("authority")
+                .global(true)
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true)
+                .help("Keypair file of the DID authority, defaults to the configured keypair"),
+        )
+        .arg(
+            Arg::new("delegator-authority")
+                .long("delegator-authority")
+                .global(true)
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true)
+                .help("Keypair file of the delegator DID authority, defaults to the DID authority"),
+        )
         .subcommand(Command::new(DID_LIST).about("List a wallet's DIDs"))
         .subcommand(
             Command::new(KEYS_LIST)
@@ -84,6 +161,21 @@ pub fn command_line() -> Command<'static> {
                         .default_value("1")
                         .value_parser(value_parser!(i8))
                         .help("Set the signing threshold to modify the DID document"),
+                )
+                .arg(
+                    Arg::new("key-type")
+                        .long("key-type")
+                        .takes_value(true)
+                        .default_value("pasta")
+                        .value_parser(["pasta", "ed25519", "secp256k1"])
+                        .help("Key type of the keypairs generated for the DID"),
+                )
+                .arg(
+                    Arg::new("parent")
+                        .long("parent")
+                        .takes_value(true)
+                        .value_parser(value_parser!(String))
+                        .help("Name of the wallet keyset whose DID delegates the new DID"),
                 ),
         )
         .subcommand(
@@ -110,6 +202,69 @@ pub fn command_line() -> Command<'static> {
                         .help("Name of keyset to decommission"),
                 ),
         )
+        .subcommand(
+            Command::new(DID_REVOKE)
+                .about("Revoke a wallet's DID after compromise")
+                .arg(
+                    Arg::new("name")
+                        .short('n')
+                        .takes_value(true)
+                        .required(true)
+                        .value_parser(value_parser!(String))
+                        .help("Name of keyset to revoke"),
+                )
+                .arg(
+                    Arg::new("reason")
+                        .short('r')
+                        .long("reason")
+                        .takes_value(true)
+                        .default_value("unspecified")
+                        .value_parser([
+                            "unspecified",
+                            "key-compromise",
+                            "authority-compromise",
+                            "superseded",
+                            "cessation-of-operation",
+                        ])
+                        .help("Reason code of the revocation"),
+                ),
+        )
+        .subcommand(
+            Command::new(DID_ANCHOR)
+                .about("Anchor file or digest seals to a wallet's DID with an interaction")
+                .arg(
+                    Arg::new("name")
+                        .short('n')
+                        .takes_value(true)
+                        .required(true)
+                        .value_parser(value_parser!(String))
+                        .help("Name of keyset to anchor with"),
+                )
+                .arg(
+                    Arg::new("file")
+                        .short('f')
+                        .long("file")
+                        .takes_value(true)
+                        .multiple_occurrences(true)
+                        .value_parser(value_parser!(PathBuf))
+                        .help("File whose Blake3 digest is anchored"),
+                )
+                .arg(
+                    Arg::new("digest")
+                        .short('d')
+                        .long("digest")
+                        .takes_value(true)
+                        .multiple_occurrences(true)
+                        .value_parser(value_parser!(String))
+                        .help("Self-addressing digest (qb64) to anchor"),
+                )
+                .group(
+                    ArgGroup::new("seals")
+                        .args(&["file", "digest"])
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
         .subcommand(
             Command::new(DID_CLOSE)
                 .about("Close a DID account without removing keyset")
@@ -120,8 +275,70 @@ pub fn command_line() -> Command<'static> {
                         .takes_value(true)
                         .value_parser(is_pubkey)
                         .help("PDA pubkey string"),
+                )
+                .arg(
+                    Arg::new("recipient")
+                        .short('r')
+                        .long("recipient")
+                        .takes_value(true)
+                        .value_parser(is_pubkey)
+                        .help(
+                            "Pubkey string receiving the account lamports, defaults to the signer",
+                        ),
+                )
+                .arg(
+                    Arg::new("force")
+                        .short('f')
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .help("Close the DID account even if not decommissioned"),
+                ),
+        )
+        .subcommand(
+            Command::new(DID_TRANSFER_AUTHORITY)
+                .about("Propose, accept or cancel the transfer of a DID account's authority")
+                .arg(
+                    Arg::new("pda")
+                        .short('p')
+                        .required(true)
+                        .takes_value(true)
+                        .value_parser(is_pubkey)
+                        .help("PDA pubkey string"),
+                )
+                .arg(
+                    Arg::new("propose")
+                        .long("propose")
+                        .takes_value(true)
+                        .value_parser(is_pubkey)
+                        .help("Pubkey string of the proposed authority"),
+                )
+                .arg(
+                    Arg::new("accept")
+                        .long("accept")
+                        .action(ArgAction::SetTrue)
+                        .help("Accept the pending transfer, the signer becomes the authority"),
+                )
+                .arg(
+                    Arg::new("cancel")
+                        .long("cancel")
+                        .action(ArgAction::SetTrue)
+                        .help("Cancel the pending transfer"),
+                )
+                .group(
+                    ArgGroup::new("transfer")
+                        .args(&["propose", "accept", "cancel"])
+                        .required(true),
                 ),
         )
+        .subcommand(endpoint_args(
+            Command::new(DID_SERVICE_ADD).about("Publish a service endpoint of a wallet's DID"),
+        ))
+        .subcommand(endpoint_args(
+            Command::new(DID_SERVICE_UPDATE).about("Update a service endpoint of a wallet's DID"),
+        ))
+        .subcommand(service_args(
+            Command::new(DID_SERVICE_REMOVE).about("Remove a service endpoint of a wallet's DID"),
+        ))
 }
 
 #[cfg(test)]
@@ -147,6 +364,69 @@ mod cli_tests {
         assert_eq!(subcmd, "did-create");
         assert_eq!(*matches.get_one::<i8>("keys").unwrap(), 2);
         assert_eq!(*matches.get_one::<i8>("threshold").unwrap(), 1);
+        assert_eq!(matches.get_one::<String>("key-type").unwrap(), "pasta");
+    }
+
+    #[test]
+    fn test_command_ed25519_did_create_pass() {
+        let cmd = command_line();
+        let y = cmd.get_matches_from(vec![
+            "soldid",
+            "did-create",
+            "-n",
+            "Alice",
+            "--key-type",
+            "ed25519",
+        ]);
+        let (subcmd, matches) = y.subcommand().unwrap();
+        assert_eq!(subcmd, "did-create");
+        assert_eq!(matches.get_one::<String>("key-type").unwrap(), "ed25519");
+    }
+
+    #[test]
+    fn test_command_secp256k1_did_create_pass() {
+        let cmd = command_line();
+        let y = cmd.get_matches_from(vec![
+            "soldid",
+            "did-create",
+            "-n",
+            "Alice",
+            "--key-type",
+            "secp256k1",
+        ]);
+        let (subcmd, matches) = y.subcommand().unwrap();
+        assert_eq!(subcmd, "did-create");
+        assert_eq!(matches.get_one::<String>("key-type").unwrap(), "secp256k1");
+    }
+
+    #[test]
+    fn test_command_unknown_key_type_did_create_fail() {
+        let cmd = command_line();
+        let y = cmd.try_get_matches_from(vec![
+            "soldid",
+            "did-create",
+            "-n",
+            "Alice",
+            "--key-type",
+            "rsa",
+        ]);
+        assert!(y.is_err());
+    }
+
+    #[test]
+    fn test_command_delegated_did_create_pass() {
+        let cmd = command_line();
+        let y = cmd.get_matches_from(vec![
+            "soldid",
+            "did-create",
+            "-n",
+            "Bob",
+            "--parent",
+            "Alice",
+        ]);
+        let (subcmd, matches) = y.subcommand().unwrap();
+        assert_eq!(subcmd, "did-create");
+        assert_eq!(matches.get_one::<String>("parent").unwrap(), "Alice");
     }
 
     #[test]
@@ -179,6 +459,57 @@ mod cli_tests {
         assert_eq!(faux_path, *w);
     }
 
+    #[test]
+    fn test_did_revoke() {
+        let cmd = command_line();
+        let y = cmd.get_matches_from(vec!["soldid", "did-revoke", "-n", "Alice"]);
+        let (subcmd, matches) = y.subcommand().unwrap();
+        assert_eq!(subcmd, "did-revoke");
+        assert_eq!(matches.get_one::<String>("reason").unwrap(), "unspecified");
+        let cmd = command_line();
+        let y = cmd.get_matches_from(vec![
+            "soldid",
+            "did-revoke",
+            "-n",
+            "Alice",
+            "--reason",
+            "key-compromise",
+        ]);
+        let (_, matches) = y.subcommand().unwrap();
+        assert_eq!(
+            matches.get_one::<String>("reason").unwrap(),
+            "key-compromise"
+        );
+        let cmd = command_line();
+        let y = cmd.try_get_matches_from(vec!["soldid", "did-revoke", "-n", "Alice", "-r", "lost"]);
+        assert!(y.is_err());
+    }
+
+    #[test]
+    fn test_did_anchor() {
+        let cmd = command_line();
+        let y = cmd.get_matches_from(vec![
+            "soldid",
+            "did-anchor",
+            "-n",
+            "Alice",
+            "--file",
+            "credential.json",
+            "-d",
+            "ELC5L3iBVD77d_MYbYGGCUQgqQBju1o4x1Ud-z2sL-ux",
+        ]);
+        let (subcmd, matches) = y.subcommand().unwrap();
+        assert_eq!(subcmd, "did-anchor");
+        let files = matches.get_many::<PathBuf>("file").unwrap();
+        assert_eq!(files.count(), 1);
+        let digests = matches.get_many::<String>("digest").unwrap();
+        assert_eq!(digests.count(), 1);
+        // A file or digest is required
+        let cmd = command_line();
+        let y = cmd.try_get_matches_from(vec!["soldid", "did-anchor", "-n", "Alice"]);
+        assert!(y.is_err());
+    }
+
     #[test]
     fn test_did_close() {
         let cmd = command_line();
@@ -186,5 +517,143 @@ mod cli_tests {
         let pda = "AgxPQbWut4owLJEzSiZTkuCxRL1xAa5YRsGy6J85MDQS";
         let y = cmd.get_matches_from(vec!["soldid", "-w", faux_dir, "did-close", "-p", pda]);
         assert_eq!(y.subcommand_name().unwrap(), "did-close");
+        let (_, matches) = y.subcommand().unwrap();
+        assert!(matches.get_one::<String>("recipient").is_none());
+        assert!(!*matches.get_one::<bool>("force").unwrap());
+    }
+
+    #[test]
+    fn test_did_close_recipient_force() {
+        let cmd = command_line();
+        let pda = "AgxPQbWut4owLJEzSiZTkuCxRL1xAa5YRsGy6J85MDQS";
+        let recipient = "BPFLoaderUpgradeab1e11111111111111111111111";
+        let y = cmd.get_matches_from(vec![
+            "soldid",
+            "did-close",
+            "-p",
+            pda,
+            "--recipient",
+            recipient,
+            "--force",
+        ]);
+        let (_, matches) = y.subcommand().unwrap();
+        assert_eq!(matches.get_one::<String>("recipient").unwrap(), recipient);
+        assert!(*matches.get_one::<bool>("force").unwrap());
+    }
+
+    #[test]
+    fn test_fee_payer_authority() {
+        let cmd = command_line();
+        let y = cmd.get_matches_from(vec!["soldid", "did-list"]);
+        assert!(y.get_one::<PathBuf>("fee-payer").is_none());
+        assert!(y.get_one::<PathBuf>("authority").is_none());
+        assert!(y.get_one::<PathBuf>("delegator-authority").is_none());
+        let cmd = command_line();
+        let y = cmd.get_matches_from(vec![
+            "soldid",
+            "did-list",
+            "--fee-payer",
+            "sponsor.json",
+            "--authority",
+            "user.json",
+            "--delegator-authority",
+            "parent.json",
+        ]);
+        assert_eq!(
+            y.get_one::<PathBuf>("fee-payer").unwrap(),
+            &PathBuf::from("sponsor.json")
+        );
+        assert_eq!(
+            y.get_one::<PathBuf>("authority").unwrap(),
+            &PathBuf::from("user.json")
+        );
+        assert_eq!(
+            y.get_one::<PathBuf>("delegator-authority").unwrap(),
+            &PathBuf::from("parent.json")
+        );
+    }
+
+    #[test]
+    fn test_did_transfer_authority() {
+        let cmd = command_line();
+        let pda = "AgxPQbWut4owLJEzSiZTkuCxRL1xAa5YRsGy6J85MDQS";
+        let authority = "BPFLoaderUpgradeab1e11111111111111111111111";
+        let y = cmd.get_matches_from(vec![
+            "soldid",
+            "did-transfer-authority",
+            "-p",
+            pda,
+            "--propose",
+            authority,
+        ]);
+        let (subcmd, matches) = y.subcommand().unwrap();
+        assert_eq!(subcmd, "did-transfer-authority");
+        assert_eq!(matches.get_one::<String>("propose").unwrap(), authority);
+        assert!(!*matches.get_one::<bool>("accept").unwrap());
+        let cmd = command_line();
+        let y = cmd.get_matches_from(vec![
+            "soldid",
+            "did-transfer-authority",
+            "-p",
+            pda,
+            "--accept",
+        ]);
+        let (_, matches) = y.subcommand().unwrap();
+        assert!(*matches.get_one::<bool>("accept").unwrap());
+        // One of propose, accept or cancel is required
+        let cmd = command_line();
+        let y = cmd.try_get_matches_from(vec!["soldid", "did-transfer-authority", "-p", pda]);
+        assert!(y.is_err());
+        let cmd = command_line();
+        let y = cmd.try_get_matches_from(vec![
+            "soldid",
+            "did-transfer-authority",
+            "-p",
+            pda,
+            "--accept",
+            "--cancel",
+        ]);
+        assert!(y.is_err());
+    }
+
+    #[test]
+    fn test_did_service_add() {
+        let cmd = command_line();
+        let y = cmd.get_matches_from(vec![
+            "soldid",
+            "did-service-add",
+            "-n",
+            "Alice",
+            "--id",
+            "#mediator",
+            "--type",
+            "DIDCommMessaging",
+            "--endpoint",
+            "https://mediator.example.com",
+        ]);
+        let (subcmd, matches) = y.subcommand().unwrap();
+        assert_eq!(subcmd, "did-service-add");
+        assert_eq!(matches.get_one::<String>("id").unwrap(), "#mediator");
+        assert_eq!(
+            matches.get_one::<String>("endpoint").unwrap(),
+            "https://mediator.example.com"
+        );
+    }
+
+    #[test]
+    fn test_did_service_remove() {
+        let cmd = command_line();
+        let y = cmd.get_matches_from(vec![
+            "soldid",
+            "did-service-remove",
+            "-n",
+            "Alice",
+            "-i",
+            "#hub",
+        ]);
+        let (subcmd, matches) = y.subcommand().unwrap();
+        assert_eq!(subcmd, "did-service-remove");
+        assert_eq!(matches.get_one::<String>("id").unwrap(), "#hub");
+        assert!(!matches.contains_id("endpoint"));
     }
 }
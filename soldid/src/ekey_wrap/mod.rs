@@ -0,0 +1,169 @@
+//! Ed25519 key wrapper
+
+use hbkr_rs::{
+    basic::Basic,
+    key_manage::{KeySet, PrivKey, Privatekey, Publickey},
+};
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use crate::wallet::wallet_enums::{KeyType, WalletKeySet};
+
+/// Ed25519 keys are held as Solana keypairs, the private keys are the
+/// 64 byte keypair encoding so signers can be rebuilt from the wallet
+#[derive(Debug)]
+pub struct Ed25519KeySet {
+    barren: bool,
+    keytype: Basic,
+    current: Vec<Keypair>,
+    next: Vec<Keypair>,
+}
+
+impl Ed25519KeySet {
+    /// Create a KeySet for count (1-127) current next KeyPairs
+    pub fn new_for(count: i8) -> Self {
+        let mut current = Vec::<Keypair>::new();
+        let mut next = Vec::<Keypair>::new();
+        for _ in 0..count {
+            current.push(Keypair::new());
+            next.push(Keypair::new());
+        }
+        Self {
+            barren: false,
+            current,
+            next,
+            keytype: Basic::ED25519,
+        }
+    }
+
+    /// Create an empty KeySet
+    pub fn new_empty() -> Self {
+        Self {
+            barren: true,
+            keytype: Basic::ED25519,
+            current: Vec::<Keypair>::new(),
+            next: Vec::<Keypair>::new(),
+        }
+    }
+}
+
+impl KeySet for Ed25519KeySet {
+    /// is_barren returns true if there are no keys in the keyset
+    fn is_barren(&self) -> bool {
+        self.barren
+    }
+
+    fn from(&mut self, current_ks: Vec<String>, next_ks: Vec<String>) {
+        self.current = current_ks
+            .iter()
+            .map(|s| Keypair::from_base58_string(s))
+            .collect::<Vec<Keypair>>();
+        self.next = next_ks
+            .iter()
+            .map(|s| Keypair::from_base58_string(s))
+            .collect::<Vec<Keypair>>();
+        self.barren = false
+    }
+    fn rotate(&mut self, new_next: Option<Vec<Privatekey>>) -> (Vec<Privatekey>, Vec<Privatekey>) {
+        self.current = std::mem::take(&mut self.next);
+        self.next = match new_next {
+            Some(k) => k
+                .iter()
+                .map(|s| Keypair::from_base58_string(&s.as_base58_string()))
+                .collect::<Vec<Keypair>>(),
+            None => self
+                .current
+                .iter()
+                .map(|_| Keypair::new())
+                .collect::<Vec<Keypair>>(),
+        };
+        (self.current_private_keys(), self.next_private_keys())
+    }
+    fn current_private_keys(&self) -> Vec<Privatekey> {
+        self.current
+            .iter()
+            .map(|x| Privatekey::new(x.to_bytes().to_vec()))
+            .collect::<Vec<Privatekey>>()
+    }
+
+    fn next_private_keys(&self) -> Vec<Privatekey> {
+        self.next
+            .iter()
+            .map(|x| Privatekey::new(x.to_bytes().to_vec()))
+            .collect::<Vec<Privatekey>>()
+    }
+
+    fn current_public_keys(&self) -> Vec<Publickey> {
+        self.current
+            .iter()
+            .map(|x| Publickey::new(x.pubkey().to_bytes().to_vec()))
+            .collect::<Vec<Publickey>>()
+    }
+
+    fn next_public_keys(&self) -> Vec<Publickey> {
+        self.next
+            .iter()
+            .map(|x| Publickey::new(x.pubkey().to_bytes().to_vec()))
+            .collect::<Vec<Publickey>>()
+    }
+
+    fn key_type(&self) -> Basic {
+        self.keytype
+    }
+}
+
+impl WalletKeySet for Ed25519KeySet {
+    fn wallet_key_type(&self) -> KeyType {
+        KeyType::ED25519
+    }
+}
+
+#[cfg(test)]
+mod ed25519_key_tests {
+    use crate::errors::SolDidResult;
+
+    use super::*;
+    #[test]
+    fn test_basic_with_ed25519_pass() -> SolDidResult<()> {
+        let kset1 = Ed25519KeySet::new_for(2);
+        assert!(!kset1.is_barren());
+        assert_eq!(kset1.key_type(), Basic::ED25519);
+        assert_eq!(kset1.current_private_keys().len(), 2);
+        assert_eq!(kset1.current_public_keys().len(), 2);
+        assert_eq!(kset1.next_private_keys().len(), 2);
+        assert_eq!(kset1.next_public_keys().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_with_ed25519_pass() -> SolDidResult<()> {
+        let kset1 = Ed25519KeySet::new_empty();
+        assert!(kset1.is_barren());
+        assert_eq!(kset1.key_type(), Basic::ED25519);
+        assert_eq!(kset1.current_private_keys().len(), 0);
+        assert_eq!(kset1.next_public_keys().len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_wallet_key_pass() -> SolDidResult<()> {
+        let kset1 = Ed25519KeySet::new_for(2);
+        let to_strings = |keys: Vec<Privatekey>| {
+            keys.iter()
+                .map(|k| k.as_base58_string())
+                .collect::<Vec<String>>()
+        };
+        let mut kset2 = Ed25519KeySet::new_empty();
+        kset2.from(
+            to_strings(kset1.current_private_keys()),
+            to_strings(kset1.next_private_keys()),
+        );
+        assert!(!kset2.is_barren());
+        assert_eq!(kset2.current_public_keys(), kset1.current_public_keys());
+        assert_eq!(kset2.next_public_keys(), kset1.next_public_keys());
+        // Rotation promotes the next keys
+        kset2.rotate(None);
+        assert_eq!(kset2.current_public_keys(), kset1.next_public_keys());
+        assert_eq!(kset2.next_public_keys().len(), 2);
+        Ok(())
+    }
+}
@@ -52,10 +52,24 @@ pub enum SolDidError {
     DIDAccountNotExists(String),
     #[error("Called Rotation with 0 current keys. Should use Decommision instead")]
     DIDInvalidRotationUseDecommision,
+    #[error("Expected a verification relationship for each of {0} keys")]
+    RelationshipsMismatch(usize),
+    #[error("Interaction anchors no seals")]
+    InteractionWithoutSeals,
+    #[error("Key event does not commit to next keys")]
+    DIDInvalidNextDigest,
+    #[error("{0} is not a key event")]
+    NotKeyEvent(String),
+    #[error("Pasta keys can neither delegate nor be delegated")]
+    PastaDelegationError,
+    #[error("Keys file version {0} is not supported")]
+    KeysVersionInvalid(u16),
     // Add custom errors here
     // Add library/crate errors here
     #[error("Solana RpcError")]
     SolRpc(#[from] solana_client::client_error::ClientError),
+    #[error("Solana transaction signing error")]
+    SolSigner(#[from] solana_sdk::signer::SignerError),
     #[error("Io Error")]
     IoError(#[from] std::io::Error),
     #[error("HBKR Error")]
@@ -66,6 +80,10 @@ pub enum SolDidError {
     EDError(#[from] SignatureError),
     #[error("Base 58 decoding error")]
     Bse58Error(#[from] bs58::decode::Error),
+    #[error("Transaction signature parsing error")]
+    SignatureParseError(#[from] solana_sdk::signature::ParseSignatureError),
+    #[error("DID program error")]
+    ProgramError(#[from] solana_did_method::error::SDMProgramError),
 }
 
 pub type SolDidResult<T> = std::result::Result<T, SolDidError>;
@@ -1,7 +1,9 @@
 //! For integration testing
 
 pub mod chain_trait;
+pub mod ekey_wrap;
 pub mod errors;
 pub mod pkey_wrap;
+pub mod skey_wrap;
 pub mod solana_wrap;
 pub mod wallet;
@@ -2,21 +2,36 @@
 mod clparse;
 pub mod errors;
 
-use std::str::FromStr;
+use std::{fs, path::PathBuf, str::FromStr};
 
 use clap::ArgMatches;
-use clparse::{DID_CLOSE, KEYS_LIST};
-use hbkr_rs::key_manage::PubKey;
-use solana_did_method::state::SDMDid;
-use solana_sdk::{borsh::try_from_slice_unchecked, pubkey::Pubkey};
+use clparse::{
+    DID_CLOSE, DID_SERVICE_ADD, DID_SERVICE_REMOVE, DID_SERVICE_UPDATE, DID_TRANSFER_AUTHORITY,
+    KEYS_LIST,
+};
+use hbkr_rs::{
+    key_manage::PubKey,
+    said::{SelfAddressing, SelfAddressingPrefix},
+};
+use solana_did_method::state::{SDMDid, SDMRevocationReason, SDMService};
+use solana_sdk::{pubkey::Pubkey, signature::read_keypair_file};
 use soldid::{
-    errors::SolDidResult,
+    ekey_wrap::Ed25519KeySet,
+    errors::{SolDidError, SolDidResult},
     pkey_wrap::PastaKeySet,
+    skey_wrap::Secp256k1KeySet,
     solana_wrap::schain_wrap::SolanaChain,
-    wallet::{generic_keys::Keys, init_wallet, load_wallet_from, Wallet},
+    wallet::{
+        generic_keys::Keys,
+        init_wallet, load_wallet_from,
+        wallet_enums::{KeyType, WalletKeySet},
+        Wallet,
+    },
 };
 
-use crate::clparse::{command_line, DID_CREATE, DID_DECOMMISION, DID_LIST, DID_ROTATE};
+use crate::clparse::{
+    command_line, DID_ANCHOR, DID_CREATE, DID_DECOMMISION, DID_LIST, DID_REVOKE, DID_ROTATE,
+};
 
 /// List the keys and their prefixes
 fn list_dids(wallet: &Wallet, schain: &mut SolanaChain) -> SolDidResult<()> {
@@ -30,8 +45,20 @@ fn list_dids(wallet: &Wallet, schain: &mut SolanaChain) -> SolDidResult<()> {
                 did_pk,
             );
             let did_acc = schain.get_did(&did_pk);
-            let adata = try_from_slice_unchecked::<SDMDid>(&did_acc.data).unwrap();
+            let adata = SDMDid::unpack(&did_acc.data)?;
             println!("DID account {:?}", adata);
+            println!(
+                "- Created slot {}, updated slot {} at unix time {}",
+                adata.created_slot(),
+                adata.updated_slot(),
+                adata.updated_unix_timestamp()
+            );
+            for service in adata.services() {
+                println!(
+                    "- Service {} {} {}",
+                    service.id, service.service_type, service.endpoint
+                );
+            }
         }
     } else {
         println!("No DID keysets exist");
@@ -51,7 +78,8 @@ fn display_keys(keyset: &Keys, detail: Option<&bool>) {
         "Account:   {:?}",
         Pubkey::from_str(keyset.account().as_base58_string().as_str()).unwrap()
     );
-    println!("Threshold: {}\n- Events", keyset.threshold());
+    println!("Threshold: {}", keyset.threshold());
+    println!("Next threshold: {}\n- Events", keyset.next_threshold());
     if *detail.unwrap() {
         // println!("\nEvents");
         // println!("-----");
@@ -91,7 +119,17 @@ fn list_keys(wallet: &Wallet, matches: &ArgMatches) -> SolDidResult<()> {
     Ok(())
 }
 
-/// Create a new DID extracts the name, keycount and threshold arguments
+/// Empty keyset of the key type to rehydrate existing keys into
+fn barren_key_set(key_type: KeyType) -> Box<dyn WalletKeySet> {
+    match key_type {
+        KeyType::ED25519 => Box::new(Ed25519KeySet::new_empty()),
+        KeyType::PASTA => Box::new(PastaKeySet::new_empty()),
+        KeyType::SECP256K1 => Box::new(Secp256k1KeySet::new_empty()),
+    }
+}
+
+/// Create a new DID extracts the name, keycount, threshold and key type
+/// arguments
 fn create_did(
     wallet: &mut Wallet,
     matches: &ArgMatches,
@@ -100,8 +138,24 @@ fn create_did(
     let key_count = *matches.get_one::<i8>("keys").unwrap();
     let threshold = *matches.get_one::<i8>("threshold").unwrap();
     let kset_name = &*matches.get_one::<String>("name").unwrap();
-    let kset = PastaKeySet::new_for(key_count);
-    wallet.new_did(kset_name, &kset, threshold, Some(schain))
+    let kset: Box<dyn WalletKeySet> = match matches.get_one::<String>("key-type").unwrap().as_str()
+    {
+        "pasta" => Box::new(PastaKeySet::new_for(key_count)),
+        "ed25519" => Box::new(Ed25519KeySet::new_for(key_count)),
+        "secp256k1" => Box::new(Secp256k1KeySet::new_for(key_count)),
+        _ => return Err(SolDidError::UnknownKeyTypeError),
+    };
+    match matches.get_one::<String>("parent") {
+        Some(parent) => wallet.new_delegated_did(
+            kset_name,
+            parent,
+            kset.as_ref(),
+            threshold,
+            None,
+            Some(schain),
+        ),
+        None => wallet.new_did(kset_name, kset.as_ref(), threshold, None, Some(schain)),
+    }
 }
 
 /// Rotate a new DID
@@ -111,10 +165,11 @@ fn simple_rotate_did(
     schain: &mut SolanaChain,
 ) -> SolDidResult<(String, Vec<u8>)> {
     let kset_name = &*matches.get_one::<String>("name").unwrap();
-    let mut barren_ks = PastaKeySet::new_empty();
+    let mut barren_ks = barren_key_set(wallet.keys_for_name(kset_name)?.key_type());
     wallet.rotate_did_with_name(
         kset_name.to_string(),
-        &mut barren_ks,
+        barren_ks.as_mut(),
+        None,
         None,
         None,
         Some(schain),
@@ -128,19 +183,124 @@ fn decommision_did(
     schain: &mut SolanaChain,
 ) -> SolDidResult<(String, Vec<u8>)> {
     let kset_name = &*matches.get_one::<String>("name").unwrap();
-    let mut barren_ks = PastaKeySet::new_empty();
-    wallet.decommission_did_with_name(kset_name.to_string(), &mut barren_ks, Some(schain))
+    let mut barren_ks = barren_key_set(wallet.keys_for_name(kset_name)?.key_type());
+    wallet.decommission_did_with_name(kset_name.to_string(), barren_ks.as_mut(), Some(schain))
+}
+
+/// Revoke a DID after compromise
+fn revoke_did(
+    wallet: &mut Wallet,
+    matches: &ArgMatches,
+    schain: &mut SolanaChain,
+) -> SolDidResult<String> {
+    let kset_name = &*matches.get_one::<String>("name").unwrap();
+    let reason = match matches.get_one::<String>("reason").unwrap().as_str() {
+        "key-compromise" => SDMRevocationReason::KeyCompromise,
+        "authority-compromise" => SDMRevocationReason::AuthorityCompromise,
+        "superseded" => SDMRevocationReason::Superseded,
+        "cessation-of-operation" => SDMRevocationReason::CessationOfOperation,
+        _ => SDMRevocationReason::Unspecified,
+    };
+    wallet.revoke_did_with_name(kset_name.to_string(), reason, Some(schain))
+}
+
+/// Anchor the digests of files and qb64 digests to a DID
+fn anchor_did(
+    wallet: &mut Wallet,
+    matches: &ArgMatches,
+    schain: &mut SolanaChain,
+) -> SolDidResult<(String, Vec<u8>)> {
+    let kset_name = &*matches.get_one::<String>("name").unwrap();
+    let mut seals = Vec::<SelfAddressingPrefix>::new();
+    if let Some(files) = matches.get_many::<PathBuf>("file") {
+        for file in files {
+            seals.push(SelfAddressing::Blake3_256.derive(&fs::read(file)?));
+        }
+    }
+    if let Some(digests) = matches.get_many::<String>("digest") {
+        for digest in digests {
+            seals.push(SelfAddressingPrefix::from_str(digest)?);
+        }
+    }
+    wallet.interact_did_with_name(kset_name.to_string(), &seals, Some(schain))
 }
 
-/// Close the DID account on the chain
+/// Close the DID account on the chain, the current keys of the wallet
+/// keyset of the account sign for the DID
 fn close_did(
-    _wallet: &mut Wallet,
+    wallet: &mut Wallet,
     matches: &ArgMatches,
     schain: &mut SolanaChain,
 ) -> SolDidResult<()> {
     let pda_key = &*matches.get_one::<String>("pda").unwrap();
     let sol_pk = Pubkey::from_str(pda_key).unwrap();
-    schain.close_did(&sol_pk)?;
+    let recipient = matches
+        .get_one::<String>("recipient")
+        .map(|r| Pubkey::from_str(r).unwrap());
+    let force = *matches.get_one::<bool>("force").unwrap();
+    let controllers = match wallet
+        .keys()?
+        .iter()
+        .find(|k| k.account().to_bytes() == sol_pk.to_bytes())
+    {
+        Some(keys) => keys.current_private_keys()?,
+        None => vec![],
+    };
+    schain.close_did(&sol_pk, recipient.as_ref(), force, &controllers)?;
+    Ok(())
+}
+
+/// Propose, accept or cancel the transfer of the DID account authority,
+/// the signer is the current authority or, when accepting, the proposed one
+fn transfer_authority(matches: &ArgMatches, schain: &mut SolanaChain) -> SolDidResult<()> {
+    let pda_key = &*matches.get_one::<String>("pda").unwrap();
+    let sol_pk = Pubkey::from_str(pda_key).unwrap();
+    let signature = match matches.get_one::<String>("propose") {
+        Some(authority) => {
+            schain.propose_authority(&sol_pk, &Pubkey::from_str(authority).unwrap())?
+        }
+        None if *matches.get_one::<bool>("accept").unwrap() => schain.accept_authority(&sol_pk)?,
+        None => schain.cancel_authority(&sol_pk)?,
+    };
+    println!("Authority transfer of {} signature {}", sol_pk, signature);
+    Ok(())
+}
+
+/// Get the DID account of the named keyset
+fn did_account_for(keys: &Keys) -> Pubkey {
+    Pubkey::from_str(&keys.account().as_base58_string()).unwrap()
+}
+
+/// Get the service from the id, type and endpoint arguments
+fn service_from(matches: &ArgMatches) -> SDMService {
+    SDMService {
+        id: matches.get_one::<String>("id").unwrap().to_string(),
+        service_type: matches.get_one::<String>("type").unwrap().to_string(),
+        endpoint: matches.get_one::<String>("endpoint").unwrap().to_string(),
+    }
+}
+
+/// Publish, update or remove a service endpoint of the DID
+fn change_service(
+    wallet: &Wallet,
+    command: &str,
+    matches: &ArgMatches,
+    schain: &mut SolanaChain,
+) -> SolDidResult<()> {
+    let keys = wallet.keys_for_name(matches.get_one::<String>("name").unwrap())?;
+    let did_key = did_account_for(keys);
+    let controllers = keys.current_private_keys()?;
+    match command {
+        DID_SERVICE_ADD => schain.add_service(&did_key, &controllers, service_from(matches))?,
+        DID_SERVICE_UPDATE => {
+            schain.update_service(&did_key, &controllers, service_from(matches))?
+        }
+        _ => schain.remove_service(
+            &did_key,
+            &controllers,
+            matches.get_one::<String>("id").unwrap(),
+        )?,
+    };
     Ok(())
 }
 
@@ -151,6 +311,17 @@ async fn main() -> SolDidResult<()> {
 
     // Load chain wrapper
     let mut chain = SolanaChain::default();
+    // Separate fee payer and authority keypairs replace the configured one,
+    // delegated DIDs are approved by the authority unless set apart
+    if let Some(path) = cmdline.get_one::<PathBuf>("fee-payer") {
+        chain.set_fee_payer(read_keypair_file(path)?);
+    }
+    if let Some(path) = cmdline.get_one::<PathBuf>("authority") {
+        chain.set_authority(read_keypair_file(path)?);
+    }
+    if let Some(path) = cmdline.get_one::<PathBuf>("delegator-authority") {
+        chain.set_delegator_authority(read_keypair_file(path)?);
+    }
     // Load default wallet or use user provided command line wallet path
     let mut wallet = match cmdline.value_source("wallet").unwrap() {
         clap::ValueSource::DefaultValue => init_wallet()?,
@@ -173,7 +344,19 @@ async fn main() -> SolDidResult<()> {
             let _res = decommision_did(&mut wallet, matches, &mut chain)?;
             {}
         }
+        DID_REVOKE => {
+            let _res = revoke_did(&mut wallet, matches, &mut chain)?;
+            {}
+        }
+        DID_ANCHOR => {
+            let _res = anchor_did(&mut wallet, matches, &mut chain)?;
+            {}
+        }
         DID_CLOSE => close_did(&mut wallet, matches, &mut chain)?,
+        DID_TRANSFER_AUTHORITY => transfer_authority(matches, &mut chain)?,
+        DID_SERVICE_ADD | DID_SERVICE_UPDATE | DID_SERVICE_REMOVE => {
+            change_service(&wallet, command, matches, &mut chain)?
+        }
         KEYS_LIST => list_keys(&wallet, matches)?,
         _ => {}
     }
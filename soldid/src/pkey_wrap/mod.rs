@@ -6,6 +6,8 @@ use hbkr_rs::{
 };
 use hbpasta_rs::Keypair as PastaKP;
 
+use crate::wallet::wallet_enums::{KeyType, WalletKeySet};
+
 #[derive(Clone, Debug)]
 pub struct PastaKeySet {
     barren: bool,
@@ -107,6 +109,12 @@ impl KeySet for PastaKeySet {
     }
 }
 
+impl WalletKeySet for PastaKeySet {
+    fn wallet_key_type(&self) -> KeyType {
+        KeyType::PASTA
+    }
+}
+
 #[cfg(test)]
 mod pasta_key_tests {
     use crate::errors::SolDidResult;
@@ -0,0 +1,453 @@
+//! Secp256k1 key wrapper
+
+use hbkr_rs::{
+    basic::Basic,
+    dummy_event::dummy_prefix,
+    event::Event,
+    event_data::EventData,
+    event_message::EventMessage,
+    identifier_prefix::IdentifierPrefix,
+    key_config::KeyConfig,
+    key_manage::{KeySet, PrivKey, Privatekey, Publickey},
+    said::{SelfAddressing, SelfAddressingPrefix},
+    said_event::SaidEvent,
+    seal::Seal,
+    serialization_info::SerializationInfo,
+    threshold::SignatureThreshold,
+    witness_config::{InceptionWitnessConfig, WitnessConfig},
+    EventTypeTag, Prefix, Typeable,
+};
+use libsecp256k1::{PublicKey, SecretKey};
+use serde::Serialize;
+use solana_did_method::{
+    instruction::SMDKeyType,
+    keri::{key_code, next_keys_digest},
+};
+
+use crate::{
+    errors::SolDidResult,
+    wallet::wallet_enums::{KeyType, WalletKeySet},
+};
+
+/// Witness configuration of an establishment event
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Secp256k1Witnesses<'a> {
+    Inception(&'a InceptionWitnessConfig),
+    Rotation(&'a WitnessConfig),
+}
+
+/// Establishment event message of secp256k1 keys, in the field order of
+/// hbkr event messages. hbkr has no secp256k1 derivation, the keys are
+/// carried with the secp256k1 code the program verifies them by
+#[derive(Serialize)]
+struct Secp256k1EventMessage<'a> {
+    v: SerializationInfo,
+    t: EventTypeTag,
+    d: String,
+    i: String,
+    s: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    p: Option<&'a SelfAddressingPrefix>,
+    kt: &'a SignatureThreshold,
+    k: Vec<String>,
+    n: String,
+    #[serde(flatten)]
+    witnesses: Secp256k1Witnesses<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    c: Option<&'a Vec<String>>,
+    a: &'a Vec<Seal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    di: Option<&'a IdentifierPrefix>,
+}
+
+impl<'a> Secp256k1EventMessage<'a> {
+    fn new(event_msg: &'a EventMessage<SaidEvent<Event>>) -> Self {
+        let content = &event_msg.event.content;
+        let (prior, key_config, witnesses, configuration, seals, delegator) =
+            match &content.event_data {
+                EventData::Icp(icp) => (
+                    None,
+                    &icp.key_config,
+                    Secp256k1Witnesses::Inception(&icp.witness_config),
+                    Some(&icp.inception_configuration),
+                    &icp.data,
+                    None,
+                ),
+                EventData::Dip(dip) => (
+                    None,
+                    &dip.inception_data.key_config,
+                    Secp256k1Witnesses::Inception(&dip.inception_data.witness_config),
+                    Some(&dip.inception_data.inception_configuration),
+                    &dip.inception_data.data,
+                    Some(&dip.delegator),
+                ),
+                EventData::Rot(rot) | EventData::Drt(rot) => (
+                    Some(&rot.previous_event_hash),
+                    &rot.key_config,
+                    Secp256k1Witnesses::Rotation(&rot.witness_config),
+                    None,
+                    &rot.data,
+                    None,
+                ),
+            };
+        Self {
+            v: event_msg.serialization_info,
+            t: event_msg.event.get_type(),
+            d: event_msg.get_digest().to_str(),
+            i: content.prefix.to_str(),
+            s: format!("{:x}", content.sn),
+            p: prior,
+            kt: &key_config.threshold,
+            k: key_config
+                .public_keys
+                .iter()
+                .map(|key| {
+                    [
+                        key_code(SMDKeyType::Secp256k1),
+                        &base64::encode_config(key.derivative(), base64::URL_SAFE_NO_PAD),
+                    ]
+                    .concat()
+                })
+                .collect(),
+            n: key_config
+                .threshold_key_digest
+                .as_ref()
+                .map(|digest| digest.to_str())
+                .unwrap_or_default(),
+            witnesses,
+            c: configuration,
+            a: seals,
+            di: delegator,
+        }
+    }
+
+    fn serialize(&self) -> SolDidResult<Vec<u8>> {
+        Ok(self.v.kind.encode(self)?)
+    }
+}
+
+/// Key configuration of an establishment event
+fn key_config(event_msg: &mut EventMessage<SaidEvent<Event>>) -> &mut KeyConfig {
+    match &mut event_msg.event.content.event_data {
+        EventData::Icp(icp) => &mut icp.key_config,
+        EventData::Dip(dip) => &mut dip.inception_data.key_config,
+        EventData::Rot(rot) | EventData::Drt(rot) => &mut rot.key_config,
+    }
+}
+
+/// Serialized event message of secp256k1 keys, the message the program
+/// verifies and the controllers sign
+pub fn secp256k1_message(event_msg: &EventMessage<SaidEvent<Event>>) -> SolDidResult<Vec<u8>> {
+    Secp256k1EventMessage::new(event_msg).serialize()
+}
+
+/// Render an establishment event of secp256k1 keys as the program
+/// verifies it. The next keys are committed to with the secp256k1 code
+/// and the digest, the prefix of an inception, derived from the message
+/// of `secp256k1_message` with the dummy digest in place
+pub fn secp256k1_event(
+    mut event_msg: EventMessage<SaidEvent<Event>>,
+    key_set: &dyn KeySet,
+    next_threshold: u64,
+) -> SolDidResult<EventMessage<SaidEvent<Event>>> {
+    let derivation = SelfAddressing::Blake3_256;
+    let next_keys = key_set
+        .next_public_keys()
+        .iter()
+        .map(|k| k.to_bytes())
+        .collect::<Vec<Vec<u8>>>();
+    key_config(&mut event_msg).threshold_key_digest = Some(SelfAddressingPrefix::new(
+        derivation.clone(),
+        next_keys_digest(SMDKeyType::Secp256k1, next_threshold, &next_keys).to_vec(),
+    ));
+    let incepting = matches!(
+        event_msg.event.content.event_data,
+        EventData::Icp(_) | EventData::Dip(_)
+    );
+    let mut dummy = Secp256k1EventMessage::new(&event_msg);
+    dummy.d = dummy_prefix(&derivation);
+    if incepting {
+        dummy.i = dummy_prefix(&derivation);
+    }
+    // The version is of fixed width, the message is sized with it in place
+    dummy.v.size = dummy.serialize()?.len();
+    let size = dummy.v.size;
+    let digest = derivation.derive(&dummy.serialize()?);
+    let mut content = event_msg.event.content;
+    if incepting {
+        content.prefix = IdentifierPrefix::SelfAddressing(digest.clone());
+    }
+    event_msg.serialization_info.size = size;
+    event_msg.event = SaidEvent::new(digest, content);
+    Ok(event_msg)
+}
+
+/// Secp256k1 keys are held as secret keys, the private keys are the
+/// 32 byte secret and the public keys the 33 byte compressed key
+#[derive(Debug)]
+pub struct Secp256k1KeySet {
+    barren: bool,
+    current: Vec<SecretKey>,
+    next: Vec<SecretKey>,
+}
+
+impl Secp256k1KeySet {
+    /// Create a KeySet for count (1-127) current next secret keys
+    pub fn new_for(count: i8) -> Self {
+        let mut rng = rand::thread_rng();
+        let mut current = Vec::<SecretKey>::new();
+        let mut next = Vec::<SecretKey>::new();
+        for _ in 0..count {
+            current.push(SecretKey::random(&mut rng));
+            next.push(SecretKey::random(&mut rng));
+        }
+        Self {
+            barren: false,
+            current,
+            next,
+        }
+    }
+
+    /// Create an empty KeySet
+    pub fn new_empty() -> Self {
+        Self {
+            barren: true,
+            current: Vec::<SecretKey>::new(),
+            next: Vec::<SecretKey>::new(),
+        }
+    }
+
+    fn from_base58_string(s: &str) -> SecretKey {
+        SecretKey::parse_slice(&bs58::decode(s).into_vec().unwrap()).unwrap()
+    }
+
+    fn private_keys(keys: &[SecretKey]) -> Vec<Privatekey> {
+        keys.iter()
+            .map(|x| Privatekey::new(x.serialize().to_vec()))
+            .collect::<Vec<Privatekey>>()
+    }
+
+    fn public_keys(keys: &[SecretKey]) -> Vec<Publickey> {
+        keys.iter()
+            .map(|x| {
+                Publickey::new(
+                    PublicKey::from_secret_key(x)
+                        .serialize_compressed()
+                        .to_vec(),
+                )
+            })
+            .collect::<Vec<Publickey>>()
+    }
+}
+
+impl KeySet for Secp256k1KeySet {
+    /// is_barren returns true if there are no keys in the keyset
+    fn is_barren(&self) -> bool {
+        self.barren
+    }
+
+    fn from(&mut self, current_ks: Vec<String>, next_ks: Vec<String>) {
+        self.current = current_ks
+            .iter()
+            .map(|s| Secp256k1KeySet::from_base58_string(s))
+            .collect::<Vec<SecretKey>>();
+        self.next = next_ks
+            .iter()
+            .map(|s| Secp256k1KeySet::from_base58_string(s))
+            .collect::<Vec<SecretKey>>();
+        self.barren = false
+    }
+    fn rotate(&mut self, new_next: Option<Vec<Privatekey>>) -> (Vec<Privatekey>, Vec<Privatekey>) {
+        self.current = std::mem::take(&mut self.next);
+        self.next = match new_next {
+            Some(k) => k
+                .iter()
+                .map(|s| Secp256k1KeySet::from_base58_string(&s.as_base58_string()))
+                .collect::<Vec<SecretKey>>(),
+            None => {
+                let mut rng = rand::thread_rng();
+                self.current
+                    .iter()
+                    .map(|_| SecretKey::random(&mut rng))
+                    .collect::<Vec<SecretKey>>()
+            }
+        };
+        (self.current_private_keys(), self.next_private_keys())
+    }
+    fn current_private_keys(&self) -> Vec<Privatekey> {
+        Secp256k1KeySet::private_keys(&self.current)
+    }
+
+    fn next_private_keys(&self) -> Vec<Privatekey> {
+        Secp256k1KeySet::private_keys(&self.next)
+    }
+
+    fn current_public_keys(&self) -> Vec<Publickey> {
+        Secp256k1KeySet::public_keys(&self.current)
+    }
+
+    fn next_public_keys(&self) -> Vec<Publickey> {
+        Secp256k1KeySet::public_keys(&self.next)
+    }
+
+    /// hbkr has no secp256k1 basic derivation, hbkr renders the keys with
+    /// the Ed25519 code and `secp256k1_event` renders them again with the
+    /// secp256k1 code. The key type of the keyset is its wallet key type
+    fn key_type(&self) -> Basic {
+        Basic::ED25519
+    }
+}
+
+impl WalletKeySet for Secp256k1KeySet {
+    fn wallet_key_type(&self) -> KeyType {
+        KeyType::SECP256K1
+    }
+}
+
+#[cfg(test)]
+mod secp256k1_key_tests {
+    use crate::errors::SolDidResult;
+    use hbkr_rs::{
+        event_msg_builder::EventMsgBuilder, inception, key_manage::key_vec_to_prefix_vec,
+        threshold::SignatureThreshold, EventTypeTag,
+    };
+    use solana_did_method::{
+        instruction::{DIDInception, DIDRotation, SDMKey},
+        keri::ExpectedEvent,
+    };
+
+    use super::*;
+
+    fn sdm_keys(keys: &[Publickey]) -> SolDidResult<Vec<SDMKey>> {
+        keys.iter()
+            .map(|k| Ok(SDMKey::try_from(&k.to_bytes()[..])?))
+            .collect()
+    }
+
+    fn digest_bytes(digest: &SelfAddressingPrefix) -> [u8; 32] {
+        digest.digest.as_slice().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_secp256k1_events_verify_pass() -> SolDidResult<()> {
+        let mut kset1 = Secp256k1KeySet::new_for(2);
+        let icp_event = secp256k1_event(inception(&kset1, 1)?, &kset1, 1)?;
+        let message = secp256k1_message(&icp_event)?;
+        let digest = icp_event.get_digest();
+        let next_digest = next_keys_digest(
+            SMDKeyType::Secp256k1,
+            1,
+            &kset1
+                .next_public_keys()
+                .iter()
+                .map(|k| k.to_bytes())
+                .collect::<Vec<Vec<u8>>>(),
+        );
+        // The program verifies the keys and commitment of the inception
+        let did_inception = DIDInception {
+            keytype: SMDKeyType::Secp256k1,
+            prefix: digest_bytes(&digest),
+            bump: 0,
+            keys: sdm_keys(&kset1.current_public_keys())?,
+            relationships: vec![],
+            threshold: 1,
+            next_digest,
+        };
+        ExpectedEvent::from(&did_inception).verify(&message)?;
+        let rendered = String::from_utf8(message.clone()).unwrap();
+        assert!(rendered.contains(&format!(r#""i":"{}""#, digest.to_str())));
+        assert_eq!(
+            icp_event.serialization_info.size,
+            message.len(),
+            "sized with the secp256k1 keys"
+        );
+        // The digest binds the message with the dummy digest in place
+        let dummy = rendered.replace(&digest.to_str(), &dummy_prefix(&SelfAddressing::Blake3_256));
+        assert!(digest.verify_binding(dummy.as_bytes()));
+
+        // The rotation follows the inception with the committed keys
+        kset1.rotate(None);
+        let rot_event = EventMsgBuilder::new(EventTypeTag::Rot)
+            .with_prefix(&IdentifierPrefix::SelfAddressing(digest.clone()))
+            .with_sn(1)
+            .with_previous_event(&digest)
+            .with_keys(key_vec_to_prefix_vec(
+                &kset1.current_public_keys(),
+                kset1.key_type(),
+            ))
+            .with_threshold(&SignatureThreshold::simple(1))
+            .with_next_keys(key_vec_to_prefix_vec(
+                &kset1.next_public_keys(),
+                kset1.key_type(),
+            ))
+            .with_next_threshold(&SignatureThreshold::simple(1))
+            .build()?;
+        let rot_event = secp256k1_event(rot_event, &kset1, 1)?;
+        let did_rotation = DIDRotation {
+            keytype: SMDKeyType::Secp256k1,
+            prefix: digest_bytes(&digest),
+            prior_digest: digest_bytes(&digest),
+            digest: digest_bytes(&rot_event.get_digest()),
+            sn: 1,
+            keys: sdm_keys(&kset1.current_public_keys())?,
+            relationships: None,
+            threshold: 1,
+            next_digest: next_keys_digest(
+                SMDKeyType::Secp256k1,
+                1,
+                &kset1
+                    .next_public_keys()
+                    .iter()
+                    .map(|k| k.to_bytes())
+                    .collect::<Vec<Vec<u8>>>(),
+            ),
+        };
+        ExpectedEvent::from(&did_rotation).verify(&secp256k1_message(&rot_event)?)?;
+        // The commitment of the inception is to the rotated keys
+        assert_eq!(
+            next_digest,
+            next_keys_digest(
+                SMDKeyType::Secp256k1,
+                1,
+                &sdm_keys(&kset1.current_public_keys())?
+            )
+        );
+        Ok(())
+    }
+    #[test]
+    fn test_basic_with_secp256k1_pass() -> SolDidResult<()> {
+        let kset1 = Secp256k1KeySet::new_for(2);
+        assert!(!kset1.is_barren());
+        assert_eq!(kset1.wallet_key_type(), KeyType::SECP256K1);
+        assert_eq!(kset1.current_private_keys().len(), 2);
+        assert_eq!(kset1.next_private_keys().len(), 2);
+        for key in kset1.current_public_keys() {
+            assert_eq!(key.to_bytes().len(), 33);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_wallet_key_pass() -> SolDidResult<()> {
+        let kset1 = Secp256k1KeySet::new_for(2);
+        let to_strings = |keys: Vec<Privatekey>| {
+            keys.iter()
+                .map(|k| k.as_base58_string())
+                .collect::<Vec<String>>()
+        };
+        let mut kset2 = Secp256k1KeySet::new_empty();
+        assert!(kset2.is_barren());
+        kset2.from(
+            to_strings(kset1.current_private_keys()),
+            to_strings(kset1.next_private_keys()),
+        );
+        assert!(!kset2.is_barren());
+        assert_eq!(kset2.current_public_keys(), kset1.current_public_keys());
+        // Rotation promotes the next keys
+        kset2.rotate(None);
+        assert_eq!(kset2.current_public_keys(), kset1.next_public_keys());
+        assert_eq!(kset2.next_public_keys().len(), 2);
+        Ok(())
+    }
+}
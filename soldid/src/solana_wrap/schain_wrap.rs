@@ -5,32 +5,55 @@ use std::{fmt::Debug, str::FromStr};
 use crate::{
     chain_trait::{Chain, ChainSignature, DidSigner},
     errors::{SolDidError, SolDidResult},
+    skey_wrap::secp256k1_message,
+    wallet::{interaction::InteractionEvent, wallet_enums::WalletKeySet},
 };
 
 use hbkr_rs::{
     event::Event,
+    event_data::EventData,
     event_message::EventMessage,
-    key_manage::{KeySet, PubKey, Publickey},
+    key_config::KeyConfig,
+    key_manage::{Privatekey, Publickey},
     said_event::SaidEvent,
+    threshold::SignatureThreshold,
     Prefix,
 };
 
+use ed25519_dalek::Signer as _;
 use solana_client::rpc_client::RpcClient;
 use solana_did_method::{
+    event_log::SDMEventLog,
     id,
     instruction::{
-        DIDDecommission, DIDInception, DIDRotation, InitializeDidAccount, SDMInstruction,
-        SMDKeyType,
+        self, find_did_address_with_program_id, DIDAuthorityTransfer, DIDDecommission,
+        DIDDelegatedInception, DIDInception, DIDInteraction, DIDPastaSignatures,
+        DIDProposeAuthority, DIDRevoke, DIDRotation, DIDService, DIDServiceRemove,
+        InitializeDidAccount, PastaSignature, SDMKey, SMDKeyType,
     },
-    state::SDMDidState,
+    layout::inception_size,
+    pasta::{self, MAX_TRANSACTION_UNITS, PASTA_SCALAR_BITS},
+    state::{SDMDid, SDMProgramError, SDMRevocationReason, SDMService},
 };
 use solana_sdk::{
     account::Account,
     commitment_config::CommitmentConfig,
-    ed25519_instruction,
-    instruction::{AccountMeta, CompiledInstruction, Instruction},
+    compute_budget::ComputeBudgetInstruction,
+    ed25519_instruction::{
+        PUBKEY_SERIALIZED_SIZE, SIGNATURE_OFFSETS_SERIALIZED_SIZE, SIGNATURE_OFFSETS_START,
+        SIGNATURE_SERIALIZED_SIZE,
+    },
+    ed25519_program,
+    hash::Hash,
+    instruction::{CompiledInstruction, Instruction},
+    keccak,
     message::Message,
-    pubkey::{Pubkey, PUBKEY_BYTES},
+    pubkey::Pubkey,
+    secp256k1_instruction::{
+        HASHED_PUBKEY_SERIALIZED_SIZE, SIGNATURE_OFFSETS_SERIALIZED_SIZE as SECP256K1_OFFSETS_SIZE,
+        SIGNATURE_SERIALIZED_SIZE as SECP256K1_SIGNATURE_SIZE,
+    },
+    secp256k1_program,
     signature::{read_keypair_file, Keypair, Signature},
     signer::Signer,
     transaction::Transaction,
@@ -40,18 +63,30 @@ use solana_transaction_status::UiTransactionEncoding;
 pub struct SolanaChain {
     rpc_url: String,
     rpc_client: RpcClient,
-    signer: Keypair,
+    fee_payer: Keypair,
+    authority: Keypair,
+    /// Authority of the delegator DIDs approving delegated events, the
+    /// authority when not set
+    delegator_authority: Option<Keypair>,
     program_id: Pubkey,
 }
 
 impl SolanaChain {
-    /// Create a new chain instance with designated client and signer
-    pub fn new(rpc_client: RpcClient, signer: Keypair, program_id: Option<Pubkey>) -> Self {
+    /// Create a new chain instance with designated client, the fee payer
+    /// of its transactions and the authority of its DIDs
+    pub fn new(
+        rpc_client: RpcClient,
+        fee_payer: Keypair,
+        authority: Keypair,
+        program_id: Option<Pubkey>,
+    ) -> Self {
         let rpc_url = rpc_client.url();
         Self {
             rpc_url,
             rpc_client,
-            signer,
+            fee_payer,
+            authority,
+            delegator_authority: None,
             program_id: match program_id {
                 Some(pk) => pk,
                 None => id(),
@@ -71,6 +106,13 @@ impl SolanaChain {
         self.rpc_client.get_account(key).unwrap()
     }
 
+    /// Get the event log of the DID prefix, the records of its key events
+    pub fn get_event_log(&self, prefix: &[u8; 32]) -> SolDidResult<SDMEventLog> {
+        let (log_key, _) = SDMEventLog::address(prefix, &self.program_id);
+        let account = self.rpc_client.get_account(&log_key)?;
+        Ok(SDMEventLog::unpack(&account.data)?)
+    }
+
     /// Set the program ID from Publickey
     pub fn set_program_id_from_publickey(&mut self, from: &Publickey) -> SolDidResult<Publickey> {
         let last_pubkey = self.program_id();
@@ -83,6 +125,23 @@ impl SolanaChain {
         self.program_id = from.clone();
         Ok(last_pubkey)
     }
+    /// Set the fee payer of the transactions, returning its pubkey
+    pub fn set_fee_payer(&mut self, fee_payer: Keypair) -> Pubkey {
+        self.fee_payer = fee_payer;
+        self.fee_payer.pubkey()
+    }
+    /// Set the authority of the DIDs, returning its pubkey
+    pub fn set_authority(&mut self, authority: Keypair) -> Pubkey {
+        self.authority = authority;
+        self.authority.pubkey()
+    }
+    /// Set the authority of the delegator DIDs approving delegated
+    /// events, returning its pubkey
+    pub fn set_delegator_authority(&mut self, delegator_authority: Keypair) -> Pubkey {
+        let pubkey = delegator_authority.pubkey();
+        self.delegator_authority = Some(delegator_authority);
+        pubkey
+    }
     /// Get the version of the chain node
     pub fn version(&self) -> semver::Version {
         let version = self.rpc_client.get_version().unwrap();
@@ -118,54 +177,462 @@ impl SolanaChain {
         prefix_bytes
     }
 
-    /// Submits a transaction with programs instruction
+    /// Get the delegator prefix of a delegated inception as 32 byte array
+    fn delegator_bytes(event_msg: &EventMessage<SaidEvent<Event>>) -> Option<[u8; 32]> {
+        match &event_msg.event.content.event_data {
+            EventData::Dip(dip) => match &dip.delegator {
+                hbkr_rs::identifier_prefix::IdentifierPrefix::SelfAddressing(sa) => {
+                    let mut delegator_bytes = [0u8; 32];
+                    delegator_bytes.copy_from_slice(&sa.digest);
+                    Some(delegator_bytes)
+                }
+                _ => unreachable!(),
+            },
+            _ => None,
+        }
+    }
+
+    /// Get the event digest as 32 byte array
+    fn digest_bytes(event_msg: &EventMessage<SaidEvent<Event>>) -> [u8; 32] {
+        let mut digest_bytes = [0u8; 32];
+        digest_bytes.copy_from_slice(&event_msg.get_digest().digest);
+        digest_bytes
+    }
+
+    /// Get the prior event digest of a rotation as 32 byte array
+    fn prior_digest_bytes(event_msg: &EventMessage<SaidEvent<Event>>) -> [u8; 32] {
+        let mut prior_bytes = [0u8; 32];
+        match &event_msg.event.content.event_data {
+            EventData::Rot(rot) | EventData::Drt(rot) => {
+                prior_bytes.copy_from_slice(&rot.previous_event_hash.digest)
+            }
+            _ => unreachable!(),
+        }
+        prior_bytes
+    }
+
+    /// Get the key configuration of a key event
+    fn key_config(event_msg: &EventMessage<SaidEvent<Event>>) -> &KeyConfig {
+        match &event_msg.event.content.event_data {
+            EventData::Icp(icp) => &icp.key_config,
+            EventData::Dip(dip) => &dip.inception_data.key_config,
+            EventData::Rot(rot) | EventData::Drt(rot) => &rot.key_config,
+        }
+    }
+
+    /// Get the signing threshold of a key event
+    fn threshold(event_msg: &EventMessage<SaidEvent<Event>>) -> u64 {
+        match SolanaChain::key_config(event_msg).threshold {
+            SignatureThreshold::Simple(t) => t,
+        }
+    }
+
+    /// Get the next keys commitment of a key event as 32 byte array,
+    /// the `n` field the event is signed with
+    fn next_digest_bytes(event_msg: &EventMessage<SaidEvent<Event>>) -> SolDidResult<[u8; 32]> {
+        match &SolanaChain::key_config(event_msg).threshold_key_digest {
+            Some(digest) => digest
+                .digest
+                .as_slice()
+                .try_into()
+                .map_err(|_| SolDidError::DIDInvalidNextDigest),
+            None => Err(SolDidError::DIDInvalidNextDigest),
+        }
+    }
+
+    /// Serialized event message signed by the keys of the key type,
+    /// secp256k1 keys carry their derivation code, see `secp256k1_message`
+    fn event_message(
+        keytype: SMDKeyType,
+        event_msg: &EventMessage<SaidEvent<Event>>,
+    ) -> SolDidResult<Vec<u8>> {
+        match keytype {
+            SMDKeyType::Secp256k1 => secp256k1_message(event_msg),
+            _ => Ok(event_msg.serialize()?),
+        }
+    }
+
+    /// Convert keyset public keys to DID keys, compressed secp256k1
+    /// keys are told apart by their length
+    fn to_keys(keys: &[Publickey]) -> SolDidResult<Vec<SDMKey>> {
+        keys.iter()
+            .map(|k| Ok(SDMKey::try_from(&k.to_bytes()[..])?))
+            .collect::<SolDidResult<Vec<SDMKey>>>()
+    }
+
+    /// Signature verification instructions of the authority and the
+    /// controllers over the message. Pasta controllers sign through a
+    /// Pasta session instead, see `pasta_session`, so only the authority
+    /// signs here for Pasta DIDs. Secp256k1
+    /// controllers sign in a secp256k1 instruction, which must be the
+    /// first instruction of the transaction
+    fn verify_instructions(
+        &self,
+        keytype: SMDKeyType,
+        controllers: &[Privatekey],
+        message: &[u8],
+    ) -> SolDidResult<Vec<Instruction>> {
+        signature_instructions(&[&self.authority], &[(keytype, controllers)], message)
+    }
+
+    /// Signature verification instructions of `verify_instructions`
+    /// approving the delegated event for the delegator DID, also signed
+    /// by the delegator's authority and its controllers, the current
+    /// keys of the delegator
+    fn approved_instructions(
+        &self,
+        keytype: SMDKeyType,
+        controllers: &[Privatekey],
+        delegator: &[u8; 32],
+        approvers: &[Privatekey],
+        message: &[u8],
+    ) -> SolDidResult<Vec<Instruction>> {
+        let (delegator_key, _) = find_did_address_with_program_id(delegator, &self.program_id);
+        let delegator_did = SDMDid::unpack(&self.rpc_client.get_account(&delegator_key)?.data)?;
+        let delegator_authority = self.delegator_authority.as_ref().unwrap_or(&self.authority);
+        // Fail early on approvals the program rejects
+        delegator_did.verify_authority(delegator_authority.pubkey())?;
+        let mut signers = vec![&self.authority];
+        if delegator_authority.pubkey() != self.authority.pubkey() {
+            signers.push(delegator_authority);
+        }
+        signature_instructions(
+            &signers,
+            &[(keytype, controllers), (delegator_did.keytype(), approvers)],
+            message,
+        )
+    }
+
+    /// Signature verification instructions of `verify_instructions`
+    /// preceded, for a Pasta DID requiring controllers, by the Pasta
+    /// session of the payer verifying the controllers signatures
+    fn authorize_instructions(
+        &self,
+        payer: &Keypair,
+        did: &SDMDid,
+        controllers: &[Privatekey],
+        message: &[u8],
+    ) -> SolDidResult<Vec<Instruction>> {
+        if did.keytype() == SMDKeyType::PASTA && did.requires_controllers() {
+            self.pasta_session(payer, did, controllers, message)?;
+        }
+        self.verify_instructions(did.keytype(), controllers, message)
+    }
+
+    /// Rotation authorized by the first threshold controllers of a Pasta
+    /// DID through a Pasta session of the fee payer, the rotation then
+    /// closes the session
+    fn pasta_rotation(
+        &self,
+        did: &SDMDid,
+        did_rotation: DIDRotation,
+        controllers: &[Privatekey],
+        message: &[u8],
+    ) -> SolDidResult<Signature> {
+        self.pasta_session(&self.fee_payer, did, controllers, message)?;
+        self.submit_transaction(vec![instruction::pasta_rotation(
+            &self.program_id,
+            &self.fee_payer.pubkey(),
+            &self.authority.pubkey(),
+            did_rotation,
+        )])
+    }
+
+    /// Pasta session of the payer verifying the signatures of the message
+    /// by the first threshold controllers of a Pasta DID, over as many
+    /// transactions as the compute budget requires. The instruction the
+    /// signatures authorize closes the session
+    fn pasta_session(
+        &self,
+        payer: &Keypair,
+        did: &SDMDid,
+        controllers: &[Privatekey],
+        message: &[u8],
+    ) -> SolDidResult<()> {
+        let signatures = controllers
+            .iter()
+            .take(did.did_doc.threshold as usize)
+            .enumerate()
+            .map(|(index, controller)| {
+                let secret = <[u8; 32]>::try_from(&controller.to_bytes()[..])
+                    .map_err(|_| SolDidError::KeySetIncoherence)?;
+                Ok(pasta::sign(&secret, index as u8, message)?)
+            })
+            .collect::<SolDidResult<Vec<PastaSignature>>>()?;
+        let bits = PASTA_SCALAR_BITS as usize * signatures.len();
+        let prefix = *did.prefix();
+        let mut signers = vec![&self.fee_payer];
+        if payer.pubkey() != self.fee_payer.pubkey() {
+            signers.push(payer);
+        }
+        // 1. Begin the session with the signatures of the message
+        self.submit_transaction(vec![instruction::pasta_begin(
+            &self.program_id,
+            &payer.pubkey(),
+            DIDPastaSignatures {
+                keytype: SMDKeyType::PASTA,
+                prefix,
+                message: message.to_vec(),
+                signatures,
+            },
+        )])?;
+        // 2. Verify the signatures in steps fitting the compute budget,
+        // the verify transactions are identical so each needs a new
+        // blockhash
+        let steps = pasta::steps_within(MAX_TRANSACTION_UNITS);
+        let mut blockhash = Hash::default();
+        for _ in 0..(bits + steps as usize - 1) / steps as usize {
+            blockhash = self.rpc_client.get_new_latest_blockhash(&blockhash)?;
+            let transaction = Transaction::new_signed_with_payer(
+                &[
+                    ComputeBudgetInstruction::set_compute_unit_limit(MAX_TRANSACTION_UNITS),
+                    instruction::pasta_verify(&self.program_id, &payer.pubkey(), &prefix, steps),
+                ],
+                Some(&self.fee_payer.pubkey()),
+                &signers,
+                blockhash,
+            );
+            self.rpc_client.send_and_confirm_transaction(&transaction)?;
+        }
+        Ok(())
+    }
+
+    /// Submits a transaction with programs instruction, the fee payer
+    /// pays and the authority signs when an instruction requires it
     fn submit_transaction(&self, instructions: Vec<Instruction>) -> SolDidResult<Signature> {
-        let mut transaction =
-            Transaction::new_unsigned(Message::new(&instructions, Some(&self.signer.pubkey())));
-        let recent_blockhash = self.rpc_client.get_latest_blockhash().unwrap();
-        transaction
-            .try_sign(&vec![&self.signer], recent_blockhash)
-            .unwrap();
+        let message = Message::new(&instructions, Some(&self.fee_payer.pubkey()));
+        let mut signers = vec![&self.fee_payer];
+        if self.authority.pubkey() != self.fee_payer.pubkey()
+            && message.signer_keys().contains(&&self.authority.pubkey())
+        {
+            signers.push(&self.authority);
+        }
+        let mut transaction = Transaction::new_unsigned(message);
+        let recent_blockhash = self.rpc_client.get_latest_blockhash()?;
+        transaction.try_sign(&signers, recent_blockhash)?;
         Ok(self.rpc_client.send_and_confirm_transaction(&transaction)?)
     }
-    /// Remove account
-    pub fn close_did(&self, did_key: &Pubkey) -> SolDidResult<()> {
-        // Accounts to pass to instruction
-        let accounts = &[
-            AccountMeta::new(self.signer.pubkey(), true),
-            AccountMeta::new(*did_key, false),
-            AccountMeta::new(solana_sdk::system_program::id(), false),
-        ];
+    /// Remove account and its event log, the lamports go to the recipient
+    /// or the fee payer if not provided. Accounts not decommissioned require force and
+    /// the signatures of their controllers, Pasta controllers signing in
+    /// a Pasta session of the authority
+    pub fn close_did(
+        &self,
+        did_key: &Pubkey,
+        recipient: Option<&Pubkey>,
+        force: bool,
+        controllers: &[Privatekey],
+    ) -> SolDidResult<()> {
+        let did = SDMDid::unpack(&self.get_did(did_key).data)?;
+        let fee_payer = self.fee_payer.pubkey();
+        let recipient = recipient.unwrap_or(&fee_payer);
+        let mut instructions = Vec::<Instruction>::new();
+        if did.requires_controllers() {
+            instructions = self.authorize_instructions(
+                &self.authority,
+                &did,
+                controllers,
+                &did.did_doc.digest,
+            )?;
+        }
+        instructions.push(match did.keytype() {
+            SMDKeyType::PASTA if did.requires_controllers() => instruction::pasta_close(
+                &self.program_id,
+                &self.authority.pubkey(),
+                did.prefix(),
+                recipient,
+                force,
+            ),
+            _ => instruction::close(
+                &self.program_id,
+                &self.authority.pubkey(),
+                did.prefix(),
+                recipient,
+                force,
+            ),
+        });
         // Build instruction array and submit transaction
-        let txn = self.submit_transaction(
-            [Instruction::new_with_borsh(
-                self.program_id,
-                &SDMInstruction::SDMClose,
-                accounts.to_vec(),
-            )]
-            .to_vec(),
-        );
-        assert!(txn.is_ok());
+        self.submit_transaction(instructions)?;
         Ok(())
     }
 
-    /// Fetches and decodes a transactions instruction data
+    /// Upgrade the DID account state to the current version
+    pub fn migrate_did(&self, did_key: &Pubkey) -> SolDidResult<ChainSignature> {
+        let signature = self.submit_transaction(vec![instruction::migrate(
+            &self.program_id,
+            &self.fee_payer.pubkey(),
+            &self.authority.pubkey(),
+            did_key,
+        )])?;
+        Ok(signature.to_string())
+    }
+
+    /// Propose a new authority of the DID account, the authority must be
+    /// the current authority of the DID
+    pub fn propose_authority(
+        &self,
+        did_key: &Pubkey,
+        authority: &Pubkey,
+    ) -> SolDidResult<ChainSignature> {
+        let did = SDMDid::unpack(&self.get_did(did_key).data)?;
+        let signature = self.submit_transaction(vec![instruction::propose_authority(
+            &self.program_id,
+            &self.fee_payer.pubkey(),
+            &self.authority.pubkey(),
+            DIDProposeAuthority {
+                keytype: did.keytype(),
+                prefix: *did.prefix(),
+                authority: *authority,
+            },
+        )])?;
+        Ok(signature.to_string())
+    }
+
+    /// Accept the pending authority transfer of the DID account, the
+    /// authority must be the proposed one
+    pub fn accept_authority(&self, did_key: &Pubkey) -> SolDidResult<ChainSignature> {
+        let did = SDMDid::unpack(&self.get_did(did_key).data)?;
+        let signature = self.submit_transaction(vec![instruction::accept_authority(
+            &self.program_id,
+            &self.fee_payer.pubkey(),
+            &self.authority.pubkey(),
+            DIDAuthorityTransfer {
+                keytype: did.keytype(),
+                prefix: *did.prefix(),
+            },
+        )])?;
+        Ok(signature.to_string())
+    }
+
+    /// Cancel the pending authority transfer of the DID account, the
+    /// authority must be the current authority of the DID
+    pub fn cancel_authority(&self, did_key: &Pubkey) -> SolDidResult<ChainSignature> {
+        let did = SDMDid::unpack(&self.get_did(did_key).data)?;
+        let signature = self.submit_transaction(vec![instruction::cancel_authority(
+            &self.program_id,
+            &self.fee_payer.pubkey(),
+            &self.authority.pubkey(),
+            DIDAuthorityTransfer {
+                keytype: did.keytype(),
+                prefix: *did.prefix(),
+            },
+        )])?;
+        Ok(signature.to_string())
+    }
+
+    /// Submits a service change of the DID, the authority and controllers
+    /// sign the service message of the instruction against the current
+    /// DID state
+    fn submit_service_change(
+        &self,
+        did_key: &Pubkey,
+        controllers: &[Privatekey],
+        change: impl FnOnce(SMDKeyType, [u8; 32]) -> Instruction,
+    ) -> SolDidResult<ChainSignature> {
+        let did = SDMDid::unpack(&self.get_did(did_key).data)?;
+        let instruction = change(did.keytype(), *did.prefix());
+        let mut instructions = self.authorize_instructions(
+            &self.fee_payer,
+            &did,
+            controllers,
+            &did.service_message(&instruction.data)?,
+        )?;
+        instructions.push(instruction);
+        let signature = self.submit_transaction(instructions)?;
+        Ok(signature.to_string())
+    }
+
+    /// Publish a new service endpoint of the DID
+    pub fn add_service(
+        &self,
+        did_key: &Pubkey,
+        controllers: &[Privatekey],
+        service: SDMService,
+    ) -> SolDidResult<ChainSignature> {
+        self.submit_service_change(did_key, controllers, |keytype, prefix| {
+            let service = DIDService {
+                keytype,
+                prefix,
+                service,
+            };
+            instruction::add_service(
+                &self.program_id,
+                &self.fee_payer.pubkey(),
+                &self.authority.pubkey(),
+                service,
+            )
+        })
+    }
+
+    /// Replace the type and endpoint of the DID service with the same id
+    pub fn update_service(
+        &self,
+        did_key: &Pubkey,
+        controllers: &[Privatekey],
+        service: SDMService,
+    ) -> SolDidResult<ChainSignature> {
+        self.submit_service_change(did_key, controllers, |keytype, prefix| {
+            let service = DIDService {
+                keytype,
+                prefix,
+                service,
+            };
+            instruction::update_service(
+                &self.program_id,
+                &self.fee_payer.pubkey(),
+                &self.authority.pubkey(),
+                service,
+            )
+        })
+    }
+
+    /// Remove the DID service with the id
+    pub fn remove_service(
+        &self,
+        did_key: &Pubkey,
+        controllers: &[Privatekey],
+        id: &str,
+    ) -> SolDidResult<ChainSignature> {
+        self.submit_service_change(did_key, controllers, |keytype, prefix| {
+            let remove = DIDServiceRemove {
+                keytype,
+                prefix,
+                id: id.to_string(),
+            };
+            instruction::remove_service(
+                &self.program_id,
+                &self.fee_payer.pubkey(),
+                &self.authority.pubkey(),
+                remove,
+            )
+        })
+    }
+
+    /// Fetches and decodes a transactions instructions of the DID program,
+    /// the signature verifications and compute budget are skipped
     pub fn inception_instructions_from_transaction(
         &self,
         signature: &String,
     ) -> SolDidResult<Vec<CompiledInstruction>> {
-        let signature = Signature::from_str(signature).unwrap();
+        let signature = Signature::from_str(signature)?;
         let tx_post = self
             .rpc_client
             .get_transaction(&signature, UiTransactionEncoding::Base64);
         if tx_post.is_ok() {
             let dc = tx_post.unwrap().transaction.transaction.decode();
             match dc {
-                Some(tx) => Ok([
-                    tx.message.instructions()[0].clone(),
-                    tx.message.instructions()[1].clone(),
-                ]
-                .to_vec()),
+                Some(tx) => {
+                    let account_keys = tx.message.static_account_keys();
+                    Ok(tx
+                        .message
+                        .instructions()
+                        .iter()
+                        .filter(|ix| {
+                            account_keys.get(ix.program_id_index as usize) == Some(&self.program_id)
+                        })
+                        .cloned()
+                        .collect())
+                }
                 None => Err(SolDidError::DecodeTransactionError),
             }
         } else {
@@ -174,6 +641,89 @@ impl SolanaChain {
     }
 }
 
+/// Ed25519 precompile instruction verifying the signature of each
+/// keypair over the message, the message is held once for all signatures
+fn new_ed25519_signatures_instruction(
+    keypairs: &[ed25519_dalek::Keypair],
+    message: &[u8],
+) -> Instruction {
+    let keys_start = SIGNATURE_OFFSETS_START + keypairs.len() * SIGNATURE_OFFSETS_SERIALIZED_SIZE;
+    let key_size = PUBKEY_SERIALIZED_SIZE + SIGNATURE_SERIALIZED_SIZE;
+    let message_offset = keys_start + keypairs.len() * key_size;
+    let mut offsets = vec![keypairs.len() as u8, 0];
+    let mut keys = Vec::<u8>::with_capacity(keypairs.len() * key_size);
+    for (index, keypair) in keypairs.iter().enumerate() {
+        let public_key_offset = keys_start + index * key_size;
+        let signature_offset = public_key_offset + PUBKEY_SERIALIZED_SIZE;
+        // Every offset refers to this instruction
+        for value in [
+            signature_offset as u16,
+            u16::MAX,
+            public_key_offset as u16,
+            u16::MAX,
+            message_offset as u16,
+            message.len() as u16,
+            u16::MAX,
+        ] {
+            offsets.extend_from_slice(&value.to_le_bytes());
+        }
+        keys.extend_from_slice(&keypair.public.to_bytes());
+        keys.extend_from_slice(&keypair.sign(message).to_bytes());
+    }
+    debug_assert_eq!(offsets.len(), keys_start);
+    offsets.extend(keys);
+    offsets.extend_from_slice(message);
+    Instruction {
+        program_id: ed25519_program::id(),
+        accounts: vec![],
+        data: offsets,
+    }
+}
+
+/// Secp256k1 precompile instruction at index verifying the signature of
+/// each secret key over the message, the message is held once for all
+/// signatures. The precompile verifies against the Ethereum address of
+/// each key and the signatures cover the Keccak-256 hash of the message
+fn new_secp256k1_signatures_instruction(
+    secret_keys: &[libsecp256k1::SecretKey],
+    message: &[u8],
+    index: u8,
+) -> Instruction {
+    let keys_start = 1 + secret_keys.len() * SECP256K1_OFFSETS_SIZE;
+    let key_size = HASHED_PUBKEY_SERIALIZED_SIZE + SECP256K1_SIGNATURE_SIZE + 1;
+    let message_offset = keys_start + secret_keys.len() * key_size;
+    let hashed = libsecp256k1::Message::parse(&keccak::hash(message).to_bytes());
+    let mut offsets = vec![secret_keys.len() as u8];
+    let mut keys = Vec::<u8>::with_capacity(secret_keys.len() * key_size);
+    for (position, secret_key) in secret_keys.iter().enumerate() {
+        let eth_address_offset = keys_start + position * key_size;
+        let signature_offset = eth_address_offset + HASHED_PUBKEY_SERIALIZED_SIZE;
+        // Every offset refers to this instruction
+        offsets.extend_from_slice(&(signature_offset as u16).to_le_bytes());
+        offsets.push(index);
+        offsets.extend_from_slice(&(eth_address_offset as u16).to_le_bytes());
+        offsets.push(index);
+        offsets.extend_from_slice(&(message_offset as u16).to_le_bytes());
+        offsets.extend_from_slice(&(message.len() as u16).to_le_bytes());
+        offsets.push(index);
+        let public_key = libsecp256k1::PublicKey::from_secret_key(secret_key).serialize();
+        keys.extend_from_slice(
+            &keccak::hash(&public_key[1..]).to_bytes()[32 - HASHED_PUBKEY_SERIALIZED_SIZE..],
+        );
+        let (signature, recovery_id) = libsecp256k1::sign(&hashed, secret_key);
+        keys.extend_from_slice(&signature.serialize());
+        keys.push(recovery_id.serialize());
+    }
+    debug_assert_eq!(offsets.len(), keys_start);
+    offsets.extend(keys);
+    offsets.extend_from_slice(message);
+    Instruction {
+        program_id: secp256k1_program::id(),
+        accounts: vec![],
+        data: offsets,
+    }
+}
+
 /// Default implementation for SolanaChain
 impl Default for SolanaChain {
     fn default() -> Self {
@@ -187,7 +737,9 @@ impl Default for SolanaChain {
                 CommitmentConfig::confirmed(),
             ),
             rpc_url: cli_config.json_rpc_url.clone(),
-            signer: read_keypair_file(cli_config.keypair_path).unwrap(),
+            fee_payer: read_keypair_file(&cli_config.keypair_path).unwrap(),
+            authority: read_keypair_file(&cli_config.keypair_path).unwrap(),
+            delegator_authority: None,
             program_id: id(),
         }
     }
@@ -199,34 +751,62 @@ impl Debug for SolanaChain {
         f.debug_struct("SolanaChain")
             .field("rpc_url", &self.rpc_url)
             // .field("rpc_client", &self.rpc_client)
-            .field("signer", &self.signer)
+            .field("fee_payer", &self.fee_payer)
+            .field("authority", &self.authority)
+            .field("delegator_authority", &self.delegator_authority)
             .field("program_id", &self.program_id)
             .finish()
     }
 }
 
-/// Calculate the size of the DID account state data size
-/// based on number of keys being managed
-pub fn get_inception_datasize(key_count: usize) -> usize {
-    0usize
-        .saturating_add(std::mem::size_of::<bool>()) // Initialized
-        .saturating_add(std::mem::size_of::<u16>()) // Version
-        .saturating_add(std::mem::size_of::<SDMDidState>()) // State
-        .saturating_add(PUBKEY_BYTES) // Authority pubkey
-        .saturating_add(PUBKEY_BYTES) // Prefix pubkey
-        .saturating_add(std::mem::size_of::<u8>()) // bump
-        .saturating_add(std::mem::size_of::<u32>()) // Borsh vector count
-        .saturating_add(PUBKEY_BYTES * key_count) // Vector of keys size
+/// Signature verification instructions of the signers and the controllers
+/// of each key type over the message. Pasta controllers are left to Pasta
+/// sessions. Secp256k1 controllers sign in a secp256k1 instruction, which
+/// must be the first instruction of the transaction
+fn signature_instructions(
+    signers: &[&Keypair],
+    controllers: &[(SMDKeyType, &[Privatekey])],
+    message: &[u8],
+) -> SolDidResult<Vec<Instruction>> {
+    let mut instructions = Vec::<Instruction>::new();
+    let mut keypairs = signers
+        .iter()
+        .map(|signer| ed25519_dalek::Keypair::from_bytes(&signer.to_bytes()))
+        .collect::<Result<Vec<ed25519_dalek::Keypair>, _>>()?;
+    let mut secret_keys = Vec::<libsecp256k1::SecretKey>::new();
+    for (keytype, controllers) in controllers {
+        for controller in controllers.iter() {
+            match keytype {
+                SMDKeyType::Ed25519 => {
+                    keypairs.push(ed25519_dalek::Keypair::from_bytes(&controller.to_bytes())?)
+                }
+                SMDKeyType::Secp256k1 => secret_keys.push(
+                    libsecp256k1::SecretKey::parse_slice(&controller.to_bytes())
+                        .map_err(|_| SolDidError::KeySetIncoherence)?,
+                ),
+                SMDKeyType::PASTA => (),
+            }
+        }
+    }
+    if !secret_keys.is_empty() {
+        instructions.push(new_secp256k1_signatures_instruction(
+            &secret_keys,
+            message,
+            0,
+        ));
+    }
+    instructions.push(new_ed25519_signatures_instruction(&keypairs, message));
+    Ok(instructions)
 }
 
-const DID_INCEPT_RENT_MULTIPLIER: u64 = 10;
-
 /// Chain trait implementation
 impl Chain for SolanaChain {
     /// Inception
     fn inception_inst(
         &self,
-        key_set: &dyn KeySet,
+        key_set: &dyn WalletKeySet,
+        relationships: &[u8],
+        approvers: &[Privatekey],
         event_msg: &EventMessage<SaidEvent<Event>>,
     ) -> SolDidResult<(ChainSignature, Publickey)> {
         // Verify prefix is not already a PDA collision
@@ -234,66 +814,76 @@ impl Chain for SolanaChain {
         let digest_bytes = event_msg.get_digest().digest;
         let prefix = event_msg.event.get_prefix().to_str();
         let (pda_key, bump) = self.safe_pda_from_digest(&prefix, &digest_bytes)?;
-        // Now we want to create two (2) instructions:
-        // 1. The ed25519 signature verification on the serialized message
-
-        // let signed_digest = self.signer.sign_message(&digest_bytes);
-        let verify_instruction = ed25519_instruction::new_ed25519_instruction(
-            &ed25519_dalek::Keypair::from_bytes(&self.signer.to_bytes())?,
-            &event_msg.serialize()?,
-        );
+        let keytype = SMDKeyType::from(key_set.wallet_key_type());
+        let delegator = SolanaChain::delegator_bytes(event_msg);
+        // Fail early on delegated Pasta DIDs the program rejects
+        if delegator.is_some() && keytype == SMDKeyType::PASTA {
+            return Err(SolDidError::ProgramError(
+                SDMProgramError::PastaNotDelegable,
+            ));
+        }
+        // Now we want to create the instructions:
+        // 1. The signature verifications on the serialized message,
+        // approved by the delegator of a delegated inception
+        let message = SolanaChain::event_message(keytype, event_msg)?;
+        let mut instructions = match &delegator {
+            Some(delegator) => {
+                self.approved_instructions(keytype, &[], delegator, approvers, &message)?
+            }
+            None => self.verify_instructions(keytype, &[], &message)?,
+        };
         // 2. The inception instruction of the DID for program
-        // Convert pasta keys to Solana Pubkey for serialization
-        let keys = key_set
-            .current_public_keys()
-            .iter()
-            .map(|k| Pubkey::from_str(&k.as_base58_string()).unwrap())
-            .collect::<Vec<Pubkey>>();
+        // Convert the keys to DID keys for serialization
+        let keys = SolanaChain::to_keys(&key_set.current_public_keys())?;
         if keys.len() == 0 {
             return Err(SolDidError::DIDInvalidInceptionZeroKeys);
         }
+        // Commit to the next keys for pre-rotation as the event does
+        let next_digest = SolanaChain::next_digest_bytes(event_msg)?;
 
-        // Get prefix in bytes
-        let prefix_bytes = SolanaChain::prefix_bytes(event_msg);
         // Setup DID inception data
-        let data_size = get_inception_datasize(keys.len()) * DID_INCEPT_RENT_MULTIPLIER as usize;
         let did_account = DIDInception {
-            keytype: SMDKeyType::PASTA,
-            prefix: prefix_bytes,
+            keytype,
+            prefix: SolanaChain::prefix_bytes(event_msg),
             bump,
             keys,
+            relationships: relationships.to_vec(),
+            threshold: SolanaChain::threshold(event_msg),
+            next_digest,
         };
 
-        // Get rent calc
+        // Fail early on inception data the program rejects
+        SDMDid::from_inception(did_account.clone(), &self.authority.pubkey())?;
+        // Get rent calc for the exact size, rotations resize the account
+        let data_size = inception_size(keytype, did_account.keys.len(), delegator.is_some());
         let rent_exemption_amount = self
             .rpc_client
             .get_minimum_balance_for_rent_exemption(data_size)?;
-        // TODO - We are paying more in rent than the size of the data which
-        // may grow due to rotation variations
         let init = InitializeDidAccount {
-            rent: DID_INCEPT_RENT_MULTIPLIER * rent_exemption_amount,
+            rent: rent_exemption_amount,
             storage: data_size as u64,
         };
-        // Accounts to pass to instruction
-        let accounts = &[
-            AccountMeta::new(self.signer.pubkey(), true),
-            AccountMeta::new(pda_key, false),
-            AccountMeta::new(solana_sdk::system_program::id(), false),
-        ];
-        // Build instruction array and submit transaction
-        let txn = self.submit_transaction(
-            [
-                verify_instruction,
-                Instruction::new_with_borsh(
-                    self.program_id,
-                    &SDMInstruction::SDMInception(init, did_account),
-                    accounts.to_vec(),
-                ),
-            ]
-            .to_vec(),
-        );
-        assert!(txn.is_ok());
-        let signature = txn.unwrap();
+        instructions.push(match delegator {
+            Some(delegator) => instruction::delegated_inception(
+                &self.program_id,
+                &self.fee_payer.pubkey(),
+                &self.authority.pubkey(),
+                init,
+                DIDDelegatedInception {
+                    delegator,
+                    inception: did_account,
+                },
+            ),
+            None => instruction::inception(
+                &self.program_id,
+                &self.fee_payer.pubkey(),
+                &self.authority.pubkey(),
+                init,
+                did_account,
+            ),
+        });
+        // Submit the transaction
+        let signature = self.submit_transaction(instructions)?;
         let pda_id = Publickey::from(pda_key.to_bytes().to_vec());
         Ok((signature.to_string(), pda_id))
     }
@@ -302,7 +892,10 @@ impl Chain for SolanaChain {
     fn rotation_inst(
         &self,
         inception_digest: &Vec<u8>,
-        key_set: &dyn KeySet,
+        key_set: &dyn WalletKeySet,
+        controllers: &[Privatekey],
+        relationships: Option<&[u8]>,
+        approvers: &[Privatekey],
         event_msg: &EventMessage<SaidEvent<Event>>,
     ) -> SolDidResult<ChainSignature> {
         // Validate we have a did
@@ -311,47 +904,72 @@ impl Chain for SolanaChain {
         if check_acc.is_err() {
             return Err(SolDidError::DIDAccountNotExists(pda_key.to_string()));
         }
-        // let _rotation_digest = event_msg.get_digest().digest;
-        // Now we want to create two (2) instructions:
-        // 1. The ed25519 signature verification on the serialized message
-        let verify_instruction = ed25519_instruction::new_ed25519_instruction(
-            &ed25519_dalek::Keypair::from_bytes(&self.signer.to_bytes())?,
-            &event_msg.serialize()?,
-        );
-        // 2. The rotation instruction of the DID for program
-        // Convert pasta keys to Solana Pubkey for serialization
-        let keys = key_set
-            .current_public_keys()
-            .iter()
-            .map(|k| Pubkey::from_str(&k.as_base58_string()).unwrap())
-            .collect::<Vec<Pubkey>>();
+        let keytype = SMDKeyType::from(key_set.wallet_key_type());
+        // Convert the keys to DID keys for serialization
+        let keys = SolanaChain::to_keys(&key_set.current_public_keys())?;
         if keys.len() == 0 {
             return Err(SolDidError::DIDInvalidRotationUseDecommision);
         }
-        // Create the instruction data
+        // Create the instruction data with the commitment to the next keys
         let did_rotation = DIDRotation {
-            keytype: SMDKeyType::PASTA,
+            keytype,
             prefix: SolanaChain::prefix_bytes(event_msg),
+            prior_digest: SolanaChain::prior_digest_bytes(event_msg),
+            digest: SolanaChain::digest_bytes(event_msg),
+            sn: event_msg.event.get_sn(),
             keys,
+            relationships: relationships.map(|r| r.to_vec()),
+            threshold: SolanaChain::threshold(event_msg),
+            next_digest: SolanaChain::next_digest_bytes(event_msg)?,
         };
-        // Accounts to pass to instruction
-        let accounts = &[
-            AccountMeta::new(self.signer.pubkey(), true),
-            AccountMeta::new(pda_key, false),
-        ];
-        let txn = self.submit_transaction(
-            [
-                verify_instruction,
-                Instruction::new_with_borsh(
-                    self.program_id,
-                    &SDMInstruction::SDMRotation(did_rotation),
-                    accounts.to_vec(),
-                ),
-            ]
-            .to_vec(),
-        );
-        assert!(txn.is_ok());
-        let signature = txn.unwrap();
+        let message = SolanaChain::event_message(keytype, event_msg)?;
+        // Pasta controllers authorize the rotation through a Pasta session
+        if keytype == SMDKeyType::PASTA && !controllers.is_empty() {
+            if let EventData::Rot(_) = &event_msg.event.content.event_data {
+                let did = SDMDid::unpack(&check_acc?.data)?;
+                let signature = self.pasta_rotation(&did, did_rotation, controllers, &message)?;
+                return Ok(signature.to_string());
+            }
+        }
+        // Now we want to create the instructions:
+        // 1. The signature verifications on the serialized message,
+        // approved by the delegator recorded at inception for a delegated
+        // rotation
+        // 2. The rotation instruction of the DID for program
+        let instructions = match &event_msg.event.content.event_data {
+            EventData::Drt(_) => {
+                let did = SDMDid::unpack(&check_acc?.data)?;
+                let delegator = did
+                    .delegator()
+                    .ok_or_else(|| SolDidError::ProgramError(SDMProgramError::DidNotDelegated))?;
+                let mut instructions = self.approved_instructions(
+                    keytype,
+                    controllers,
+                    delegator,
+                    approvers,
+                    &message,
+                )?;
+                instructions.push(instruction::delegated_rotation(
+                    &self.program_id,
+                    &self.fee_payer.pubkey(),
+                    &self.authority.pubkey(),
+                    delegator,
+                    did_rotation,
+                ));
+                instructions
+            }
+            _ => {
+                let mut instructions = self.verify_instructions(keytype, controllers, &message)?;
+                instructions.push(instruction::rotation(
+                    &self.program_id,
+                    &self.fee_payer.pubkey(),
+                    &self.authority.pubkey(),
+                    did_rotation,
+                ));
+                instructions
+            }
+        };
+        let signature = self.submit_transaction(instructions)?;
         Ok(signature.to_string())
     }
 
@@ -359,6 +977,7 @@ impl Chain for SolanaChain {
     fn decommission_inst(
         &self,
         inception_digest: &Vec<u8>,
+        controllers: &[Privatekey],
         event_msg: &EventMessage<SaidEvent<Event>>,
     ) -> SolDidResult<ChainSignature> {
         // Validate we have a did
@@ -367,40 +986,105 @@ impl Chain for SolanaChain {
         if check_acc.is_err() {
             return Err(SolDidError::DIDAccountNotExists(pda_key.to_string()));
         }
-        // Now we want to create two (2) instructions:
-        // 1. The ed25519 signature verification on the serialized message
-        let verify_instruction = ed25519_instruction::new_ed25519_instruction(
-            &ed25519_dalek::Keypair::from_bytes(&self.signer.to_bytes())?,
-            &event_msg.serialize()?,
-        );
+        let did = SDMDid::unpack(&check_acc?.data)?;
+        // Now we want to create the instructions:
+        // 1. The signature verifications on the serialized message
+        let message = SolanaChain::event_message(did.keytype(), event_msg)?;
+        let mut instructions =
+            self.authorize_instructions(&self.fee_payer, &did, controllers, &message)?;
         // 2. The decommission instruction of the DID for program
         let did_decomm = DIDDecommission {
-            keytype: SMDKeyType::PASTA,
+            keytype: did.keytype(),
             prefix: SolanaChain::prefix_bytes(event_msg),
+            prior_digest: SolanaChain::prior_digest_bytes(event_msg),
+            digest: SolanaChain::digest_bytes(event_msg),
+            sn: event_msg.event.get_sn(),
         };
-        // Accounts to pass to instruction
-        let accounts = &[
-            AccountMeta::new(self.signer.pubkey(), true),
-            AccountMeta::new(pda_key, false),
-        ];
-        let txn = self.submit_transaction(
-            [
-                verify_instruction,
-                Instruction::new_with_borsh(
-                    self.program_id,
-                    &SDMInstruction::SDMDecommission(did_decomm),
-                    accounts.to_vec(),
-                ),
-            ]
-            .to_vec(),
-        );
-        assert!(txn.is_ok());
-        let signature = txn.unwrap();
+        instructions.push(instruction::decommission(
+            &self.program_id,
+            &self.fee_payer.pubkey(),
+            &self.authority.pubkey(),
+            did_decomm,
+        ));
+        let signature = self.submit_transaction(instructions)?;
+        Ok(signature.to_string())
+    }
+
+    /// Revoke
+    fn revoke_inst(
+        &self,
+        inception_digest: &Vec<u8>,
+        reason: SDMRevocationReason,
+    ) -> SolDidResult<ChainSignature> {
+        // Validate we have a did
+        let (pda_key, _bump) = Pubkey::find_program_address(&[inception_digest], &self.program_id);
+        let check_acc = self.rpc_client.get_account(&pda_key);
+        if check_acc.is_err() {
+            return Err(SolDidError::DIDAccountNotExists(pda_key.to_string()));
+        }
+        let did = SDMDid::unpack(&check_acc?.data)?;
+        let did_revoke = DIDRevoke {
+            keytype: did.keytype(),
+            prefix: *did.prefix(),
+            reason,
+        };
+        let signature = self.submit_transaction(vec![instruction::revoke(
+            &self.program_id,
+            &self.fee_payer.pubkey(),
+            &self.authority.pubkey(),
+            did_revoke,
+        )])?;
+        Ok(signature.to_string())
+    }
+
+    /// Interaction
+    fn interaction_inst(
+        &self,
+        inception_digest: &Vec<u8>,
+        controllers: &[Privatekey],
+        event: &InteractionEvent,
+    ) -> SolDidResult<ChainSignature> {
+        // Validate we have a did
+        let (pda_key, _bump) = Pubkey::find_program_address(&[inception_digest], &self.program_id);
+        let check_acc = self.rpc_client.get_account(&pda_key);
+        if check_acc.is_err() {
+            return Err(SolDidError::DIDAccountNotExists(pda_key.to_string()));
+        }
+        let did = SDMDid::unpack(&check_acc?.data)?;
+        // Now we want to create the instructions:
+        // 1. The signature verifications on the serialized message
+        let mut instructions =
+            self.authorize_instructions(&self.fee_payer, &did, controllers, &event.serialize()?)?;
+        // 2. The interaction instruction of the DID for program
+        let to_bytes = |digest: &[u8]| {
+            let mut bytes = [0u8; 32];
+            bytes.copy_from_slice(digest);
+            bytes
+        };
+        let did_ixn = DIDInteraction {
+            keytype: did.keytype(),
+            prefix: to_bytes(&event.prefix()?.digest),
+            prior_digest: to_bytes(&event.prior_digest()?.digest),
+            digest: to_bytes(&event.digest()?.digest),
+            sn: event.sn(),
+            seals: event
+                .seal_digests()
+                .iter()
+                .map(|seal| to_bytes(&seal.digest))
+                .collect(),
+        };
+        instructions.push(instruction::interaction(
+            &self.program_id,
+            &self.fee_payer.pubkey(),
+            &self.authority.pubkey(),
+            did_ixn,
+        ));
+        let signature = self.submit_transaction(instructions)?;
         Ok(signature.to_string())
     }
 
     fn inst_signer(&self) -> DidSigner {
-        self.signer.to_bytes().to_vec()
+        self.authority.to_bytes().to_vec()
     }
 
     fn url(&self) -> &String {
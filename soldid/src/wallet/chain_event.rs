@@ -21,27 +21,35 @@ pub enum ChainEventType {
     DelegatedRotation,
     Revoked,
     Decommissioned,
+    Interaction,
 }
 
 impl ChainEventType {
     pub fn can_rotate(prev: ChainEventType) -> bool {
         // Expand when we have more coverage
-        if let ChainEventType::Inception | ChainEventType::Rotation = prev {
-            true
-        } else {
-            false
-        }
+        matches!(
+            prev,
+            ChainEventType::Inception
+                | ChainEventType::Rotation
+                | ChainEventType::DelegatedInception
+                | ChainEventType::DelegatedRotation
+                | ChainEventType::Interaction
+        )
     }
 }
 
-impl From<EventTypeTag> for ChainEventType {
-    fn from(ett: EventTypeTag) -> Self {
+impl TryFrom<EventTypeTag> for ChainEventType {
+    type Error = SolDidError;
+
+    fn try_from(ett: EventTypeTag) -> SolDidResult<Self> {
         match ett {
-            EventTypeTag::Icp => ChainEventType::Inception,
-            EventTypeTag::Rot => ChainEventType::Rotation,
-            EventTypeTag::Ixn | EventTypeTag::Dip | EventTypeTag::Drt | EventTypeTag::Rct => {
-                todo!()
-            }
+            EventTypeTag::Icp => Ok(ChainEventType::Inception),
+            EventTypeTag::Rot => Ok(ChainEventType::Rotation),
+            EventTypeTag::Dip => Ok(ChainEventType::DelegatedInception),
+            EventTypeTag::Drt => Ok(ChainEventType::DelegatedRotation),
+            EventTypeTag::Ixn => Ok(ChainEventType::Interaction),
+            // Receipts are not key events of the DID
+            EventTypeTag::Rct => Err(SolDidError::NotKeyEvent(format!("{:?}", ett))),
         }
     }
 }
@@ -56,7 +64,8 @@ pub enum KeyBlock {
 }
 
 /// ChainEven tracks/associates key changes for DID to a confirmed signature chain event
-/// Signatures are base58 representation
+/// Signatures are base58 representation. The next keys are held with the
+/// signing threshold committed to for them
 #[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Default)]
 pub struct ChainEvent {
     pub event_type: ChainEventType,
@@ -66,6 +75,7 @@ pub struct ChainEvent {
     pub km_digest: String,
     pub km_keytype: KeyType,
     pub keysets: HashMap<KeyBlock, Vec<Key>>,
+    pub km_next_threshold: u64,
 }
 
 impl fmt::Display for ChainEvent {
@@ -132,12 +142,41 @@ impl ChainEvent {
     }
 }
 
-impl From<&EventMessage<SaidEvent<Event>>> for ChainEvent {
-    fn from(event: &EventMessage<SaidEvent<Event>>) -> Self {
+impl TryFrom<&EventMessage<SaidEvent<Event>>> for ChainEvent {
+    type Error = SolDidError;
+
+    fn try_from(event: &EventMessage<SaidEvent<Event>>) -> SolDidResult<Self> {
         let mut ce = ChainEvent::default();
         ce.km_sn = event.event.get_sn();
         ce.km_digest = event.get_digest().to_str();
-        ce.event_type = ChainEventType::from(event.event.get_type());
-        ce
+        ce.event_type = ChainEventType::try_from(event.event.get_type())?;
+        Ok(ce)
+    }
+}
+
+#[cfg(test)]
+mod chain_event_tests {
+    use super::*;
+
+    #[test]
+    fn test_key_event_types_pass() {
+        for (tag, expected) in [
+            (EventTypeTag::Icp, "Inception"),
+            (EventTypeTag::Rot, "Rotation"),
+            (EventTypeTag::Dip, "DelegatedInception"),
+            (EventTypeTag::Drt, "DelegatedRotation"),
+            (EventTypeTag::Ixn, "Interaction"),
+        ] {
+            let event_type = ChainEventType::try_from(tag).unwrap();
+            assert_eq!(format!("{:?}", event_type), expected);
+        }
+    }
+
+    #[test]
+    fn test_receipt_event_type_fail() {
+        assert!(matches!(
+            ChainEventType::try_from(EventTypeTag::Rct),
+            Err(SolDidError::NotKeyEvent(_))
+        ));
     }
 }
@@ -4,23 +4,69 @@ use borsh::{BorshDeserialize, BorshSerialize};
 
 use super::{
     chain_event::{ChainEvent, ChainEventType, KeyBlock},
-    wallet_enums::{KeyState, KeyType},
+    interaction::InteractionEvent,
+    versions::{KeysV1, KeysVersion, CURRENT_KEYS_VERSION, KEYS_FILE_MAGIC},
+    wallet_enums::{KeyState, KeyType, WalletKeySet},
     KEYS_CONFIGURATION,
 };
 use crate::{
     chain_trait::Chain,
     errors::{SolDidError, SolDidResult},
+    skey_wrap::secp256k1_event,
 };
 use chrono;
 use hbkr_rs::{
+    delegated_inception,
+    event::Event,
+    event_message::EventMessage,
+    event_msg_builder::EventMsgBuilder,
+    identifier_prefix::IdentifierPrefix,
     inception,
-    key_manage::{KeySet, PrivKey, Privatekey, Publickey},
-    rotation,
+    key_manage::{key_vec_to_prefix_vec, PrivKey, Privatekey, Publickey},
     said::SelfAddressingPrefix,
-    Prefix,
+    said_event::SaidEvent,
+    threshold::SignatureThreshold,
+    EventTypeTag, Prefix,
 };
+use solana_did_method::state::{SDMRevocationReason, ALL_RELATIONSHIPS};
 use std::{fs, io::Write, path::PathBuf, str::FromStr};
 
+/// Build the rotation of the prefix following its last event. The keys
+/// of the key set sign to the threshold committed to by the last event
+/// and the rotation commits to the next keys and next threshold.
+/// Rotations to secp256k1 keys are rendered with their derivation code
+fn rotation_event(
+    event_type: EventTypeTag,
+    prefix: &str,
+    prior_digest: &str,
+    sn: u64,
+    key_set: &dyn WalletKeySet,
+    threshold: u64,
+    next_threshold: u64,
+) -> SolDidResult<EventMessage<SaidEvent<Event>>> {
+    let event = EventMsgBuilder::new(event_type)
+        .with_prefix(&IdentifierPrefix::SelfAddressing(
+            SelfAddressingPrefix::from_str(prefix)?,
+        ))
+        .with_sn(sn)
+        .with_previous_event(&SelfAddressingPrefix::from_str(prior_digest)?)
+        .with_keys(key_vec_to_prefix_vec(
+            &key_set.current_public_keys(),
+            key_set.key_type(),
+        ))
+        .with_threshold(&SignatureThreshold::simple(threshold))
+        .with_next_keys(key_vec_to_prefix_vec(
+            &key_set.next_public_keys(),
+            key_set.key_type(),
+        ))
+        .with_next_threshold(&SignatureThreshold::simple(next_threshold))
+        .build()?;
+    match key_set.wallet_key_type() {
+        KeyType::SECP256K1 => secp256k1_event(event, key_set, next_threshold),
+        _ => Ok(event),
+    }
+}
+
 /// Keys define a named collection of public and private keys
 /// represented as strings
 #[derive(BorshDeserialize, BorshSerialize, Debug, Default)]
@@ -29,6 +75,7 @@ pub struct Keys {
     dirty: bool,
     name: String,
     prefix: String,
+    delegator: Option<String>,
     account: Publickey,
     threshold: i8,
     chain_events: Vec<ChainEvent>,
@@ -48,6 +95,11 @@ impl Keys {
         &self.prefix
     }
 
+    /// Get the prefix of the delegator of delegated keys
+    pub fn delegator(&self) -> Option<&String> {
+        self.delegator.as_ref()
+    }
+
     /// Get the key descriptive name
     pub fn name(&self) -> &String {
         &self.name
@@ -58,45 +110,106 @@ impl Keys {
         &self.account
     }
 
-    /// Get the threshold value
+    /// Get the signing threshold of the current keys
     pub fn threshold(&self) -> i8 {
         self.threshold
     }
+
+    /// Get the signing threshold committed to for the next keys
+    pub fn next_threshold(&self) -> u64 {
+        self.chain_events
+            .last()
+            .map(|ce| ce.km_next_threshold)
+            .unwrap_or_default()
+    }
+
+    /// Get the key type of the keys
+    pub fn key_type(&self) -> KeyType {
+        self.chain_events
+            .last()
+            .map(|ce| ce.km_keytype)
+            .unwrap_or_default()
+    }
+
+    /// Get the private keys of the current keys, the controllers of
+    /// the DID
+    pub fn current_private_keys(&self) -> SolDidResult<Vec<Privatekey>> {
+        match self.chain_events.last() {
+            Some(ce) => ce.get_keys_as_private_for(KeyBlock::CURRENT),
+            None => Err(SolDidError::KeySetIncoherence),
+        }
+    }
+    /// Verify there is a relationship bitmask for each key
+    fn verify_relationships(relationships: &[u8], key_count: usize) -> SolDidResult<()> {
+        if relationships.len() == key_count {
+            Ok(())
+        } else {
+            Err(SolDidError::RelationshipsMismatch(key_count))
+        }
+    }
+
     /// Accepts a native keyset this has been incepted
     /// distributes current (Incepted) and next (NextRotation) keys
     /// and stores the chain event initiating this function call
+    /// Current keys hold every verification relationship unless
+    /// relationships are provided for each. Keys with delegator keys
+    /// are incepted by a delegated inception approved by the current
+    /// keys of the delegator
     pub fn incept_keys(
         name: &String,
         chain: Option<&dyn Chain>,
-        key_set: &dyn KeySet,
+        key_set: &dyn WalletKeySet,
         threshold: i8,
+        relationships: Option<Vec<u8>>,
+        delegator: Option<&Keys>,
     ) -> SolDidResult<(Self, String, String, Vec<u8>)> {
+        let set_type = key_set.wallet_key_type();
+        let key_count = key_set.current_private_keys().len();
+        let relationships = relationships.unwrap_or_else(|| vec![ALL_RELATIONSHIPS; key_count]);
+        Keys::verify_relationships(&relationships, key_count)?;
         // Create an inception event
         let utc = chrono::Utc::now();
 
-        let icp_event = inception(key_set, threshold as u64)?;
+        let icp_event = match delegator {
+            Some(delegator) => delegated_inception(
+                key_set,
+                threshold as u64,
+                &IdentifierPrefix::from_str(delegator.prefix())?,
+            )?,
+            None => inception(key_set, threshold as u64)?,
+        };
+        let icp_event = match set_type {
+            KeyType::SECP256K1 => secp256k1_event(icp_event, key_set, threshold as u64)?,
+            _ => icp_event,
+        };
         let prefix = icp_event.event.get_prefix().to_str();
         // Optionally store on chain
         let (signature, account) = match chain {
-            Some(chain) => chain.inception_inst(key_set, &icp_event)?,
+            Some(chain) => {
+                let approvers = match delegator {
+                    Some(delegator) => delegator.current_private_keys()?,
+                    None => Vec::new(),
+                };
+                chain.inception_inst(key_set, &relationships, &approvers, &icp_event)?
+            }
             None => ("sol_did_signature".to_string(), Publickey::default()),
         };
 
-        // Covert Type
-        let set_type = KeyType::from(key_set.key_type());
         // Setup the chain event
-        let mut chain_event = ChainEvent::from(&icp_event);
+        let mut chain_event = ChainEvent::try_from(&icp_event)?;
 
         chain_event.km_keytype = set_type;
         chain_event.did_signature = signature.clone();
         chain_event.time_stamp = utc.timestamp_millis();
+        chain_event.km_next_threshold = threshold as u64;
 
         // Convert keyset current keys and next keys to Key
-        let keysets_current = Keys::to_keys_from_private(
+        let mut keysets_current = Keys::to_keys_from_private(
             KeyState::Incepted,
             set_type,
             &key_set.current_private_keys(),
         );
+        Keys::assign_relationships(&mut keysets_current, &relationships);
         let keysets_next = Keys::to_keys_from_private(
             KeyState::NextRotation,
             set_type,
@@ -116,6 +229,7 @@ impl Keys {
                 dirty: true,
                 name: name.to_string(),
                 prefix: prefix.clone(),
+                delegator: delegator.map(|d| d.prefix().clone()),
                 account,
                 threshold,
                 chain_events: chain_vec,
@@ -134,14 +248,26 @@ impl Keys {
             .collect::<Vec<Key>>()
     }
 
+    /// Set the verification relationships of each key by position
+    fn assign_relationships(keys: &mut [Key], relationships: &[u8]) {
+        for (key, relationship) in keys.iter_mut().zip(relationships) {
+            key.relationships = *relationship;
+        }
+    }
+
     /// rotate_keys creates a new rotation event and
     /// optionally commits to blockchain and
     /// then syncs current state and updates the chainevents
+    /// The relationships of the current keys are preserved by position
+    /// unless reassigned. Delegated keys are rotated with the approval
+    /// of the approvers, the current keys of the delegator
     pub fn rotate_keys(
         &mut self,
-        barren_ks: &mut dyn KeySet,
+        barren_ks: &mut dyn WalletKeySet,
         new_next_set: Option<Vec<Privatekey>>,
         threshold: Option<u64>,
+        relationships: Option<Vec<u8>>,
+        approvers: &[Privatekey],
         chain: Option<&dyn Chain>,
     ) -> SolDidResult<(String, Vec<u8>)> {
         // Validate state
@@ -157,51 +283,80 @@ impl Keys {
                 if !ChainEventType::can_rotate(last_event.event_type) {
                     return Err(SolDidError::RotationIncompatible);
                 }
+                // Keys rotate within the key type they were stored with
+                let keytype = last_event.km_keytype;
+                if barren_ks.wallet_key_type() != keytype {
+                    return Err(SolDidError::KeySetIncoherence);
+                }
                 // Re-hydrate the keystate
                 let last_current = last_event.get_keys_as_strings_for(KeyBlock::CURRENT)?;
                 let last_next = last_event.get_keys_as_strings_for(KeyBlock::NEXT)?;
+                let controllers = last_event.get_keys_as_private_for(KeyBlock::CURRENT)?;
                 barren_ks.from(last_current.clone(), last_next);
                 // Default rotation of keys should create equivalent count of keysets for next
                 let (ncurr, nnext) = barren_ks.rotate(new_next_clone);
+                let assigned = match &relationships {
+                    Some(r) => r.clone(),
+                    None => last_event
+                        .get_keys_for(KeyBlock::CURRENT)?
+                        .iter()
+                        .map(|k| k.relationships())
+                        .collect(),
+                };
+                Keys::verify_relationships(&assigned, ncurr.len())?;
                 // Rotate event
                 let utc = chrono::Utc::now();
-                let rot_event = rotation(
+                // Delegated keys rotate with delegated rotations, the
+                // rotated keys sign to the threshold committed to and
+                // commit to the new threshold
+                let event_type = match self.delegator {
+                    Some(_) => EventTypeTag::Drt,
+                    None => EventTypeTag::Rot,
+                };
+                let committed = last_event.km_next_threshold;
+                let next_threshold = threshold.unwrap_or(committed);
+                let rot_event = rotation_event(
+                    event_type,
                     &self.prefix,
                     &last_event.km_digest,
                     last_event.km_sn + 1,
                     barren_ks,
-                    match threshold {
-                        Some(t) => {
-                            self.threshold = t as i8;
-                            t
-                        }
-                        None => self.threshold as u64,
-                    },
+                    committed,
+                    next_threshold,
                 )?;
-                // Optionally store on chain
+                // Optionally store on chain, the rotated keyset carries
+                // the pre-rotation commitment to its next keys
                 let signature = match chain {
                     Some(chain) => {
                         let incp_ce = self.chain_events.first().unwrap();
                         let incp_digest = SelfAddressingPrefix::from_str(&incp_ce.km_digest)?;
-                        chain.rotation_inst(&incp_digest.digest, barren_ks, &rot_event)?
+                        chain.rotation_inst(
+                            &incp_digest.digest,
+                            barren_ks,
+                            &controllers,
+                            relationships.as_deref(),
+                            approvers,
+                            &rot_event,
+                        )?
                     }
                     None => "sol_did_signature".to_string(),
                 };
-                // Create the event keysets
-                let keytype = KeyType::from(barren_ks.key_type());
+                // The committed threshold is promoted with the keys it
+                // was committed for once the rotation is stored
+                self.threshold = committed as i8;
                 // Create the chain event
-                let mut chain_event = ChainEvent::from(&rot_event);
+                let mut chain_event = ChainEvent::try_from(&rot_event)?;
                 chain_event.km_keytype = keytype;
                 chain_event.did_signature = signature.clone();
                 chain_event.time_stamp = utc.timestamp_millis();
+                chain_event.km_next_threshold = next_threshold;
                 // Build the key state map
-                chain_event.keysets.insert(
-                    KeyBlock::CURRENT,
-                    ncurr
-                        .iter()
-                        .map(|k| Key::new(KeyState::Rotated, keytype, &k.as_base58_string()))
-                        .collect::<Vec<Key>>(),
-                );
+                let mut current = ncurr
+                    .iter()
+                    .map(|k| Key::new(KeyState::Rotated, keytype, &k.as_base58_string()))
+                    .collect::<Vec<Key>>();
+                Keys::assign_relationships(&mut current, &assigned);
+                chain_event.keysets.insert(KeyBlock::CURRENT, current);
                 chain_event.keysets.insert(
                     KeyBlock::NEXT,
                     nnext
@@ -226,7 +381,7 @@ impl Keys {
     /// Decommission this key set
     pub fn decommission_keys(
         &mut self,
-        barren_ks: &mut dyn KeySet,
+        barren_ks: &mut dyn WalletKeySet,
         chain: Option<&dyn Chain>,
     ) -> SolDidResult<(String, Vec<u8>)> {
         if self.chain_events.len() == 0 {
@@ -236,26 +391,32 @@ impl Keys {
             if !ChainEventType::can_rotate(last_event.event_type) {
                 return Err(SolDidError::RotationIncompatible);
             }
-            // Rotate event with empty keyset
+            let keytype = last_event.km_keytype;
+            if barren_ks.wallet_key_type() != keytype {
+                return Err(SolDidError::KeySetIncoherence);
+            }
+            // Rotate event with empty keyset and no threshold
             // TODO: Check that barren is just that
             let utc = chrono::Utc::now();
-            let rot_event = rotation(
+            let rot_event = rotation_event(
+                EventTypeTag::Rot,
                 &self.prefix,
                 &last_event.km_digest,
                 last_event.km_sn + 1,
                 barren_ks,
                 0,
+                0,
             )?;
             // Optionally store on chain
             let signature = match chain {
                 Some(chain) => {
                     let incp_ce = self.chain_events.first().unwrap();
                     let incp_digest = SelfAddressingPrefix::from_str(&incp_ce.km_digest)?;
-                    chain.decommission_inst(&incp_digest.digest, &rot_event)?
+                    let controllers = last_event.get_keys_as_private_for(KeyBlock::CURRENT)?;
+                    chain.decommission_inst(&incp_digest.digest, &controllers, &rot_event)?
                 }
                 None => "sol_did_signature".to_string(),
             };
-            let keytype = KeyType::from(barren_ks.key_type());
             let last_current = last_event.get_keys_as_strings_for(KeyBlock::CURRENT)?;
             let last_next = last_event.get_keys_as_strings_for(KeyBlock::NEXT)?;
 
@@ -271,7 +432,7 @@ impl Keys {
             );
 
             // Set decommissioned chain event
-            let mut chain_event = ChainEvent::from(&rot_event);
+            let mut chain_event = ChainEvent::try_from(&rot_event)?;
             chain_event.km_keytype = keytype;
             chain_event.did_signature = signature.clone();
             chain_event.time_stamp = utc.timestamp_millis();
@@ -290,12 +451,117 @@ impl Keys {
         }
     }
 
+    /// Revoke this key set, the chain records the reason of revocation
+    /// without a key event so the key event sequence is unchanged
+    pub fn revoke_keys(
+        &mut self,
+        reason: SDMRevocationReason,
+        chain: Option<&dyn Chain>,
+    ) -> SolDidResult<String> {
+        if self.chain_events.is_empty() {
+            Err(SolDidError::RotationIncoherence)
+        } else {
+            let last_event = self.chain_events.last().unwrap();
+            if !ChainEventType::can_rotate(last_event.event_type) {
+                return Err(SolDidError::RotationIncompatible);
+            }
+            let utc = chrono::Utc::now();
+            // Optionally store on chain
+            let signature = match chain {
+                Some(chain) => {
+                    let incp_ce = self.chain_events.first().unwrap();
+                    let incp_digest = SelfAddressingPrefix::from_str(&incp_ce.km_digest)?;
+                    chain.revoke_inst(&incp_digest.digest, reason)?
+                }
+                None => "sol_did_signature".to_string(),
+            };
+            let keytype = last_event.km_keytype;
+            let last_current = last_event.get_keys_as_strings_for(KeyBlock::CURRENT)?;
+            let last_next = last_event.get_keys_as_strings_for(KeyBlock::NEXT)?;
+
+            let mut event_past = last_current
+                .iter()
+                .map(|s| Key::new(KeyState::Revoked, keytype, s))
+                .collect::<Vec<Key>>();
+            event_past.extend(
+                last_next
+                    .iter()
+                    .map(|s| Key::new(KeyState::Revoked, keytype, s))
+                    .collect::<Vec<Key>>(),
+            );
+
+            // Set revoked chain event on the last key event
+            let mut chain_event = last_event.clone();
+            chain_event.did_signature = signature.clone();
+            chain_event.time_stamp = utc.timestamp_millis();
+            chain_event.event_type = ChainEventType::Revoked;
+            // Capture key states
+            chain_event.keysets.insert(KeyBlock::PAST, event_past);
+            chain_event
+                .keysets
+                .insert(KeyBlock::CURRENT, Vec::<Key>::new());
+            chain_event
+                .keysets
+                .insert(KeyBlock::NEXT, Vec::<Key>::new());
+            self.chain_events.push(chain_event);
+            self.dirty = true;
+            Ok(signature)
+        }
+    }
+
+    /// Anchor the seal digests with an interaction event, the keys are
+    /// unchanged and remain able to rotate
+    pub fn interact(
+        &mut self,
+        seals: &[SelfAddressingPrefix],
+        chain: Option<&dyn Chain>,
+    ) -> SolDidResult<(String, Vec<u8>)> {
+        if self.chain_events.is_empty() {
+            Err(SolDidError::RotationIncoherence)
+        } else if seals.is_empty() {
+            Err(SolDidError::InteractionWithoutSeals)
+        } else {
+            let last_event = self.chain_events.last().unwrap();
+            if !ChainEventType::can_rotate(last_event.event_type) {
+                return Err(SolDidError::RotationIncompatible);
+            }
+            let utc = chrono::Utc::now();
+            let ixn_event = InteractionEvent::new(
+                &self.prefix,
+                &last_event.km_digest,
+                last_event.km_sn + 1,
+                seals,
+            )?;
+            // Optionally store on chain
+            let signature = match chain {
+                Some(chain) => {
+                    let incp_ce = self.chain_events.first().unwrap();
+                    let incp_digest = SelfAddressingPrefix::from_str(&incp_ce.km_digest)?;
+                    let controllers = last_event.get_keys_as_private_for(KeyBlock::CURRENT)?;
+                    chain.interaction_inst(&incp_digest.digest, &controllers, &ixn_event)?
+                }
+                None => "sol_did_signature".to_string(),
+            };
+            // The interaction keeps the key states of the last event
+            let digest = ixn_event.digest()?;
+            let mut chain_event = last_event.clone();
+            chain_event.event_type = ChainEventType::Interaction;
+            chain_event.km_sn = ixn_event.sn();
+            chain_event.km_digest = digest.to_str();
+            chain_event.did_signature = signature.clone();
+            chain_event.time_stamp = utc.timestamp_millis();
+            self.chain_events.push(chain_event);
+            self.dirty = true;
+            Ok((signature, digest.digest))
+        }
+    }
+
     /// Read keys for wallet from path
     pub fn load(loc: &mut PathBuf) -> SolDidResult<Keys> {
         loc.push(KEYS_CONFIGURATION);
         match loc.exists() {
             true => {
-                let mut keys = Keys::try_from_slice(&fs::read(loc.clone())?)?;
+                let mut keys = KeysVersion::unpack(&fs::read(loc.clone())?)?.upgrade();
                 keys.dirty = false;
                 Ok(keys)
             }
@@ -317,15 +583,11 @@ impl Keys {
             fs::create_dir(rpath.clone())?;
         }
         rpath.push(KEYS_CONFIGURATION);
-        let mut file = if !rpath.exists() {
-            fs::File::create(rpath)?
-        } else {
-            fs::File::options().write(true).open(rpath)?
-        };
-        if self.dirty {
-            // let mut file = fs::File::create(rpath)?;
-            let wser = self.try_to_vec()?;
-            file.write(&wser)?;
+        // Keys are written in the current version, upgrading older files
+        if self.dirty || !rpath.exists() {
+            let mut file = fs::File::create(rpath)?;
+            let wser = (KEYS_FILE_MAGIC, CURRENT_KEYS_VERSION, &*self).try_to_vec()?;
+            file.write_all(&wser)?;
             self.dirty = false;
         }
 
@@ -333,26 +595,54 @@ impl Keys {
     }
 }
 
+/// Keys were not delegated and the threshold of the keys was the one
+/// committed to for the next keys
+impl From<KeysV1> for Keys {
+    fn from(keys: KeysV1) -> Self {
+        let next_threshold = keys.threshold as u64;
+        Keys {
+            dirty: false,
+            name: keys.name,
+            prefix: keys.prefix,
+            delegator: None,
+            account: keys.account,
+            threshold: keys.threshold,
+            chain_events: keys
+                .chain_events
+                .into_iter()
+                .map(|ce| ce.upgrade(next_threshold))
+                .collect(),
+        }
+    }
+}
+
 /// Key represents a keypair by encoding the private
 /// key to a string. The keytype provider knows how
-/// to reconstruct into it's keypair type
+/// to reconstruct into it's keypair type. The relationships
+/// are the bitmask of the key's DID verification relationships
 #[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Hash, Eq, PartialEq, PartialOrd)]
 pub struct Key {
     key_state: KeyState,
     key_type: KeyType,
     key: String,
+    relationships: u8,
 }
 
 impl Key {
-    /// Create a new Key
+    /// Create a new Key holding every verification relationship
     pub fn new(key_state: KeyState, key_type: KeyType, key: &String) -> Key {
         Key {
             key_state,
             key_type,
             key: key.clone(),
+            relationships: ALL_RELATIONSHIPS,
         }
     }
     pub fn key(&self) -> String {
         self.key.clone()
     }
+    /// Get the verification relationships bitmask
+    pub fn relationships(&self) -> u8 {
+        self.relationships
+    }
 }
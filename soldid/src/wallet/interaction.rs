@@ -0,0 +1,125 @@
+//! Interaction events anchoring seals to a DID
+//!
+//! hbkr builds establishment events only, interaction events are built
+//! here following the same self-addressing derivation of the digest
+
+use std::str::FromStr;
+
+use hbkr_rs::{
+    dummy_event::dummy_prefix,
+    said::{SelfAddressing, SelfAddressingPrefix},
+    seal::{DigestSeal, Seal},
+    serialization_info::{SerializationFormats, SerializationInfo},
+    EventTypeTag, Prefix,
+};
+use serde::{Serialize, Serializer};
+
+use crate::errors::SolDidResult;
+
+/// Serialize the sequence number as compact hex
+fn compact_hex<S: Serializer>(sn: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:x}", sn))
+}
+
+/// Interaction event message, the digest seals anchor the digests of
+/// data such as credentials or documents without changing the keys
+#[derive(Serialize, Debug, Clone)]
+pub struct InteractionEvent {
+    #[serde(rename = "v")]
+    serialization_info: SerializationInfo,
+    #[serde(rename = "t")]
+    event_type: EventTypeTag,
+    #[serde(rename = "d")]
+    digest: String,
+    #[serde(rename = "i")]
+    prefix: String,
+    #[serde(rename = "s", serialize_with = "compact_hex")]
+    sn: u64,
+    #[serde(rename = "p")]
+    prior_digest: String,
+    #[serde(rename = "a")]
+    seals: Vec<Seal>,
+}
+
+impl InteractionEvent {
+    /// Build the interaction following the prior event of the prefix
+    pub fn new(
+        prefix: &str,
+        prior_digest: &str,
+        sn: u64,
+        seals: &[SelfAddressingPrefix],
+    ) -> SolDidResult<Self> {
+        let derivation = SelfAddressing::Blake3_256;
+        let mut event = Self {
+            serialization_info: SerializationInfo::new(SerializationFormats::JSON, 0),
+            event_type: EventTypeTag::Ixn,
+            digest: dummy_prefix(&derivation),
+            prefix: prefix.to_string(),
+            sn,
+            prior_digest: prior_digest.to_string(),
+            seals: seals
+                .iter()
+                .map(|dig| Seal::Digest(DigestSeal { dig: dig.clone() }))
+                .collect(),
+        };
+        // Size the message then digest it with the dummy digest in place
+        event.serialization_info =
+            SerializationInfo::new(SerializationFormats::JSON, event.serialize()?.len());
+        event.digest = derivation.derive(&event.serialize()?).to_str();
+        Ok(event)
+    }
+
+    /// Serialized event message
+    pub fn serialize(&self) -> SolDidResult<Vec<u8>> {
+        Ok(SerializationFormats::JSON.encode(self)?)
+    }
+
+    pub fn prefix(&self) -> SolDidResult<SelfAddressingPrefix> {
+        Ok(SelfAddressingPrefix::from_str(&self.prefix)?)
+    }
+
+    pub fn sn(&self) -> u64 {
+        self.sn
+    }
+
+    pub fn digest(&self) -> SolDidResult<SelfAddressingPrefix> {
+        Ok(SelfAddressingPrefix::from_str(&self.digest)?)
+    }
+
+    pub fn prior_digest(&self) -> SolDidResult<SelfAddressingPrefix> {
+        Ok(SelfAddressingPrefix::from_str(&self.prior_digest)?)
+    }
+
+    /// Digests anchored by the seals
+    pub fn seal_digests(&self) -> Vec<&SelfAddressingPrefix> {
+        self.seals
+            .iter()
+            .filter_map(|seal| match seal {
+                Seal::Digest(seal) => Some(&seal.dig),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod interaction_tests {
+    use super::*;
+
+    #[test]
+    fn test_interaction_digest_pass() -> SolDidResult<()> {
+        let derivation = SelfAddressing::Blake3_256;
+        let prefix = derivation.derive(b"prefix").to_str();
+        let seal = derivation.derive(b"document");
+        let event = InteractionEvent::new(&prefix, &prefix, 1, &[seal.clone()])?;
+        let message = String::from_utf8(event.serialize()?).unwrap();
+        assert!(message.contains(r#""t":"ixn""#));
+        assert!(message.contains(r#""s":"1""#));
+        assert!(message.contains(&format!(r#""a":[{{"d":"{}"}}]"#, seal.to_str())));
+        assert_eq!(event.seal_digests(), vec![&seal]);
+        // The digest binds the message with the dummy digest in place
+        let dummy = message.replace(&event.digest()?.to_str(), &dummy_prefix(&derivation));
+        assert!(event.digest()?.verify_binding(dummy.as_bytes()));
+        Ok(())
+    }
+}
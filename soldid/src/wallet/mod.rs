@@ -2,6 +2,8 @@
 
 pub mod chain_event;
 pub mod generic_keys;
+pub mod interaction;
+pub mod versions;
 pub mod wallet_enums;
 
 use crate::{
@@ -10,10 +12,10 @@ use crate::{
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 
+use solana_did_method::state::SDMRevocationReason;
+
 use hbkr_rs::{
-    event::Event,
-    event_message::EventMessage,
-    key_manage::{KeySet, Privatekey},
+    event::Event, event_message::EventMessage, key_manage::Privatekey, said::SelfAddressingPrefix,
     said_event::SaidEvent,
 };
 
@@ -24,7 +26,10 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use self::generic_keys::Keys;
+use self::{
+    generic_keys::Keys,
+    wallet_enums::{KeyType, WalletKeySet},
+};
 
 static DEFAULT_WALLET_PATH: &str = "/.solwall";
 static WALLET_CONFIGURATION: &str = "wallet.bor";
@@ -90,17 +95,52 @@ impl Wallet {
     }
 
     /// Creates a new DID with keyset
+    /// Optional verification relationships for each current key, by
+    /// default each key holds every relationship
     pub fn new_did(
         &mut self,
         name: &String,
-        keyset: &dyn KeySet,
+        keyset: &dyn WalletKeySet,
+        threshold: i8,
+        relationships: Option<Vec<u8>>,
+        chain: Option<&dyn Chain>,
+    ) -> SolDidResult<(String, String, Vec<u8>)> {
+        if self.key_name_exists(name) {
+            return Err(SolDidError::KeysNameExistError(name.to_string()));
+        }
+        let (keys, signature, prefix, digest) =
+            Keys::incept_keys(name, chain, keyset, threshold, relationships, None)?;
+        self.add_keys(keys)?;
+        Ok((signature, prefix, digest))
+    }
+    /// Creates a new DID with keyset delegated by the DID of the
+    /// keyset named parent. Pasta keys sign through Pasta sessions which
+    /// delegated events can not be approved with, so neither keyset may
+    /// be Pasta
+    pub fn new_delegated_did(
+        &mut self,
+        name: &String,
+        parent: &String,
+        keyset: &dyn WalletKeySet,
         threshold: i8,
+        relationships: Option<Vec<u8>>,
         chain: Option<&dyn Chain>,
     ) -> SolDidResult<(String, String, Vec<u8>)> {
         if self.key_name_exists(name) {
             return Err(SolDidError::KeysNameExistError(name.to_string()));
         }
-        let (keys, signature, prefix, digest) = Keys::incept_keys(name, chain, keyset, threshold)?;
+        let delegator = self.keys_for_name(parent)?;
+        if keyset.wallet_key_type() == KeyType::PASTA || delegator.key_type() == KeyType::PASTA {
+            return Err(SolDidError::PastaDelegationError);
+        }
+        let (keys, signature, prefix, digest) = Keys::incept_keys(
+            name,
+            chain,
+            keyset,
+            threshold,
+            relationships,
+            Some(delegator),
+        )?;
         self.add_keys(keys)?;
         Ok((signature, prefix, digest))
     }
@@ -110,24 +150,34 @@ impl Wallet {
     ///     A barren keyset
     ///     Optional vector of private keys to use as the next rotation
     ///     Optional new threshold to set for keyset
+    ///     Optional verification relationships reassigned to each key
     ///     Optional chain to commit to
     /// Returns Transaction Signature and Rotation digest
     pub fn rotate_did_with_prefix(
         &mut self,
         keyprefix: String,
-        keyset: &mut dyn KeySet,
+        keyset: &mut dyn WalletKeySet,
         new_next_set: Option<Vec<Privatekey>>,
         threshold: Option<u64>,
+        relationships: Option<Vec<u8>>,
         chain: Option<&dyn Chain>,
     ) -> SolDidResult<(String, Vec<u8>)> {
         // Validate keyset is barren
         if !keyset.is_barren() {
             Err(SolDidError::KeySetIncoherence)
         } else {
+            let approvers = self.approvers(|k| k.prefix() == &keyprefix)?;
             // Get the prefix Keys
             match self.keys.iter_mut().find(|k| k.prefix() == &keyprefix) {
                 Some(k) => {
-                    let result = k.rotate_keys(keyset, new_next_set, threshold, chain);
+                    let result = k.rotate_keys(
+                        keyset,
+                        new_next_set,
+                        threshold,
+                        relationships,
+                        &approvers,
+                        chain,
+                    );
                     if result.is_ok() {
                         self.save()?;
                     }
@@ -143,24 +193,34 @@ impl Wallet {
     ///     A barren keyset
     ///     Optional vector of private keys to use as the next rotation
     ///     Optional new threshold to set for keyset
+    ///     Optional verification relationships reassigned to each key
     ///     Optional chain to commit to
     /// Returns Transaction Signature and Rotation digest
     pub fn rotate_did_with_name(
         &mut self,
         keyname: String,
-        keyset: &mut dyn KeySet,
+        keyset: &mut dyn WalletKeySet,
         new_next_set: Option<Vec<Privatekey>>,
         threshold: Option<u64>,
+        relationships: Option<Vec<u8>>,
         chain: Option<&dyn Chain>,
     ) -> SolDidResult<(String, Vec<u8>)> {
         // Validate keyset is barren
         if !keyset.is_barren() {
             Err(SolDidError::KeySetIncoherence)
         } else {
+            let approvers = self.approvers(|k| k.name() == &keyname)?;
             // Get the prefix Keys
             match self.keys.iter_mut().find(|k| k.name() == &keyname) {
                 Some(k) => {
-                    let result = k.rotate_keys(keyset, new_next_set, threshold, chain);
+                    let result = k.rotate_keys(
+                        keyset,
+                        new_next_set,
+                        threshold,
+                        relationships,
+                        &approvers,
+                        chain,
+                    );
                     if result.is_ok() {
                         self.save()?;
                     }
@@ -170,11 +230,27 @@ impl Wallet {
             }
         }
     }
+    /// Current keys of the delegator approving the rotations of the
+    /// matching delegated Keys, none for Keys not delegated
+    fn approvers<P>(&self, predicate: P) -> SolDidResult<Vec<Privatekey>>
+    where
+        P: Fn(&Keys) -> bool,
+    {
+        match self
+            .keys
+            .iter()
+            .find(|k| predicate(k))
+            .and_then(|k| k.delegator())
+        {
+            Some(delegator) => self.keys_for_prefix(delegator)?.current_private_keys(),
+            None => Ok(Vec::new()),
+        }
+    }
     /// Decommission a did by rotating in an empty vector of Privatekeys
     pub fn decommission_did_with_prefix(
         &mut self,
         keyprefix: String,
-        keyset: &mut dyn KeySet,
+        keyset: &mut dyn WalletKeySet,
         chain: Option<&dyn Chain>,
     ) -> SolDidResult<(String, Vec<u8>)> {
         if !keyset.is_barren() {
@@ -197,7 +273,7 @@ impl Wallet {
     pub fn decommission_did_with_name(
         &mut self,
         keyname: String,
-        keyset: &mut dyn KeySet,
+        keyset: &mut dyn WalletKeySet,
         chain: Option<&dyn Chain>,
     ) -> SolDidResult<(String, Vec<u8>)> {
         if !keyset.is_barren() {
@@ -216,6 +292,82 @@ impl Wallet {
         }
     }
 
+    /// Revoke a did after compromise, distinct from decommission
+    pub fn revoke_did_with_prefix(
+        &mut self,
+        keyprefix: String,
+        reason: SDMRevocationReason,
+        chain: Option<&dyn Chain>,
+    ) -> SolDidResult<String> {
+        match self.keys.iter_mut().find(|k| k.prefix() == &keyprefix) {
+            Some(k) => {
+                let result = k.revoke_keys(reason, chain);
+                if result.is_ok() {
+                    self.save()?;
+                }
+                result
+            }
+            None => Err(SolDidError::PrefixNotFound(keyprefix)),
+        }
+    }
+
+    /// Revoke a did after compromise, distinct from decommission
+    pub fn revoke_did_with_name(
+        &mut self,
+        keyname: String,
+        reason: SDMRevocationReason,
+        chain: Option<&dyn Chain>,
+    ) -> SolDidResult<String> {
+        match self.keys.iter_mut().find(|k| k.name() == &keyname) {
+            Some(k) => {
+                let result = k.revoke_keys(reason, chain);
+                if result.is_ok() {
+                    self.save()?;
+                }
+                result
+            }
+            None => Err(SolDidError::NameNotFound(keyname)),
+        }
+    }
+
+    /// Anchor the seal digests to a did with an interaction event
+    pub fn interact_did_with_prefix(
+        &mut self,
+        keyprefix: String,
+        seals: &[SelfAddressingPrefix],
+        chain: Option<&dyn Chain>,
+    ) -> SolDidResult<(String, Vec<u8>)> {
+        match self.keys.iter_mut().find(|k| k.prefix() == &keyprefix) {
+            Some(k) => {
+                let result = k.interact(seals, chain);
+                if result.is_ok() {
+                    self.save()?;
+                }
+                result
+            }
+            None => Err(SolDidError::PrefixNotFound(keyprefix)),
+        }
+    }
+
+    /// Anchor the seal digests to a did with an interaction event
+    pub fn interact_did_with_name(
+        &mut self,
+        keyname: String,
+        seals: &[SelfAddressingPrefix],
+        chain: Option<&dyn Chain>,
+    ) -> SolDidResult<(String, Vec<u8>)> {
+        match self.keys.iter_mut().find(|k| k.name() == &keyname) {
+            Some(k) => {
+                let result = k.interact(seals, chain);
+                if result.is_ok() {
+                    self.save()?;
+                }
+                result
+            }
+            None => Err(SolDidError::NameNotFound(keyname)),
+        }
+    }
+
     /// Return all keysets
     pub fn keys(&self) -> SolDidResult<&Vec<Keys>> {
         Ok(&self.keys)
@@ -331,15 +483,27 @@ pub fn to_json(title: &str, event: &EventMessage<SaidEvent<Event>>) {
 #[cfg(test)]
 mod wallet_tests {
 
-    use hbkr_rs::key_manage::{KeySet, Privatekey};
+    use hbkr_rs::{
+        key_manage::{KeySet, Privatekey},
+        said::{SelfAddressing, SelfAddressingPrefix},
+    };
 
-    use super::{load_wallet_from, Wallet};
+    use super::{generic_keys::Keys, load_wallet_from, Wallet};
     use crate::{
+        ekey_wrap::Ed25519KeySet,
         errors::{SolDidError, SolDidResult},
         pkey_wrap::PastaKeySet,
-        wallet::chain_event::KeyBlock,
+        skey_wrap::Secp256k1KeySet,
+        wallet::{
+            chain_event::{ChainEventType, KeyBlock},
+            versions::{ChainEventV1, KeyV1, KeysV1, KEYS_FILE_MAGIC},
+            wallet_enums::{KeyState, KeyType, WalletKeySet},
+            KEYS_CONFIGURATION,
+        },
     };
-    use std::{env, fs, path::Path};
+    use borsh::BorshSerialize;
+    use solana_did_method::state::{SDMRevocationReason, ALL_RELATIONSHIPS};
+    use std::{env, fs, path::Path, str::FromStr};
 
     /// Test wallet core path
     const TEST_WALLET_LOCATION: &str = "/.solwall_test";
@@ -391,7 +555,8 @@ mod wallet_tests {
         let threshold = 1i8;
         let kset1 = PastaKeySet::new_for(count);
         let keys_name = "Alice".to_string();
-        let (signature, prefix, digest) = wallet.new_did(&keys_name, &kset1, threshold, None)?;
+        let (signature, prefix, digest) =
+            wallet.new_did(&keys_name, &kset1, threshold, None, None)?;
         assert_eq!("sol_did_signature".to_string(), signature);
         assert!(!digest.is_empty());
         let k = wallet.keys_for_prefix(&prefix)?;
@@ -415,7 +580,8 @@ mod wallet_tests {
         let threshold = 1i8;
         let kset1 = PastaKeySet::new_for(count);
         let keys_name = "Alice".to_string();
-        let (_signature, prefix, _digest) = wallet.new_did(&keys_name, &kset1, threshold, None)?;
+        let (_signature, prefix, _digest) =
+            wallet.new_did(&keys_name, &kset1, threshold, None, None)?;
         let k = wallet.keys_for_prefix(&prefix)?;
         assert_eq!(prefix, *k.prefix());
         let k = wallet.keys_for_name(&keys_name)?;
@@ -445,7 +611,8 @@ mod wallet_tests {
         let threshold = 1i8;
         let kset1 = PastaKeySet::new_for(count);
         let keys_name = "Franks First".to_string();
-        let (_signature, _prefix, _digest) = wallet.new_did(&keys_name, &kset1, threshold, None)?;
+        let (_signature, _prefix, _digest) =
+            wallet.new_did(&keys_name, &kset1, threshold, None, None)?;
         let wallet = build_test_wallet()?;
         assert_eq!(wallet.prefixes.len(), 1);
         // Target prefix we want to rotation
@@ -454,7 +621,7 @@ mod wallet_tests {
         // Rotate
         let mut wallet = build_test_wallet()?;
         let mut barren_ks = PastaKeySet::new_empty();
-        let _ = wallet.rotate_did_with_name(keys_name, &mut barren_ks, None, None, None)?;
+        let _ = wallet.rotate_did_with_name(keys_name, &mut barren_ks, None, None, None, None)?;
         // Observe
         let rot_keys = wallet.keys.first().unwrap();
         let rot_prefix = rot_keys.prefix();
@@ -463,6 +630,107 @@ mod wallet_tests {
         Ok(())
     }
 
+    #[test]
+    /// Test inception and rotation of Ed25519 keys
+    fn test_rotation_ed25519_keys_pass() -> SolDidResult<()> {
+        let mut wallet = build_test_wallet()?;
+        assert!(wallet.prefixes.is_empty());
+        let kset1 = Ed25519KeySet::new_for(2);
+        let keys_name = "Franks First".to_string();
+        let (_signature, prefix, _digest) = wallet.new_did(&keys_name, &kset1, 1, None, None)?;
+        let keys = wallet.keys_for_prefix(&prefix)?;
+        assert_eq!(keys.key_type(), KeyType::ED25519);
+        assert_eq!(keys.current_private_keys()?, kset1.current_private_keys());
+        // Rotate with a barren keyset of the same key type
+        let mut barren_ks = Ed25519KeySet::new_empty();
+        let _ = wallet.rotate_did_with_name(keys_name, &mut barren_ks, None, None, None, None)?;
+        let keys = wallet.keys_for_prefix(&prefix)?;
+        assert_eq!(keys.key_type(), KeyType::ED25519);
+        assert_eq!(keys.current_private_keys()?, kset1.next_private_keys());
+        remove_test_wallet(wallet)?;
+        Ok(())
+    }
+
+    #[test]
+    /// Test a changed threshold is committed to for the next keys and
+    /// signs once they are rotated in
+    fn test_rotation_threshold_pass() -> SolDidResult<()> {
+        let mut wallet = build_test_wallet()?;
+        let kset1 = Ed25519KeySet::new_for(2);
+        let keys_name = "Franks First".to_string();
+        let (_signature, prefix, _digest) = wallet.new_did(&keys_name, &kset1, 1, None, None)?;
+        let keys = wallet.keys_for_prefix(&prefix)?;
+        assert_eq!((keys.threshold(), keys.next_threshold()), (1, 1));
+        // The rotated keys sign to the threshold committed at inception
+        let mut barren_ks = Ed25519KeySet::new_empty();
+        wallet.rotate_did_with_name(
+            keys_name.clone(),
+            &mut barren_ks,
+            None,
+            Some(2),
+            None,
+            None,
+        )?;
+        let keys = wallet.keys_for_prefix(&prefix)?;
+        assert_eq!((keys.threshold(), keys.next_threshold()), (1, 2));
+        // The committed threshold is kept by the wallet
+        let mut wallet = build_test_wallet()?;
+        let keys = wallet.keys_for_prefix(&prefix)?;
+        assert_eq!((keys.threshold(), keys.next_threshold()), (1, 2));
+        let mut barren_ks = Ed25519KeySet::new_empty();
+        wallet.rotate_did_with_name(keys_name, &mut barren_ks, None, None, None, None)?;
+        let keys = wallet.keys_for_prefix(&prefix)?;
+        assert_eq!((keys.threshold(), keys.next_threshold()), (2, 2));
+        remove_test_wallet(wallet)?;
+        Ok(())
+    }
+
+    #[test]
+    /// Test secp256k1 keys incept and rotate with events of the
+    /// secp256k1 code
+    fn test_secp256k1_keys_pass() -> SolDidResult<()> {
+        let mut wallet = build_test_wallet()?;
+        let kset1 = Secp256k1KeySet::new_for(2);
+        let keys_name = "Franks First".to_string();
+        let (_signature, prefix, digest) = wallet.new_did(&keys_name, &kset1, 1, None, None)?;
+        assert_eq!(
+            SelfAddressingPrefix::from_str(&prefix)?.digest,
+            digest.clone()
+        );
+        let keys = wallet.keys_for_prefix(&prefix)?;
+        assert_eq!(keys.key_type(), KeyType::SECP256K1);
+        // Rotate with a barren keyset of the same key type
+        let mut barren_ks = Secp256k1KeySet::new_empty();
+        let (_signature, rot_digest) = wallet.rotate_did_with_name(
+            keys_name.clone(),
+            &mut barren_ks,
+            None,
+            None,
+            None,
+            None,
+        )?;
+        assert_ne!(rot_digest, digest);
+        let keys = wallet.keys_for_prefix(&prefix)?;
+        assert_eq!(keys.key_type(), KeyType::SECP256K1);
+        assert_eq!(keys.current_private_keys()?, kset1.next_private_keys());
+        // The stored key type, not the keyset, is the key type of the keys
+        let result = wallet.rotate_did_with_name(
+            keys_name.clone(),
+            &mut Ed25519KeySet::new_empty(),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert!(matches!(result, Err(SolDidError::KeySetIncoherence)));
+        let mut barren_ks = Secp256k1KeySet::new_empty();
+        wallet.decommission_did_with_name(keys_name, &mut barren_ks, None)?;
+        let keys = wallet.keys_for_prefix(&prefix)?;
+        assert_eq!(keys.key_type(), KeyType::SECP256K1);
+        remove_test_wallet(wallet)?;
+        Ok(())
+    }
+
     #[test]
     /// Test rotation to different keys than default
     fn test_rotation_to_different_pasta_keys_pass() -> SolDidResult<()> {
@@ -472,7 +740,8 @@ mod wallet_tests {
         let threshold = 1i8;
         let kset1 = PastaKeySet::new_for(count);
         let keys_name = "Franks First".to_string();
-        let (_signature, _prefix, _digest) = wallet.new_did(&keys_name, &kset1, threshold, None)?;
+        let (_signature, _prefix, _digest) =
+            wallet.new_did(&keys_name, &kset1, threshold, None, None)?;
         let new_first = wallet.keys.first().unwrap().prefix().to_string();
         assert_eq!(wallet.keys_for_prefix(&new_first)?.chain_event_len(), 1);
         // Rotate
@@ -486,6 +755,7 @@ mod wallet_tests {
             Some(new_next_set.clone()),
             None,
             None,
+            None,
         )?;
         let chain_events = wallet.keys_for_prefix(&new_first)?.chain_events();
         assert_eq!(chain_events.len(), 2);
@@ -508,7 +778,8 @@ mod wallet_tests {
         let threshold = 1i8;
         let kset1 = PastaKeySet::new_for(count);
         let keys_name = "Franks First".to_string();
-        let (_signature, _prefix, _digest) = wallet.new_did(&keys_name, &kset1, threshold, None)?;
+        let (_signature, _prefix, _digest) =
+            wallet.new_did(&keys_name, &kset1, threshold, None, None)?;
         let new_first = wallet.keys.first().unwrap().prefix().to_string();
         assert_eq!(wallet.keys_for_prefix(&new_first)?.chain_event_len(), 1);
         // Rotate to empty
@@ -521,12 +792,146 @@ mod wallet_tests {
             Some(new_next_set.clone()),
             None,
             None,
+            None,
         );
         assert!(result.is_err());
         remove_test_wallet(wallet)?;
         Ok(())
     }
 
+    #[test]
+    /// Test delegated inception and rotation under a named parent
+    fn test_delegated_ed25519_keys_pass() -> SolDidResult<()> {
+        let mut wallet = build_test_wallet()?;
+        let parent_name = "Franks First".to_string();
+        let child_name = "Franks Delegate".to_string();
+        let (_signature, parent_prefix, _digest) =
+            wallet.new_did(&parent_name, &Ed25519KeySet::new_for(2), 1, None, None)?;
+        let (_signature, child_prefix, _digest) = wallet.new_delegated_did(
+            &child_name,
+            &parent_name,
+            &Ed25519KeySet::new_for(2),
+            1,
+            None,
+            None,
+        )?;
+        let child = wallet.keys_for_prefix(&child_prefix)?;
+        assert_eq!(child.delegator(), Some(&parent_prefix));
+        let mut barren_ks = Ed25519KeySet::new_empty();
+        wallet.rotate_did_with_name(child_name, &mut barren_ks, None, None, None, None)?;
+        let events = wallet.keys_for_prefix(&child_prefix)?.chain_events();
+        assert!(matches!(
+            events[0].event_type,
+            ChainEventType::DelegatedInception
+        ));
+        assert!(matches!(
+            events[1].event_type,
+            ChainEventType::DelegatedRotation
+        ));
+        // Unknown parent
+        let result = wallet.new_delegated_did(
+            &"Orphan".to_string(),
+            &"Nobody".to_string(),
+            &Ed25519KeySet::new_for(1),
+            1,
+            None,
+            None,
+        );
+        assert!(result.is_err());
+        remove_test_wallet(wallet)?;
+        Ok(())
+    }
+
+    #[test]
+    /// Test Pasta keys neither delegate nor are delegated
+    fn test_delegated_pasta_keys_fail() -> SolDidResult<()> {
+        let mut wallet = build_test_wallet()?;
+        let pasta_name = "Franks First".to_string();
+        let ed25519_name = "Franks Second".to_string();
+        wallet.new_did(&pasta_name, &PastaKeySet::new_for(2), 1, None, None)?;
+        wallet.new_did(&ed25519_name, &Ed25519KeySet::new_for(2), 1, None, None)?;
+        for (parent, keyset) in [
+            (&pasta_name, &PastaKeySet::new_for(2) as &dyn WalletKeySet),
+            (&pasta_name, &Ed25519KeySet::new_for(2)),
+            (&ed25519_name, &PastaKeySet::new_for(2)),
+        ] {
+            let result = wallet.new_delegated_did(
+                &"Franks Delegate".to_string(),
+                parent,
+                keyset,
+                1,
+                None,
+                None,
+            );
+            assert!(matches!(result, Err(SolDidError::PastaDelegationError)));
+        }
+        assert_eq!(wallet.prefixes.len(), 2);
+        remove_test_wallet(wallet)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_revoke_pass() -> SolDidResult<()> {
+        let mut wallet = build_test_wallet()?;
+        let kset1 = PastaKeySet::new_for(2);
+        let keys_name = "Franks First".to_string();
+        wallet.new_did(&keys_name, &kset1, 1, None, None)?;
+        let result = wallet.revoke_did_with_name(
+            keys_name.clone(),
+            SDMRevocationReason::KeyCompromise,
+            None,
+        );
+        assert!(result.is_ok());
+        let keys = wallet.keys_for_name(&keys_name)?;
+        assert_eq!(keys.chain_event_len(), 2);
+        assert!(matches!(
+            keys.chain_events().last().unwrap().event_type,
+            ChainEventType::Revoked
+        ));
+        // Revoked keys can not rotate or be revoked again
+        let mut barren_ks = PastaKeySet::new_empty();
+        let result =
+            wallet.rotate_did_with_name(keys_name.clone(), &mut barren_ks, None, None, None, None);
+        assert!(result.is_err());
+        let result = wallet.revoke_did_with_name(keys_name, SDMRevocationReason::Unspecified, None);
+        assert!(result.is_err());
+        remove_test_wallet(wallet)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_interaction_pass() -> SolDidResult<()> {
+        let mut wallet = build_test_wallet()?;
+        let kset1 = PastaKeySet::new_for(2);
+        let keys_name = "Franks First".to_string();
+        wallet.new_did(&keys_name, &kset1, 1, None, None)?;
+        // Interaction requires seals
+        let result = wallet.interact_did_with_name(keys_name.clone(), &[], None);
+        assert!(matches!(result, Err(SolDidError::InteractionWithoutSeals)));
+        let seal = SelfAddressing::Blake3_256.derive(b"document");
+        let (_signature, digest) =
+            wallet.interact_did_with_name(keys_name.clone(), &[seal], None)?;
+        let keys = wallet.keys_for_name(&keys_name)?;
+        assert_eq!(keys.chain_event_len(), 2);
+        let interaction = keys.chain_events().last().unwrap();
+        assert!(matches!(
+            interaction.event_type,
+            ChainEventType::Interaction
+        ));
+        assert_eq!(interaction.km_sn, 1);
+        assert_eq!(
+            SelfAddressingPrefix::from_str(&interaction.km_digest)?.digest,
+            digest
+        );
+        // Keys rotate following the interaction
+        let mut barren_ks = PastaKeySet::new_empty();
+        wallet.rotate_did_with_name(keys_name.clone(), &mut barren_ks, None, None, None, None)?;
+        let keys = wallet.keys_for_name(&keys_name)?;
+        assert_eq!(keys.chain_events().last().unwrap().km_sn, 2);
+        remove_test_wallet(wallet)?;
+        Ok(())
+    }
+
     #[test]
     fn test_decommission_pass() -> SolDidResult<()> {
         let mut wallet = build_test_wallet()?;
@@ -535,7 +940,8 @@ mod wallet_tests {
         let threshold = 1i8;
         let kset1 = PastaKeySet::new_for(count);
         let keys_name = "Franks First".to_string();
-        let (_signature, _prefix, _digest) = wallet.new_did(&keys_name, &kset1, threshold, None)?;
+        let (_signature, _prefix, _digest) =
+            wallet.new_did(&keys_name, &kset1, threshold, None, None)?;
         let new_first = wallet.keys.first().unwrap().prefix().to_string();
         assert_eq!(wallet.keys_for_prefix(&new_first)?.chain_event_len(), 1);
         // Decommission keys
@@ -546,4 +952,101 @@ mod wallet_tests {
         remove_test_wallet(wallet)?;
         Ok(())
     }
+
+    #[test]
+    /// Test keys written before relationships and delegators load and
+    /// are rewritten in the current version once changed
+    fn test_load_version_1_keys_pass() -> SolDidResult<()> {
+        let mut wallet = build_test_wallet()?;
+        let kset1 = PastaKeySet::new_for(2);
+        let keys_name = "Franks First".to_string();
+        let (_signature, prefix, _digest) = wallet.new_did(&keys_name, &kset1, 1, None, None)?;
+        // Rewrite the keys in the version 1 layout
+        let keys = wallet.keys_for_prefix(&prefix)?;
+        let legacy = KeysV1 {
+            name: keys.name().clone(),
+            prefix: keys.prefix().clone(),
+            account: keys.account().clone(),
+            threshold: keys.threshold(),
+            chain_events: keys
+                .chain_events()
+                .iter()
+                .map(|ce| ChainEventV1 {
+                    event_type: ce.event_type,
+                    time_stamp: ce.time_stamp,
+                    did_signature: ce.did_signature.clone(),
+                    km_sn: ce.km_sn,
+                    km_digest: ce.km_digest.clone(),
+                    km_keytype: ce.km_keytype,
+                    keysets: ce
+                        .keysets
+                        .iter()
+                        .map(|(block, keys)| {
+                            let key_state = match block {
+                                KeyBlock::NEXT => KeyState::NextRotation,
+                                _ => KeyState::Incepted,
+                            };
+                            let keys = keys
+                                .iter()
+                                .map(|k| KeyV1 {
+                                    key_state,
+                                    key_type: ce.km_keytype,
+                                    key: k.key(),
+                                })
+                                .collect();
+                            (block.clone(), keys)
+                        })
+                        .collect(),
+                })
+                .collect(),
+        };
+        let mut keys_file = wallet.full_path().parent().unwrap().to_path_buf();
+        keys_file.push(&prefix);
+        keys_file.push(KEYS_CONFIGURATION);
+        fs::write(&keys_file, legacy.try_to_vec()?)?;
+        // Load the version 1 keys
+        let mut wallet = build_test_wallet()?;
+        let keys = wallet.keys_for_prefix(&prefix)?;
+        assert_eq!(keys.name(), &keys_name);
+        assert_eq!(keys.delegator(), None);
+        assert_eq!(keys.threshold(), 1);
+        assert_eq!(keys.next_threshold(), 1);
+        assert_eq!(keys.current_private_keys()?, kset1.current_private_keys());
+        let current = keys.chain_events()[0].get_keys_for(KeyBlock::CURRENT)?;
+        assert!(current
+            .iter()
+            .all(|k| k.relationships() == ALL_RELATIONSHIPS));
+        // Changed keys are written in the current version
+        let mut barren_ks = PastaKeySet::new_empty();
+        wallet.rotate_did_with_name(keys_name, &mut barren_ks, None, None, None, None)?;
+        assert!(fs::read(&keys_file)?.starts_with(&KEYS_FILE_MAGIC));
+        let wallet = build_test_wallet()?;
+        let keys = wallet.keys_for_prefix(&prefix)?;
+        assert_eq!(keys.chain_event_len(), 2);
+        assert_eq!(keys.current_private_keys()?, kset1.next_private_keys());
+        remove_test_wallet(wallet)?;
+        Ok(())
+    }
+
+    #[test]
+    /// Test keys files of an unknown version are rejected
+    fn test_load_unknown_keys_version_fail() -> SolDidResult<()> {
+        let mut wallet = build_test_wallet()?;
+        let kset1 = PastaKeySet::new_for(2);
+        let keys_name = "Franks First".to_string();
+        let (_signature, prefix, _digest) = wallet.new_did(&keys_name, &kset1, 1, None, None)?;
+        let mut keys_folder = wallet.full_path().parent().unwrap().to_path_buf();
+        keys_folder.push(&prefix);
+        let mut keys_file = keys_folder.clone();
+        keys_file.push(KEYS_CONFIGURATION);
+        let mut data = fs::read(&keys_file)?;
+        data[KEYS_FILE_MAGIC.len()..KEYS_FILE_MAGIC.len() + 2].copy_from_slice(&3u16.to_le_bytes());
+        fs::write(&keys_file, data)?;
+        assert!(matches!(
+            Keys::load(&mut keys_folder),
+            Err(SolDidError::KeysVersionInvalid(3))
+        ));
+        remove_test_wallet(wallet)?;
+        Ok(())
+    }
 }
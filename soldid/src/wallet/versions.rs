@@ -0,0 +1,113 @@
+//! Historical keys file layouts
+//!
+//! Each layout is frozen as it was persisted under its version number
+//! and upgrades to the current layout. Keys files are read in any version
+//! and written in the current version, headed by the keys file magic and
+//! the version. Version 1 files predate the header
+
+use std::collections::HashMap;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use hbkr_rs::key_manage::Publickey;
+
+use super::{
+    chain_event::{ChainEvent, ChainEventType, KeyBlock},
+    generic_keys::{Key, Keys},
+    wallet_enums::{KeyState, KeyType},
+};
+use crate::errors::{SolDidError, SolDidResult};
+
+/// Leading bytes of versioned keys files. Version 1 files lead with the
+/// length of the keys name, which is never this large
+pub const KEYS_FILE_MAGIC: [u8; 4] = *b"SDMK";
+/// Version of the keys file layout written
+pub const CURRENT_KEYS_VERSION: u16 = 2;
+
+/// Version 1 key, without verification relationships
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
+pub struct KeyV1 {
+    pub key_state: KeyState,
+    pub key_type: KeyType,
+    pub key: String,
+}
+
+/// Version 1 chain event holding version 1 keys
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
+pub struct ChainEventV1 {
+    pub event_type: ChainEventType,
+    pub time_stamp: i64,
+    pub did_signature: String,
+    pub km_sn: u64,
+    pub km_digest: String,
+    pub km_keytype: KeyType,
+    pub keysets: HashMap<KeyBlock, Vec<KeyV1>>,
+}
+
+/// Version 1, keys without a delegator
+#[derive(BorshDeserialize, BorshSerialize, Debug)]
+pub struct KeysV1 {
+    pub name: String,
+    pub prefix: String,
+    pub account: Publickey,
+    pub threshold: i8,
+    pub chain_events: Vec<ChainEventV1>,
+}
+
+/// Keys in any of the persisted layouts
+#[derive(Debug)]
+pub enum KeysVersion {
+    V1(KeysV1),
+    V2(Keys),
+}
+
+impl KeysVersion {
+    /// Deserializes the keys file in the layout of its version
+    pub fn unpack(data: &[u8]) -> SolDidResult<Self> {
+        match data.strip_prefix(&KEYS_FILE_MAGIC[..]) {
+            Some(versioned) => {
+                let data = &mut &*versioned;
+                match u16::deserialize(data)? {
+                    2 => Ok(Self::V2(Keys::try_from_slice(*data)?)),
+                    version => Err(SolDidError::KeysVersionInvalid(version)),
+                }
+            }
+            None => Ok(Self::V1(KeysV1::try_from_slice(data)?)),
+        }
+    }
+
+    /// Upgrades the keys to the current layout
+    pub fn upgrade(self) -> Keys {
+        match self {
+            Self::V1(keys) => keys.into(),
+            Self::V2(keys) => keys,
+        }
+    }
+}
+
+/// Keys held every verification relationship
+impl From<KeyV1> for Key {
+    fn from(key: KeyV1) -> Self {
+        Key::new(key.key_state, key.key_type, &key.key)
+    }
+}
+
+impl ChainEventV1 {
+    /// Upgrades the chain event to the current layout, the threshold of
+    /// the next keys was held by the keys rather than the event
+    pub fn upgrade(self, next_threshold: u64) -> ChainEvent {
+        ChainEvent {
+            event_type: self.event_type,
+            time_stamp: self.time_stamp,
+            did_signature: self.did_signature,
+            km_sn: self.km_sn,
+            km_digest: self.km_digest,
+            km_keytype: self.km_keytype,
+            keysets: self
+                .keysets
+                .into_iter()
+                .map(|(block, keys)| (block, keys.into_iter().map(Key::from).collect()))
+                .collect(),
+            km_next_threshold: next_threshold,
+        }
+    }
+}
@@ -1,7 +1,9 @@
 //! Various enum types for wallet and keys
 
 use borsh::{BorshDeserialize, BorshSerialize};
-use hbkr_rs::basic::Basic;
+use hbkr_rs::{basic::Basic, key_manage::KeySet};
+use solana_did_method::instruction::SMDKeyType;
+
 #[derive(Debug, BorshDeserialize, BorshSerialize, Clone, Copy, Hash, Eq, PartialEq, PartialOrd)]
 pub enum KeyState {
     PreInception,
@@ -16,6 +18,7 @@ pub enum KeyState {
 pub enum KeyType {
     ED25519,
     PASTA,
+    SECP256K1,
 }
 
 impl Default for KeyType {
@@ -24,6 +27,12 @@ impl Default for KeyType {
     }
 }
 
+/// Keysets of the wallet know their key type, hbkr's `Basic` has no
+/// secp256k1 derivation to tell secp256k1 keysets by
+pub trait WalletKeySet: KeySet {
+    fn wallet_key_type(&self) -> KeyType;
+}
+
 impl From<Basic> for KeyType {
     fn from(basic_type: Basic) -> Self {
         match basic_type {
@@ -32,3 +41,13 @@ impl From<Basic> for KeyType {
         }
     }
 }
+
+impl From<KeyType> for SMDKeyType {
+    fn from(key_type: KeyType) -> Self {
+        match key_type {
+            KeyType::ED25519 => SMDKeyType::Ed25519,
+            KeyType::PASTA => SMDKeyType::PASTA,
+            KeyType::SECP256K1 => SMDKeyType::Secp256k1,
+        }
+    }
+}
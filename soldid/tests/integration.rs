@@ -1,8 +1,12 @@
 // #[cfg(test)]
 // mod tests {
 use borsh::BorshDeserialize;
-use hbkr_rs::key_manage::KeySet;
-use solana_did_method::{id, instruction::SDMInstruction};
+use hbkr_rs::{key_manage::KeySet, said::SelfAddressingPrefix};
+use solana_did_method::{
+    id,
+    instruction::{SDMInstruction, SMDKeyType},
+    state::SDMDid,
+};
 use solana_rpc::rpc::JsonRpcConfig;
 use solana_sdk::{
     // ed25519_instruction,
@@ -12,8 +16,10 @@ use solana_sdk::{
 use solana_test_validator::{TestValidator, TestValidatorGenesis};
 use soldid::{
     chain_trait::Chain,
+    ekey_wrap::Ed25519KeySet,
     errors::{SolDidError, SolDidResult},
     pkey_wrap::PastaKeySet,
+    skey_wrap::Secp256k1KeySet,
     solana_wrap::schain_wrap::SolanaChain,
     wallet::{load_wallet_from, Wallet},
 };
@@ -96,7 +102,7 @@ fn build_and_run_inception(
     let kset1 = PastaKeySet::new_for(key_count);
     assert!(!kset1.is_barren());
     let keys_name = "Franks First".to_string();
-    wallet.new_did(&keys_name, &kset1, key_threshold as i8, Some(vchain))
+    wallet.new_did(&keys_name, &kset1, key_threshold as i8, None, Some(vchain))
 }
 
 #[test]
@@ -112,7 +118,7 @@ fn test_wallet_test_location_pass() -> SolDidResult<()> {
 #[test]
 fn test_basic_test_chain_pass() -> SolDidResult<()> {
     let (test_validator, payer, _program_pk) = clean_ledger_setup_validator()?;
-    let mchain = SolanaChain::new(test_validator.get_rpc_client(), payer, None);
+    let mchain = SolanaChain::new(test_validator.get_rpc_client(), payer, Keypair::new(), None);
     let vchain = mchain.version();
     assert_eq!(vchain.major, 1);
     assert_eq!(vchain.minor, 11);
@@ -124,7 +130,7 @@ fn test_pasta_inception_pass() -> SolDidResult<()> {
     // Get the test validator running
     let (test_validator, payer, _program_pk) = clean_ledger_setup_validator()?;
     // Get the SolanaChain setup
-    let mchain = SolanaChain::new(test_validator.get_rpc_client(), payer, None);
+    let mchain = SolanaChain::new(test_validator.get_rpc_client(), payer, Keypair::new(), None);
     // Initialize an empty wallet
     let mut wallet = build_test_wallet()?;
     // Capture our programs log statements
@@ -155,7 +161,7 @@ fn test_pasta_rotation_pass() -> SolDidResult<()> {
     // Get the test validator running
     let (test_validator, payer, _program_pk) = clean_ledger_setup_validator()?;
     // Get the SolanaChain setup
-    let mchain = SolanaChain::new(test_validator.get_rpc_client(), payer, None);
+    let mchain = SolanaChain::new(test_validator.get_rpc_client(), payer, Keypair::new(), None);
     // Initialize an empty wallet
     let mut wallet = build_test_wallet()?;
 
@@ -176,6 +182,7 @@ fn test_pasta_rotation_pass() -> SolDidResult<()> {
             &mut barren_ks,
             None,
             None,
+            None,
             Some(&mchain),
         );
         assert!(result.is_ok());
@@ -189,7 +196,7 @@ fn test_pasta_decommission_pass() -> SolDidResult<()> {
     // Get the test validator running
     let (test_validator, payer, _program_pk) = clean_ledger_setup_validator()?;
     // Get the SolanaChain setup
-    let mchain = SolanaChain::new(test_validator.get_rpc_client(), payer, None);
+    let mchain = SolanaChain::new(test_validator.get_rpc_client(), payer, Keypair::new(), None);
     // Initialize an empty wallet
     let mut wallet = build_test_wallet()?;
     // Incept keys
@@ -212,4 +219,81 @@ fn test_pasta_decommission_pass() -> SolDidResult<()> {
     remove_test_wallet(wallet)?;
     Ok(())
 }
+#[test]
+fn test_secp256k1_inception_rotation_pass() -> SolDidResult<()> {
+    // Get the test validator running
+    let (test_validator, payer, _program_pk) = clean_ledger_setup_validator()?;
+    // Get the SolanaChain setup
+    let mchain = SolanaChain::new(test_validator.get_rpc_client(), payer, Keypair::new(), None);
+    // Initialize an empty wallet
+    let mut wallet = build_test_wallet()?;
+    // Incept secp256k1 keys, the program verifies the keys of the event
+    let kset1 = Secp256k1KeySet::new_for(2);
+    let keys_name = "Franks First".to_string();
+    let (_signature, prefix, _) = wallet.new_did(&keys_name, &kset1, 1, None, Some(&mchain))?;
+    sleep(Duration::from_secs(5));
+    let account = wallet.keys_for_prefix(&prefix)?.account().clone();
+    let did_key = Pubkey::new(&account.to_bytes());
+    let did = SDMDid::unpack(&mchain.get_did(&did_key).data)?;
+    assert_eq!(did.keytype(), SMDKeyType::Secp256k1);
+    // Rotate, the controllers sign in a secp256k1 instruction
+    let mut barren_ks = Secp256k1KeySet::new_empty();
+    wallet.rotate_did_with_prefix(prefix, &mut barren_ks, None, None, None, Some(&mchain))?;
+    sleep(Duration::from_secs(5));
+    let did = SDMDid::unpack(&mchain.get_did(&did_key).data)?;
+    assert_eq!(did.did_doc.sn, 1);
+    remove_test_wallet(wallet)?;
+    Ok(())
+}
+#[test]
+fn test_delegated_inception_rotation_pass() -> SolDidResult<()> {
+    // Get the test validator running
+    let (test_validator, payer, _program_pk) = clean_ledger_setup_validator()?;
+    // Get the SolanaChain setup, the delegator DID has its own authority
+    let delegator_authority = Keypair::new();
+    let mut mchain = SolanaChain::new(
+        test_validator.get_rpc_client(),
+        payer,
+        Keypair::from_bytes(&delegator_authority.to_bytes()).unwrap(),
+        None,
+    );
+    // Initialize an empty wallet
+    let mut wallet = build_test_wallet()?;
+    let parent_name = "Franks Parent".to_string();
+    let (_signature, parent, _) = wallet.new_did(
+        &parent_name,
+        &Ed25519KeySet::new_for(2),
+        2,
+        None,
+        Some(&mchain),
+    )?;
+    sleep(Duration::from_secs(5));
+    // The delegated DID is approved by the delegator's authority and
+    // controllers
+    mchain.set_authority(Keypair::new());
+    mchain.set_delegator_authority(delegator_authority);
+    let keys_name = "Franks Delegated".to_string();
+    let (_signature, prefix, _) = wallet.new_delegated_did(
+        &keys_name,
+        &parent_name,
+        &Ed25519KeySet::new_for(2),
+        1,
+        None,
+        Some(&mchain),
+    )?;
+    sleep(Duration::from_secs(5));
+    let account = wallet.keys_for_prefix(&prefix)?.account().clone();
+    let did_key = Pubkey::new(&account.to_bytes());
+    let did = SDMDid::unpack(&mchain.get_did(&did_key).data)?;
+    let delegator = SelfAddressingPrefix::from_str(&parent)?.digest;
+    assert_eq!(did.delegator().map(|d| d.to_vec()), Some(delegator));
+    // Delegated rotation is approved the same way
+    let mut barren_ks = Ed25519KeySet::new_empty();
+    wallet.rotate_did_with_prefix(prefix, &mut barren_ks, None, None, None, Some(&mchain))?;
+    sleep(Duration::from_secs(5));
+    let did = SDMDid::unpack(&mchain.get_did(&did_key).data)?;
+    assert_eq!(did.did_doc.sn, 1);
+    remove_test_wallet(wallet)?;
+    Ok(())
+}
 // }